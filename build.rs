@@ -0,0 +1,5 @@
+fn main() {
+    // The gRPC code is generated only for --features grpc, which needs protoc
+    #[cfg(feature = "grpc")]
+    tonic_build::compile_protos("proto/muonfp.proto").expect("Failed to compile proto/muonfp.proto");
+}
@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+use serde::Serialize;
+
+// Distinct values counted per table in one interval; new ones past this are
+// left out of the tables but still count towards `records`
+const SUMMARY_MAX_KEYS: usize = 65536;
+
+/// Settings from the `[summary]` section.
+#[derive(Clone)]
+pub struct SummarySettings {
+    pub interval: Duration,
+    /// Entries kept per table, busiest first
+    pub top: usize,
+}
+
+#[derive(Serialize)]
+pub struct Count<T: Serialize> {
+    pub value: T,
+    pub count: u64,
+}
+
+/// Body of a `fingerprint_summary` event.
+#[derive(Serialize)]
+pub struct FingerprintSummary {
+    /// Seconds covered by the summary
+    pub interval: u64,
+    pub records: u64,
+    pub fingerprints: Vec<Count<String>>,
+    pub ips: Vec<Count<IpAddr>>,
+    /// Destination ports of client SYNs
+    pub ports: Vec<Count<u16>>,
+}
+
+/// Counts fingerprints per value, IP and destination port so dashboards
+/// don't have to re-aggregate the raw records.
+pub struct Aggregator {
+    settings: SummarySettings,
+    started: Instant,
+    records: u64,
+    fingerprints: HashMap<String, u64>,
+    ips: HashMap<IpAddr, u64>,
+    ports: HashMap<u16, u64>,
+}
+
+impl Aggregator {
+    pub fn new(settings: SummarySettings) -> Self {
+        Aggregator {
+            settings,
+            started: Instant::now(),
+            records: 0,
+            fingerprints: HashMap::new(),
+            ips: HashMap::new(),
+            ports: HashMap::new(),
+        }
+    }
+
+    pub fn observe(&mut self, ip: IpAddr, fingerprint: &str, dst_port: Option<u16>) {
+        self.records += 1;
+        count(&mut self.fingerprints, fingerprint.to_string());
+        count(&mut self.ips, ip);
+        if let Some(port) = dst_port {
+            count(&mut self.ports, port);
+        }
+    }
+
+    /// Returns the summary once the interval has passed, or right away with
+    /// `force`, and starts a new one.
+    pub fn report(&mut self, force: bool) -> Option<FingerprintSummary> {
+        let elapsed = self.started.elapsed();
+        if !force && elapsed < self.settings.interval {
+            return None;
+        }
+        self.started = Instant::now();
+
+        let top = self.settings.top;
+        Some(FingerprintSummary {
+            interval: elapsed.as_secs(),
+            records: std::mem::take(&mut self.records),
+            fingerprints: busiest(std::mem::take(&mut self.fingerprints), top),
+            ips: busiest(std::mem::take(&mut self.ips), top),
+            ports: busiest(std::mem::take(&mut self.ports), top),
+        })
+    }
+}
+
+fn busiest<T: Serialize + Hash + Eq>(counts: HashMap<T, u64>, top: usize) -> Vec<Count<T>> {
+    let mut counts: Vec<Count<T>> = counts.into_iter().map(|(value, count)| Count { value, count }).collect();
+    counts.sort_by(|a, b| b.count.cmp(&a.count));
+    counts.truncate(top);
+    counts
+}
+
+fn count<T: Hash + Eq>(counts: &mut HashMap<T, u64>, key: T) {
+    if counts.len() >= SUMMARY_MAX_KEYS && !counts.contains_key(&key) {
+        return;
+    }
+    *counts.entry(key).or_default() += 1;
+}
@@ -0,0 +1,57 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+use serde::Serialize;
+
+// Hosts remembered before the cache is reset
+const ANOMALY_MAX_HOSTS: usize = 65536;
+
+struct LastSeen {
+    fingerprint: String,
+    at: Instant,
+}
+
+/// Body of an `anomaly` event.
+#[derive(Serialize)]
+pub struct FingerprintChange {
+    pub ip_address: IpAddr,
+    pub role: &'static str,
+    pub previous_fingerprint: String,
+    pub fingerprint: String,
+    /// Seconds since the previous fingerprint was last seen
+    pub previous_age: u64,
+}
+
+/// Remembers the last fingerprint of every IP and reports when a host that
+/// was seen recently suddenly presents a different one (spoofing, a replaced
+/// device or a proxy).
+pub struct AnomalyDetector {
+    ttl: Duration,
+    last_seen: HashMap<(IpAddr, &'static str), LastSeen>,
+}
+
+impl AnomalyDetector {
+    pub fn new(ttl: Duration) -> Self {
+        AnomalyDetector { ttl, last_seen: HashMap::new() }
+    }
+
+    /// Client and server fingerprints of one IP are tracked separately.
+    pub fn observe(&mut self, ip: IpAddr, role: &'static str, fingerprint: &str) -> Option<FingerprintChange> {
+        if self.last_seen.len() >= ANOMALY_MAX_HOSTS && !self.last_seen.contains_key(&(ip, role)) {
+            self.last_seen.clear();
+        }
+        let current = LastSeen { fingerprint: fingerprint.to_string(), at: Instant::now() };
+        let previous = self.last_seen.insert((ip, role), current)?;
+        let age = previous.at.elapsed();
+        if previous.fingerprint == fingerprint || age > self.ttl {
+            return None;
+        }
+        Some(FingerprintChange {
+            ip_address: ip,
+            role,
+            previous_fingerprint: previous.fingerprint,
+            fingerprint: fingerprint.to_string(),
+            previous_age: age.as_secs(),
+        })
+    }
+}
@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+use std::fs;
+use std::net::IpAddr;
+use std::path::Path;
+use maxminddb::{geoip2, Reader};
+
+/// Maps addresses to the autonomous system announcing them.
+pub enum AsnDb {
+    /// A MaxMind GeoLite2-ASN (or compatible) database
+    Mmdb(Reader<Vec<u8>>),
+    /// A pyasn `ipasn` file, optionally paired with its `asnames.json`
+    Prefixes {
+        // One table per prefix length, keyed by the masked network address
+        networks: Vec<HashMap<u32, u32>>,
+        names: HashMap<u32, String>,
+    },
+}
+
+impl AsnDb {
+    /// Opens `path` as an MMDB when it ends in `.mmdb`, otherwise as a pyasn
+    /// prefix file with AS names from `names`.
+    pub fn load(path: &Path, names: Option<&Path>) -> Result<AsnDb, String> {
+        if path.extension().map_or(false, |extension| extension == "mmdb") {
+            let reader = Reader::open_readfile(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+            return Ok(AsnDb::Mmdb(reader));
+        }
+
+        let text = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let mut networks = vec![HashMap::new(); 33];
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+                continue;
+            }
+            let parsed = line.split_once(char::is_whitespace).and_then(|(prefix, asn)| {
+                let (network, length) = prefix.split_once('/')?;
+                let network: std::net::Ipv4Addr = network.parse().ok()?;
+                let length: usize = length.parse().ok().filter(|&length| length <= 32)?;
+                Some((u32::from(network) & mask(length), length, asn.trim().parse::<u32>().ok()?))
+            });
+            let Some((network, length, asn)) = parsed else {
+                return Err(format!("{}:{}: expected `prefix/length asn`", path.display(), number + 1));
+            };
+            networks[length].insert(network, asn);
+        }
+
+        let names = match names {
+            Some(names) => {
+                let text = fs::read_to_string(names).map_err(|e| format!("Failed to read {}: {}", names.display(), e))?;
+                let entries: HashMap<String, String> = serde_json::from_str(&text)
+                    .map_err(|e| format!("{}: {}", names.display(), e))?;
+                entries.into_iter().filter_map(|(asn, name)| Some((asn.parse().ok()?, name))).collect()
+            }
+            None => HashMap::new(),
+        };
+        Ok(AsnDb::Prefixes { networks, names })
+    }
+
+    /// Returns the AS number and organization announcing `ip`.
+    pub fn lookup(&self, ip: IpAddr) -> Option<(u32, Option<String>)> {
+        match self {
+            AsnDb::Mmdb(reader) => {
+                let asn: geoip2::Asn = reader.lookup(ip).ok()?;
+                Some((asn.autonomous_system_number?, asn.autonomous_system_organization.map(str::to_string)))
+            }
+            AsnDb::Prefixes { networks, names } => {
+                let IpAddr::V4(ip) = ip else { return None };
+                let address = u32::from(ip);
+                // Longest prefix first
+                let asn = (0..=32).rev().find_map(|length| networks[length].get(&(address & mask(length))))?;
+                Some((*asn, names.get(asn).cloned()))
+            }
+        }
+    }
+}
+
+fn mask(length: usize) -> u32 {
+    if length == 0 { 0 } else { u32::MAX << (32 - length) }
+}
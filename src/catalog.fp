@@ -0,0 +1,71 @@
+; Built-in muonfp catalog of well-known stacks and scanning tools.
+;
+; Same layout as a p0f.fp database: a label followed by its signatures, where
+; each sig is a muonfp fingerprint (window:options:mss:scale, * matches any
+; field). Labels with the `!` class are tools rather than operating systems.
+;
+; Replace it with `catalog = /path/to/file` in the [fingerprints] section.
+
+[tcp:request]
+
+label = s:unix:Linux:3.x-6.x
+sig   = 64240:2-4-8-1-3:1460:7
+sig   = 29200:2-4-8-1-3:1460:7
+sig   = 65535:2-4-8-1-3:1460:7
+sig   = 43690:2-4-8-1-3:65495:7
+
+label = s:unix:Android:
+sig   = 65535:2-4-8-1-3:1460:8
+sig   = 65535:2-4-8-1-3:1460:9
+
+label = s:win:Windows:10/11
+sig   = 64240:2-1-3-1-1-4:1460:8
+sig   = 65535:2-1-3-1-1-4:1460:8
+
+label = s:win:Windows:7/8
+sig   = 8192:2-1-3-1-1-4:1460:8
+sig   = 8192:2-1-3-1-1-4:1460:2
+
+label = s:unix:macOS:
+sig   = 65535:2-1-3-1-1-8-4-0:1460:6
+sig   = 65535:2-1-3-1-1-8-4-0:1460:5
+
+label = s:unix:iOS:
+sig   = 65535:2-1-3-1-1-8-4-0:1440:6
+
+label = s:unix:FreeBSD:
+sig   = 65535:2-1-3-4-8:1460:6
+sig   = 65535:2-1-3-4-8:1460:9
+
+; masscan and nmap -sS both send a bare MSS of 1460; nmap also picks 2048-4096
+label = s:!:masscan:
+sig   = 1024:::
+
+label = s:!:masscan or nmap:SYN scan
+sig   = 1024:2:1460:
+
+label = s:!:nmap:SYN scan
+sig   = 2048:2:1460:
+sig   = 3072:2:1460:
+sig   = 4096:2:1460:
+
+label = s:!:zmap:
+sig   = 65535:::
+sig   = 65535:2:1460:
+
+; Shodan's and Censys' crawlers (ZGrab) connect through the stock Linux stack
+; and are only told apart from ordinary Linux clients by their source ranges.
+
+[tcp:response]
+
+label = s:unix:Linux:3.x-6.x
+sig   = 65160:2-4-8-1-3:1460:7
+sig   = 28960:2-4-8-1-3:1460:7
+sig   = 65483:2-4-8-1-3:65495:7
+
+label = s:win:Windows:
+sig   = 65535:2-1-3-1-1-4:1460:8
+sig   = 8192:2-1-3-1-1-4:1460:8
+
+label = s:unix:FreeBSD:
+sig   = 65535:2-1-3-4-8:1460:6
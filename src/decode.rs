@@ -0,0 +1,214 @@
+use std::net::{IpAddr, Ipv4Addr};
+
+const ETHERNET_HEADER_LEN: usize = 14;
+const VLAN_TAG_LEN: usize = 4;
+const GRE_HEADER_LEN: usize = 4;
+const ERSPAN_II_HEADER_LEN: usize = 8;
+const ERSPAN_III_HEADER_LEN: usize = 12;
+const ERSPAN_III_SUBHEADER_LEN: usize = 8;
+const UDP_HEADER_LEN: usize = 8;
+const VXLAN_HEADER_LEN: usize = 8;
+const GENEVE_HEADER_LEN: usize = 8;
+const MPLS_LABEL_LEN: usize = 4;
+const PW_CONTROL_WORD_LEN: usize = 4;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+const ETHERTYPE_QINQ: u16 = 0x88a8;
+const ETHERTYPE_QINQ_LEGACY: u16 = 0x9100;
+const ETHERTYPE_TRANSPARENT_BRIDGING: u16 = 0x6558;
+const ETHERTYPE_ERSPAN_II: u16 = 0x88be;
+const ETHERTYPE_ERSPAN_III: u16 = 0x22eb;
+const ETHERTYPE_MPLS_UNICAST: u16 = 0x8847;
+const ETHERTYPE_MPLS_MULTICAST: u16 = 0x8848;
+
+const IP_PROTOCOL_UDP: u8 = 17;
+const IP_PROTOCOL_GRE: u8 = 47;
+
+const VXLAN_PORT: u16 = 4789;
+const VXLAN_VNI_PRESENT: u8 = 0x08;
+const GENEVE_PORT: u16 = 6081;
+
+const GRE_CHECKSUM_PRESENT: u16 = 0x8000;
+const GRE_KEY_PRESENT: u16 = 0x2000;
+const GRE_SEQUENCE_PRESENT: u16 = 0x1000;
+
+// Bounds nested tunnels so a crafted packet can't recurse indefinitely
+const MAX_TUNNEL_DEPTH: usize = 4;
+
+/// Details collected while peeling link-layer headers off a frame.
+#[derive(Default)]
+pub struct Encapsulation {
+    /// VLAN IDs from outermost to innermost tag
+    pub vlan_ids: Vec<u16>,
+    /// Outer source address of the first tunnel the packet arrived through
+    pub tunnel_source: Option<IpAddr>,
+    /// Virtual network identifier of the innermost overlay
+    pub vni: Option<u32>,
+}
+
+impl Encapsulation {
+    /// The innermost (customer) VLAN ID, if the frame was tagged.
+    pub fn vlan_id(&self) -> Option<u16> {
+        self.vlan_ids.last().copied()
+    }
+
+    /// The outermost (service) VLAN ID of a QinQ frame.
+    pub fn outer_vlan_id(&self) -> Option<u16> {
+        if self.vlan_ids.len() > 1 {
+            self.vlan_ids.first().copied()
+        } else {
+            None
+        }
+    }
+
+    pub fn is_tunneled(&self) -> bool {
+        self.tunnel_source.is_some()
+    }
+}
+
+/// Returns the innermost IPv4 packet carried by an Ethernet frame, skipping
+/// VLAN tags and decapsulating GRE/ERSPAN, VXLAN and Geneve tunnels along the way.
+pub fn ipv4_payload<'a>(frame: &'a [u8], encap: &mut Encapsulation) -> Option<&'a [u8]> {
+    let ip = ethernet_ipv4(frame, encap)?;
+    decapsulate(ip, encap, 0)
+}
+
+// Walks the ethertype chain of an Ethernet frame, skipping 802.1Q tags,
+// stacked 802.1ad S-tags and MPLS label stacks, and returns the IPv4 packet it carries.
+fn ethernet_ipv4<'a>(frame: &'a [u8], encap: &mut Encapsulation) -> Option<&'a [u8]> {
+    if frame.len() < ETHERNET_HEADER_LEN {
+        return None;
+    }
+    let mut ethertype = u16::from_be_bytes([frame[12], frame[13]]);
+    let mut offset = ETHERNET_HEADER_LEN;
+
+    while matches!(ethertype, ETHERTYPE_VLAN | ETHERTYPE_QINQ | ETHERTYPE_QINQ_LEGACY) {
+        let tag = frame.get(offset..offset + VLAN_TAG_LEN)?;
+        encap.vlan_ids.push(u16::from_be_bytes([tag[0], tag[1]]) & 0x0fff);
+        ethertype = u16::from_be_bytes([tag[2], tag[3]]);
+        offset += VLAN_TAG_LEN;
+    }
+
+    match ethertype {
+        ETHERTYPE_IPV4 => Some(&frame[offset..]),
+        ETHERTYPE_MPLS_UNICAST | ETHERTYPE_MPLS_MULTICAST => mpls_inner(&frame[offset..], encap),
+        _ => None,
+    }
+}
+
+// Skips an MPLS label stack. MPLS carries no payload type, so the first
+// nibble after the bottom label decides: 4 is IPv4, 0 is a pseudowire
+// control word followed by an Ethernet frame.
+fn mpls_inner<'a>(mpls: &'a [u8], encap: &mut Encapsulation) -> Option<&'a [u8]> {
+    let mut offset = 0;
+    loop {
+        let label = mpls.get(offset..offset + MPLS_LABEL_LEN)?;
+        offset += MPLS_LABEL_LEN;
+        if label[2] & 0x01 != 0 {
+            break;
+        }
+    }
+    let payload = &mpls[offset..];
+    match payload.first()? >> 4 {
+        4 => Some(payload),
+        0 => ethernet_ipv4(payload.get(PW_CONTROL_WORD_LEN..)?, encap),
+        _ => None,
+    }
+}
+
+// Strips tunnel headers from `ip` until a non-tunnel IPv4 packet is reached.
+fn decapsulate<'a>(ip: &'a [u8], encap: &mut Encapsulation, depth: usize) -> Option<&'a [u8]> {
+    if depth >= MAX_TUNNEL_DEPTH || ip.len() < 20 {
+        return Some(ip);
+    }
+    let header_len = ((ip[0] & 0x0f) as usize) * 4;
+    let payload = ip.get(header_len..)?;
+    let source = IpAddr::V4(Ipv4Addr::new(ip[12], ip[13], ip[14], ip[15]));
+
+    let inner = match ip[9] {
+        IP_PROTOCOL_GRE => gre_inner(payload, encap)?,
+        IP_PROTOCOL_UDP => match udp_tunnel_inner(payload, encap) {
+            Some(inner) => inner,
+            None => return Some(ip),
+        },
+        _ => return Some(ip),
+    };
+    encap.tunnel_source.get_or_insert(source);
+    decapsulate(inner, encap, depth + 1)
+}
+
+// Parses a GRE header (and any ERSPAN header behind it) and returns the
+// inner IPv4 packet.
+fn gre_inner<'a>(gre: &'a [u8], encap: &mut Encapsulation) -> Option<&'a [u8]> {
+    if gre.len() < GRE_HEADER_LEN {
+        return None;
+    }
+    let flags = u16::from_be_bytes([gre[0], gre[1]]);
+    let protocol = u16::from_be_bytes([gre[2], gre[3]]);
+
+    let mut offset = GRE_HEADER_LEN;
+    for optional_field in [GRE_CHECKSUM_PRESENT, GRE_KEY_PRESENT, GRE_SEQUENCE_PRESENT] {
+        if flags & optional_field != 0 {
+            offset += 4;
+        }
+    }
+    let payload = gre.get(offset..)?;
+
+    match protocol {
+        ETHERTYPE_IPV4 => Some(payload),
+        ETHERTYPE_TRANSPARENT_BRIDGING => ethernet_ipv4(payload, encap),
+        // ERSPAN type I has no header of its own and is sent without a sequence number
+        ETHERTYPE_ERSPAN_II if flags & GRE_SEQUENCE_PRESENT == 0 => ethernet_ipv4(payload, encap),
+        ETHERTYPE_ERSPAN_II => ethernet_ipv4(payload.get(ERSPAN_II_HEADER_LEN..)?, encap),
+        ETHERTYPE_ERSPAN_III => {
+            let header = payload.get(..ERSPAN_III_HEADER_LEN)?;
+            // The O bit announces an optional platform specific subheader
+            let header_len = if header[11] & 0x01 != 0 {
+                ERSPAN_III_HEADER_LEN + ERSPAN_III_SUBHEADER_LEN
+            } else {
+                ERSPAN_III_HEADER_LEN
+            };
+            ethernet_ipv4(payload.get(header_len..)?, encap)
+        }
+        _ => None,
+    }
+}
+
+// Returns the inner IPv4 packet when the UDP datagram is a known overlay
+// encapsulation, or `None` for ordinary UDP traffic.
+fn udp_tunnel_inner<'a>(udp: &'a [u8], encap: &mut Encapsulation) -> Option<&'a [u8]> {
+    if udp.len() < UDP_HEADER_LEN {
+        return None;
+    }
+    let destination_port = u16::from_be_bytes([udp[2], udp[3]]);
+    let payload = &udp[UDP_HEADER_LEN..];
+
+    match destination_port {
+        VXLAN_PORT => {
+            let header = payload.get(..VXLAN_HEADER_LEN)?;
+            if header[0] & VXLAN_VNI_PRESENT == 0 {
+                return None;
+            }
+            encap.vni = Some(u32::from_be_bytes([0, header[4], header[5], header[6]]));
+            ethernet_ipv4(&payload[VXLAN_HEADER_LEN..], encap)
+        }
+        GENEVE_PORT => {
+            let header = payload.get(..GENEVE_HEADER_LEN)?;
+            // Version must be 0; the low 6 bits give the option length in 4-byte words
+            if header[0] >> 6 != 0 {
+                return None;
+            }
+            let header_len = GENEVE_HEADER_LEN + ((header[0] & 0x3f) as usize) * 4;
+            let protocol = u16::from_be_bytes([header[2], header[3]]);
+            encap.vni = Some(u32::from_be_bytes([0, header[4], header[5], header[6]]));
+            let inner = payload.get(header_len..)?;
+            match protocol {
+                ETHERTYPE_TRANSPARENT_BRIDGING => ethernet_ipv4(inner, encap),
+                ETHERTYPE_IPV4 => Some(inner),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
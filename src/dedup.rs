@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+use serde::Serialize;
+
+// Pairs remembered before expired entries are evicted
+const DEDUP_MAX_ENTRIES: usize = 262144;
+
+/// Settings from the `[dedup]` section.
+#[derive(Clone)]
+pub struct DedupSettings {
+    /// How long an (IP, fingerprint) pair is suppressed after it was written
+    pub ttl: Duration,
+    /// How often `duplicate_summary` records are emitted
+    pub interval: Duration,
+}
+
+struct Entry {
+    written_at: Instant,
+    suppressed: u64,
+}
+
+/// Body of a `duplicate_summary` event.
+#[derive(Serialize)]
+pub struct DuplicateSummary {
+    pub ip_address: IpAddr,
+    pub muonfp_fingerprint: String,
+    /// Records suppressed since the last summary
+    pub count: u64,
+}
+
+/// Writes each (IP, fingerprint) pair once per TTL and counts the repeats,
+/// so a single scan doesn't produce thousands of identical records.
+pub struct Deduplicator {
+    settings: DedupSettings,
+    entries: HashMap<(IpAddr, String), Entry>,
+    last_report: Instant,
+}
+
+impl Deduplicator {
+    pub fn new(settings: DedupSettings) -> Self {
+        Deduplicator { settings, entries: HashMap::new(), last_report: Instant::now() }
+    }
+
+    /// Returns whether the record should be written.
+    pub fn observe(&mut self, ip: IpAddr, fingerprint: &str) -> bool {
+        let ttl = self.settings.ttl;
+        if let Some(entry) = self.entries.get_mut(&(ip, fingerprint.to_string())) {
+            if entry.written_at.elapsed() <= ttl {
+                entry.suppressed += 1;
+                return false;
+            }
+            entry.written_at = Instant::now();
+            return true;
+        }
+
+        if self.entries.len() >= DEDUP_MAX_ENTRIES {
+            self.entries.retain(|_, entry| entry.written_at.elapsed() <= ttl);
+            if self.entries.len() >= DEDUP_MAX_ENTRIES {
+                self.entries.clear();
+            }
+        }
+        self.entries.insert((ip, fingerprint.to_string()), Entry { written_at: Instant::now(), suppressed: 0 });
+        true
+    }
+
+    /// Once per interval, or right away with `force`, returns the repeat
+    /// counts since the last summary and forgets pairs whose TTL has passed.
+    pub fn report(&mut self, force: bool) -> Vec<DuplicateSummary> {
+        if !force && self.last_report.elapsed() < self.settings.interval {
+            return Vec::new();
+        }
+        self.last_report = Instant::now();
+
+        let mut summaries = Vec::new();
+        for ((ip, fingerprint), entry) in self.entries.iter_mut() {
+            if entry.suppressed > 0 {
+                summaries.push(DuplicateSummary {
+                    ip_address: *ip,
+                    muonfp_fingerprint: fingerprint.clone(),
+                    count: entry.suppressed,
+                });
+                entry.suppressed = 0;
+            }
+        }
+        let ttl = self.settings.ttl;
+        self.entries.retain(|_, entry| entry.written_at.elapsed() <= ttl);
+        summaries
+    }
+}
@@ -0,0 +1,96 @@
+use std::net::Ipv4Addr;
+use serde::Serialize;
+
+const BOOTREQUEST: u8 = 1;
+const MAGIC_COOKIE: [u8; 4] = [0x63, 0x82, 0x53, 0x63];
+const OPTIONS_OFFSET: usize = 240;
+
+const OPTION_PAD: u8 = 0;
+const OPTION_HOSTNAME: u8 = 12;
+const OPTION_REQUESTED_IP: u8 = 50;
+const OPTION_MESSAGE_TYPE: u8 = 53;
+const OPTION_PARAMETER_REQUEST_LIST: u8 = 55;
+const OPTION_VENDOR_CLASS: u8 = 60;
+const OPTION_END: u8 = 255;
+
+/// Body of a `dhcp` event, describing a client's Discover or Request.
+#[derive(Serialize)]
+pub struct DhcpFingerprint {
+    pub mac: String,
+    /// `discover` or `request`
+    pub message_type: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_ip: Option<Ipv4Addr>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requested_ip: Option<Ipv4Addr>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vendor_class: Option<String>,
+    /// Option 55 as a comma separated list, the classic DHCP fingerprint
+    pub parameter_request_list: String,
+    /// Every option code in the order the client sent them
+    pub options: String,
+}
+
+/// Parses a DHCP Discover or Request from a UDP payload sent to port 67.
+pub fn parse(message: &[u8]) -> Option<DhcpFingerprint> {
+    if message.first() != Some(&BOOTREQUEST) || message.get(236..OPTIONS_OFFSET)? != MAGIC_COOKIE {
+        return None;
+    }
+    let hardware_length = (message[2] as usize).min(16);
+    let mac = message[28..28 + hardware_length].iter().map(|byte| format!("{:02x}", byte)).collect::<Vec<_>>().join(":");
+    let client_ip = Ipv4Addr::new(message[12], message[13], message[14], message[15]);
+
+    let mut fingerprint = DhcpFingerprint {
+        mac,
+        message_type: "",
+        client_ip: (!client_ip.is_unspecified()).then_some(client_ip),
+        requested_ip: None,
+        hostname: None,
+        vendor_class: None,
+        parameter_request_list: String::new(),
+        options: String::new(),
+    };
+
+    let mut codes = Vec::new();
+    let mut options = &message[OPTIONS_OFFSET..];
+    while let Some((&code, rest)) = options.split_first() {
+        if code == OPTION_END {
+            break;
+        }
+        if code == OPTION_PAD {
+            options = rest;
+            continue;
+        }
+        let (&length, rest) = rest.split_first()?;
+        let value = rest.get(..length as usize)?;
+        options = &rest[length as usize..];
+        codes.push(code.to_string());
+
+        match code {
+            OPTION_MESSAGE_TYPE => {
+                fingerprint.message_type = match value.first() {
+                    Some(1) => "discover",
+                    Some(3) => "request",
+                    _ => return None,
+                };
+            }
+            OPTION_HOSTNAME => fingerprint.hostname = Some(String::from_utf8_lossy(value).into_owned()),
+            OPTION_VENDOR_CLASS => fingerprint.vendor_class = Some(String::from_utf8_lossy(value).into_owned()),
+            OPTION_REQUESTED_IP if value.len() == 4 => {
+                fingerprint.requested_ip = Some(Ipv4Addr::new(value[0], value[1], value[2], value[3]));
+            }
+            OPTION_PARAMETER_REQUEST_LIST => {
+                fingerprint.parameter_request_list = value.iter().map(|code| code.to_string()).collect::<Vec<_>>().join(",");
+            }
+            _ => {}
+        }
+    }
+
+    if fingerprint.message_type.is_empty() {
+        return None;
+    }
+    fingerprint.options = codes.join(",");
+    Some(fingerprint)
+}
@@ -0,0 +1,72 @@
+use std::net::IpAddr;
+use serde::Serialize;
+
+// Longest name worth decoding, per RFC 1035
+const DNS_MAX_NAME_LENGTH: usize = 255;
+
+/// Body of a `dns` record, one query a local host sent to a resolver.
+#[derive(Serialize)]
+pub struct DnsQuery {
+    pub ip_address: IpAddr,
+    pub resolver: IpAddr,
+    pub qname: String,
+    pub qtype: String,
+}
+
+/// Parses the question of a DNS query sent to port 53, returning the name
+/// and record type asked for.
+pub fn parse_query(message: &[u8]) -> Option<(String, String)> {
+    // Queries only (QR clear, opcode 0) with at least one question
+    let flags = u16::from_be_bytes([*message.get(2)?, *message.get(3)?]);
+    let questions = u16::from_be_bytes([*message.get(4)?, *message.get(5)?]);
+    if flags & 0xf800 != 0 || questions == 0 {
+        return None;
+    }
+
+    let mut labels = Vec::new();
+    let mut length = 0;
+    let mut offset = 12;
+    loop {
+        let label_length = *message.get(offset)? as usize;
+        offset += 1;
+        if label_length == 0 {
+            break;
+        }
+        // Compression pointers don't appear in the first question
+        if label_length > 63 {
+            return None;
+        }
+        let label = message.get(offset..offset + label_length)?;
+        length += label_length + 1;
+        if length > DNS_MAX_NAME_LENGTH {
+            return None;
+        }
+        labels.push(String::from_utf8_lossy(label).to_lowercase());
+        offset += label_length;
+    }
+    let qname = if labels.is_empty() { ".".to_string() } else { labels.join(".") };
+    let qtype = u16::from_be_bytes([*message.get(offset)?, *message.get(offset + 1)?]);
+    Some((qname, type_name(qtype)))
+}
+
+fn type_name(qtype: u16) -> String {
+    let name = match qtype {
+        1 => "A",
+        2 => "NS",
+        5 => "CNAME",
+        6 => "SOA",
+        12 => "PTR",
+        15 => "MX",
+        16 => "TXT",
+        28 => "AAAA",
+        33 => "SRV",
+        35 => "NAPTR",
+        43 => "DS",
+        48 => "DNSKEY",
+        64 => "SVCB",
+        65 => "HTTPS",
+        255 => "ANY",
+        _ => return format!("TYPE{}", qtype),
+    };
+    name.to_string()
+}
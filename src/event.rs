@@ -0,0 +1,40 @@
+use std::net::IpAddr;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+
+/// A record other than a fingerprint (alerts, summaries) that shares the
+/// fingerprint output. `event_type` tells them apart from fingerprints.
+#[derive(Serialize)]
+struct Event<'a, T: Serialize> {
+    event_type: &'static str,
+    hostname: &'a str,
+    sensor_id: &'a str,
+    interface: &'a str,
+    timestamp: DateTime<Utc>,
+    #[serde(flatten)]
+    body: T,
+}
+
+/// Wraps `body` with the common event fields.
+pub fn event(event_type: &'static str, hostname: &str, sensor_id: &str, interface: &str, body: impl Serialize) -> Value {
+    let event = Event {
+        event_type,
+        hostname,
+        sensor_id,
+        interface,
+        timestamp: Utc::now(),
+        body,
+    };
+    serde_json::to_value(event).unwrap_or(Value::Null)
+}
+
+/// Event body for something seen in a single packet, e.g. a QUIC Initial.
+#[derive(Serialize)]
+pub struct PacketDetails<T: Serialize> {
+    pub ip_address: IpAddr,
+    pub src_port: u16,
+    pub dst_port: u16,
+    #[serde(flatten)]
+    pub details: T,
+}
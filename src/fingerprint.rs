@@ -1,14 +1,105 @@
+use std::collections::HashMap;
 use std::net::IpAddr;
 use pnet::packet::tcp::TcpFlags;
 use chrono::{DateTime, Utc};
 use serde::Serialize;
 
+/// Version of the record layout. Within a version fields are only added,
+/// never renamed, retyped or removed; anything else bumps it.
+pub const SCHEMA_VERSION: u32 = 1;
+/// Version of the `muonfp_fingerprint` string. Bumped whenever the same
+/// SYN would produce a different string, so old and new ones aren't compared.
+pub const FINGERPRINT_VERSION: u32 = 1;
+
+/// Sender address and port, then receiver address and port, of a TCP segment.
+pub type Connection = (IpAddr, u16, IpAddr, u16);
+
 #[derive(Serialize)]
 pub struct Fingerprint {
+    pub schema_version: u32,
+    pub fingerprint_version: u32,
     pub hostname: String,
+    pub sensor_id: String,
+    pub interface: String,
     pub timestamp: DateTime<Utc>,
     pub ip_address: IpAddr,
     pub muonfp_fingerprint: String,
+    pub src_port: u16,
+    pub dst_port: u16,
+    pub packet_type: &'static str,
+    /// "incoming" or "outgoing" relative to the sensor, "mirrored" for tunneled copies
+    pub direction: &'static str,
+    /// "client_syn" or "server_synack"; the two have different option layouts
+    pub role: &'static str,
+    pub seq: u32,
+    pub ttl: u8,
+    pub initial_ttl: u8,
+    pub ece: bool,
+    pub cwr: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vlan_id: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outer_vlan_id: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tunnel_source: Option<IpAddr>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vni: Option<u32>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub fragmented: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ip_options: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub df: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ip_id: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tsval: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tsecr: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub options_hex: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub options_detail: Option<Vec<TcpOptionDetail>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mptcp_subtype: Option<u8>,
+    pub tfo: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tfo_cookie_length: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub window_mss_ratio: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ja4t: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ja4ts: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub p0f: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub os_label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub os_class: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub os_match_quality: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub os_guess: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_guess: Option<String>,
+    /// Sender's MAC, for hosts on the capture segment
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mac: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mac_vendor: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub asn: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub as_org: Option<String>,
+    /// PTR name, once the background resolver has looked it up
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ptr: Option<String>,
+    /// Threat feeds listing the IP
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub threat_feeds: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<&'static str>,
 }
 
 impl Fingerprint {
@@ -21,29 +112,162 @@ impl Fingerprint {
             window_scale
         );
         Fingerprint {
+            schema_version: SCHEMA_VERSION,
+            fingerprint_version: FINGERPRINT_VERSION,
+            sensor_id: hostname.clone(),
+            interface: String::new(),
             hostname,
             timestamp: Utc::now(),
             ip_address: ip,
             muonfp_fingerprint,
+            src_port: 0,
+            dst_port: 0,
+            packet_type: "syn",
+            direction: "incoming",
+            role: "client_syn",
+            seq: 0,
+            ttl: 0,
+            initial_ttl: 0,
+            ece: false,
+            cwr: false,
+            vlan_id: None,
+            outer_vlan_id: None,
+            tunnel_source: None,
+            vni: None,
+            fragmented: false,
+            ip_options: None,
+            df: None,
+            ip_id: None,
+            tsval: None,
+            tsecr: None,
+            options_hex: None,
+            options_detail: None,
+            mptcp_subtype: None,
+            tfo: false,
+            tfo_cookie_length: None,
+            window_mss_ratio: None,
+            ja4t: None,
+            ja4ts: None,
+            p0f: None,
+            os_label: None,
+            os_class: None,
+            os_match_quality: None,
+            os_guess: None,
+            tool_guess: None,
+            mac: None,
+            mac_vendor: None,
+            asn: None,
+            as_org: None,
+            ptr: None,
+            threat_feeds: Vec::new(),
+            tags: Vec::new(),
         }
     }
 
     pub fn to_json(&self) -> String {
         serde_json::to_string(self).unwrap_or_else(|_| String::from("{}"))
     }
+
+    pub fn to_value(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap_or(serde_json::Value::Null)
+    }
+}
+
+/// Returns the TCP header (options included) of an IPv4 packet. IHL, total
+/// length and data offset are validated so inconsistent headers are rejected
+/// instead of being misparsed.
+pub fn tcp_header(ip: &[u8]) -> Option<&[u8]> {
+    let ip_header_length = ((ip.first()? & 0x0f) as usize) * 4;
+    if ip_header_length < 20 || ip.len() < ip_header_length {
+        return None;
+    }
+    // Frames may carry link-layer padding beyond the total length, while
+    // offloaded packets captured on the sending host report a total length of 0
+    let total_length = u16::from_be_bytes([ip[2], ip[3]]) as usize;
+    let end = if total_length == 0 { ip.len() } else { total_length.min(ip.len()) };
+    if end < ip_header_length + 20 {
+        return None;
+    }
+    let tcp = &ip[ip_header_length..end];
+    let tcp_header_length = ((tcp[12] >> 4) as usize) * 4;
+    if tcp_header_length < 20 || tcp.len() < tcp_header_length {
+        return None;
+    }
+    Some(&tcp[..tcp_header_length])
+}
+
+/// Returns the data following the TCP header (as returned by [`tcp_header`])
+/// of an IPv4 packet, without any link-layer padding.
+pub fn tcp_payload<'a>(ip: &'a [u8], tcp_header: &[u8]) -> &'a [u8] {
+    let start = ((ip[0] & 0x0f) as usize) * 4 + tcp_header.len();
+    let total_length = u16::from_be_bytes([ip[2], ip[3]]) as usize;
+    let end = if total_length == 0 { ip.len() } else { total_length.min(ip.len()) };
+    ip.get(start..end).unwrap_or(&[])
+}
+
+/// Returns the kinds of any IPv4 options in `kind-kind` form, or `None` for a
+/// plain 20-byte header.
+pub fn ip_options(ip: &[u8]) -> Option<String> {
+    let ip_header_length = ((ip.first()? & 0x0f) as usize) * 4;
+    let options_slice = ip.get(20..ip_header_length)?;
+    if options_slice.is_empty() {
+        return None;
+    }
+
+    let mut kinds = Vec::new();
+    let mut i = 0;
+    while i < options_slice.len() {
+        let kind = options_slice[i];
+        kinds.push(kind.to_string());
+        match kind {
+            0 => break,
+            1 => i += 1,
+            _ => {
+                let length = *options_slice.get(i + 1)? as usize;
+                if length < 2 { break; }
+                i += length;
+            },
+        }
+    }
+    Some(kinds.join("-"))
 }
 
-pub fn extract_tcp_options(tcp_payload: &[u8]) -> (String, String, String) {
+/// Values decoded from the options of a TCP header.
+pub struct TcpOptions {
+    /// Option kinds in wire order, e.g. `2-4-8-1-3`
+    pub layout: String,
+    /// Bare option kinds in wire order
+    pub kinds: Vec<u8>,
+    pub mss: String,
+    pub window_scale: String,
+    pub ts_val: Option<u32>,
+    pub ts_ecr: Option<u32>,
+    /// MPTCP option subtype (0 = MP_CAPABLE, 1 = MP_JOIN, ...)
+    pub mptcp_subtype: Option<u8>,
+    /// TCP Fast Open cookie length; 0 is a cookie request
+    pub tfo_cookie_length: Option<u8>,
+}
+
+// Experiment ID used by TFO before kind 34 was assigned
+const TFO_EXPERIMENT_ID: [u8; 2] = [0xf9, 0x89];
+
+/// Parses the options of a TCP header as returned by [`tcp_header`].
+pub fn extract_tcp_options(tcp_header: &[u8]) -> TcpOptions {
     let mut options_str = String::new();
     let mut mss = String::new();
     let mut window_scale = String::new();
+    let mut ts_val = None;
+    let mut ts_ecr = None;
+    let mut mptcp_subtype = None;
+    let mut tfo_cookie_length = None;
 
-    let tcp_header_length = ((tcp_payload[12] >> 4) as usize) * 4;
-    let options_slice = &tcp_payload[20..tcp_header_length];
+    let options_slice = &tcp_header[20..];
 
+    let mut kinds = Vec::new();
     let mut i = 0;
     while i < options_slice.len() {
         let kind = options_slice[i];
+        kinds.push(kind);
         match kind {
             0 => {
                 options_str.push_str("0-");
@@ -67,20 +291,168 @@ pub fn extract_tcp_options(tcp_payload: &[u8]) -> (String, String, String) {
                 options_str.push_str("3-");
                 i += 3;
             },
+            8 => {
+                if options_slice.len() >= i + 10 {
+                    let value = |at: usize| u32::from_be_bytes([
+                        options_slice[at], options_slice[at+1], options_slice[at+2], options_slice[at+3]
+                    ]);
+                    ts_val = Some(value(i + 2));
+                    ts_ecr = Some(value(i + 6));
+                }
+                options_str.push_str("8-");
+                i += 10;
+            },
             _ => {
-                options_str.push_str(&format!("{}-", kind));
                 if options_slice.len() > i + 1 {
                     let length = options_slice[i + 1] as usize;
-                    if length < 2 { break; }
+                    if length < 2 {
+                        options_str.push_str(&format!("{}-", kind));
+                        break;
+                    }
+                    let data = &options_slice[(i + 2).min(options_slice.len())..(i + length).min(options_slice.len())];
+                    match kind {
+                        30 => mptcp_subtype = data.first().map(|byte| byte >> 4),
+                        34 => tfo_cookie_length = Some(data.len() as u8),
+                        254 if data.starts_with(&TFO_EXPERIMENT_ID) => tfo_cookie_length = Some((data.len() - 2) as u8),
+                        _ => {}
+                    }
+                    // RFC 6994 experimental options are told apart by their 16-bit ExID
+                    if (kind == 253 || kind == 254) && data.len() >= 2 {
+                        options_str.push_str(&format!("{}(0x{:02x}{:02x})-", kind, data[0], data[1]));
+                    } else {
+                        options_str.push_str(&format!("{}-", kind));
+                    }
                     i += length;
                 } else {
+                    options_str.push_str(&format!("{}-", kind));
                     break;
                 }
             },
         }
     }
 
-    (options_str.trim_end_matches('-').to_string(), mss, window_scale)
+    TcpOptions {
+        layout: options_str.trim_end_matches('-').to_string(),
+        kinds,
+        mss,
+        window_scale,
+        ts_val,
+        ts_ecr,
+        mptcp_subtype,
+        tfo_cookie_length,
+    }
+}
+
+/// A single TCP option with its length and value exactly as seen on the wire.
+#[derive(Serialize)]
+pub struct TcpOptionDetail {
+    pub kind: u8,
+    /// Length byte, absent for the single-byte EOL and NOP options
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub length: Option<u8>,
+    /// Option data (SACK blocks, cookies, unknown payloads) as hex
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub value: String,
+}
+
+/// Decodes every option of a TCP header as returned by [`tcp_header`],
+/// keeping lengths and values so middlebox option mangling can be spotted.
+pub fn decode_tcp_options(tcp_header: &[u8]) -> Vec<TcpOptionDetail> {
+    let options_slice = &tcp_header[20..];
+    let mut details = Vec::new();
+
+    let mut i = 0;
+    while i < options_slice.len() {
+        let kind = options_slice[i];
+        if kind == 0 || kind == 1 {
+            details.push(TcpOptionDetail { kind, length: None, value: String::new() });
+            if kind == 0 { break; }
+            i += 1;
+            continue;
+        }
+        let Some(&length) = options_slice.get(i + 1) else {
+            details.push(TcpOptionDetail { kind, length: None, value: String::new() });
+            break;
+        };
+        // Keep whatever is there even if the length overruns the header
+        let end = (i + length.max(2) as usize).min(options_slice.len());
+        details.push(TcpOptionDetail {
+            kind,
+            length: Some(length),
+            value: hex::encode(&options_slice[(i + 2).min(end)..end]),
+        });
+        if length < 2 { break; }
+        i += length as usize;
+    }
+
+    details
+}
+
+/// Renders the FoxIO JA4T (client SYN) / JA4TS (server SYN-ACK) string:
+/// `window_options_mss_windowscale`, with `00` for a missing MSS or scale.
+pub fn ja4t(window_size: u16, options: &TcpOptions) -> String {
+    let kinds = options.kinds.iter().map(|kind| kind.to_string()).collect::<Vec<_>>().join("-");
+    let or_zero = |value: &str| if value.is_empty() { "00".to_string() } else { value.to_string() };
+    format!(
+        "{}_{}_{}_{}",
+        window_size,
+        if kinds.is_empty() { "00".to_string() } else { kinds },
+        or_zero(&options.mss),
+        or_zero(&options.window_scale)
+    )
+}
+
+/// Window size divided by MSS, rounded to two decimals. Whole numbers mean
+/// the stack sizes its window as a multiple of the MSS (p0f's `mss*N`).
+pub fn window_mss_ratio(window_size: u16, mss: &str) -> Option<f64> {
+    let mss: u16 = mss.parse().ok().filter(|&mss| mss > 0)?;
+    Some((window_size as f64 / mss as f64 * 100.0).round() / 100.0)
+}
+
+/// Rounds an observed TTL up to the initial TTL the sender most likely used
+/// (the common stack defaults are 32, 64, 128 and 255).
+pub fn initial_ttl(ttl: u8) -> u8 {
+    match ttl {
+        0..=32 => 32,
+        33..=64 => 64,
+        65..=128 => 128,
+        _ => 255,
+    }
+}
+
+// Largest jump between two SYNs from one host still counted as a sequential IP ID
+const IP_ID_INCREMENTAL_WINDOW: u16 = 1024;
+// Hosts remembered before the IP ID history is reset
+const IP_ID_MAX_HOSTS: usize = 65536;
+
+/// Classifies IP ID generation per host by comparing consecutive SYNs.
+#[derive(Default)]
+pub struct IpIdTracker {
+    last_ids: HashMap<IpAddr, u16>,
+}
+
+impl IpIdTracker {
+    pub fn new() -> Self {
+        IpIdTracker { last_ids: HashMap::new() }
+    }
+
+    /// Returns "zero", "incremental" or "random", or `None` until a second
+    /// non-zero ID from the same host has been seen.
+    pub fn observe(&mut self, ip: IpAddr, id: u16) -> Option<&'static str> {
+        if id == 0 {
+            return Some("zero");
+        }
+        if self.last_ids.len() >= IP_ID_MAX_HOSTS {
+            self.last_ids.clear();
+        }
+        let previous = self.last_ids.insert(ip, id)?;
+        let delta = id.wrapping_sub(previous);
+        if delta > 0 && delta <= IP_ID_INCREMENTAL_WINDOW {
+            Some("incremental")
+        } else {
+            Some("random")
+        }
+    }
 }
 
 pub fn is_syn_packet(tcp_flags: u8, is_incoming: bool) -> bool {
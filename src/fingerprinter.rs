@@ -0,0 +1,520 @@
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use pnet::packet::Packet;
+use pnet::packet::ipv4::{Ipv4Flags, Ipv4Packet};
+use pnet::packet::tcp::TcpFlags;
+use serde_json::Value;
+
+use crate::aggregate::{Aggregator, SummarySettings};
+use crate::anomaly::AnomalyDetector;
+use crate::asn::AsnDb;
+use crate::decode::{self, Encapsulation};
+use crate::dhcp;
+use crate::dns::{self, DnsQuery};
+use crate::dedup::{DedupSettings, Deduplicator};
+use crate::event::{event, PacketDetails};
+use crate::flow::{FlowKey, FlowTracker};
+use crate::http::HttpTracker;
+use crate::icmp::IcmpTracker;
+use crate::intel::{IntelSettings, ThreatIntel};
+use crate::inventory::{Inventory, InventorySettings};
+use crate::fingerprint::{
+    Fingerprint, IpIdTracker, decode_tcp_options, extract_tcp_options, initial_ttl, ip_options, is_syn_packet,
+    ja4t, tcp_header, tcp_payload, window_mss_ratio,
+};
+use crate::nat::{NatDetector, NatSettings};
+use crate::neighbors::{NeighborTracker, format_mac};
+use crate::oui::OuiDb;
+use crate::p0f;
+use crate::quic;
+use crate::rdns::{RdnsSettings, ReverseResolver};
+use crate::scanner::{ScanDetector, ScannerSettings};
+use crate::signatures::SignatureDb;
+use crate::ssh::SshTracker;
+
+/// Optional record fields, taken from the `[fingerprints]` section.
+#[derive(Clone, Default)]
+pub struct FingerprintOptions {
+    pub sensor_id: Option<String>,
+    pub ip_behavior: bool,
+    pub options_hex: bool,
+    pub verbose_options: bool,
+    pub ja4t: bool,
+    pub p0f: bool,
+    /// Emit `quic` events for client Initial packets
+    pub quic: bool,
+    /// Emit `ssh` events with HASSH fingerprints for port 22 connections
+    pub ssh: bool,
+    /// Emit `http` events with the Host and User-Agent of plaintext requests
+    pub http: bool,
+    /// Emit `dhcp` events for client Discover and Request messages
+    pub dhcp: bool,
+    /// Emit `icmp` events describing echo requests
+    pub icmp: bool,
+    /// Emit `new_host` events for MAC/IP pairings learned from ARP and NDP
+    pub neighbors: bool,
+    /// Collect the DNS queries of local hosts for the passive DNS output
+    pub dns: bool,
+    pub signatures: Option<Arc<SignatureDb>>,
+    pub catalog: Option<Arc<SignatureDb>>,
+    /// Vendor names for the `mac_vendor` field
+    pub oui: Option<Arc<OuiDb>>,
+    /// Network operators for the `asn` and `as_org` fields
+    pub asn: Option<Arc<AsnDb>>,
+    pub rdns: Option<RdnsSettings>,
+    pub intel: Option<IntelSettings>,
+    pub inventory: Option<InventorySettings>,
+    pub scanner: Option<ScannerSettings>,
+    pub nat: Option<NatSettings>,
+    /// How long a fingerprint is remembered for `anomaly` events
+    pub anomaly_ttl: Option<Duration>,
+    pub dedup: Option<DedupSettings>,
+    pub summary: Option<SummarySettings>,
+    /// How long a SYN waits for its SYN-ACK for `flow` events
+    pub flow_timeout: Option<Duration>,
+}
+
+/// Turns captured frames into fingerprint records.
+pub struct Fingerprinter {
+    hostname: String,
+    interface: String,
+    local_ips: HashSet<IpAddr>,
+    options: FingerprintOptions,
+    ip_id_tracker: IpIdTracker,
+    scan_detector: Option<ScanDetector>,
+    nat_detector: Option<NatDetector>,
+    anomaly_detector: Option<AnomalyDetector>,
+    deduplicator: Option<Deduplicator>,
+    aggregator: Option<Aggregator>,
+    flow_tracker: Option<FlowTracker>,
+    ssh_tracker: Option<SshTracker>,
+    http_tracker: Option<HttpTracker>,
+    icmp_tracker: Option<IcmpTracker>,
+    neighbor_tracker: Option<NeighborTracker>,
+    reverse_resolver: Option<ReverseResolver>,
+    threat_intel: Option<ThreatIntel>,
+    inventory: Option<Inventory>,
+    mirrored: bool,
+    events: Vec<Value>,
+    dns_queries: Vec<Value>,
+}
+
+impl Fingerprinter {
+    pub fn new(hostname: &str, interface: &str, local_ips: HashSet<IpAddr>, options: FingerprintOptions) -> Self {
+        Fingerprinter {
+            hostname: hostname.to_string(),
+            interface: interface.to_string(),
+            local_ips,
+            scan_detector: options.scanner.clone().map(ScanDetector::new),
+            nat_detector: options.nat.clone().map(NatDetector::new),
+            anomaly_detector: options.anomaly_ttl.map(AnomalyDetector::new),
+            deduplicator: options.dedup.clone().map(Deduplicator::new),
+            aggregator: options.summary.clone().map(Aggregator::new),
+            flow_tracker: options.flow_timeout.map(FlowTracker::new),
+            ssh_tracker: if options.ssh { Some(SshTracker::new()) } else { None },
+            http_tracker: if options.http { Some(HttpTracker::new()) } else { None },
+            icmp_tracker: if options.icmp { Some(IcmpTracker::new()) } else { None },
+            neighbor_tracker: if options.neighbors { Some(NeighborTracker::new()) } else { None },
+            reverse_resolver: options.rdns.clone().map(ReverseResolver::new),
+            threat_intel: options.intel.clone().map(ThreatIntel::new),
+            inventory: options.inventory.clone().map(Inventory::new),
+            mirrored: false,
+            options,
+            ip_id_tracker: IpIdTracker::new(),
+            events: Vec::new(),
+            dns_queries: Vec::new(),
+        }
+    }
+
+    /// Treats every packet as mirrored traffic, for sources such as sFlow
+    /// where no address is local to the sensor.
+    pub fn set_mirrored(&mut self, mirrored: bool) {
+        self.mirrored = mirrored;
+    }
+
+    fn inspect_udp(&mut self, ip_packet: &Ipv4Packet) {
+        let udp = ip_packet.payload();
+        if udp.len() < 8 {
+            return;
+        }
+        let src_port = u16::from_be_bytes([udp[0], udp[1]]);
+        let dst_port = u16::from_be_bytes([udp[2], udp[3]]);
+        let data = &udp[8..];
+        let ip_address = IpAddr::V4(ip_packet.get_source());
+
+        if self.options.quic && dst_port == 443 {
+            if let Some(initial) = quic::parse_initial(data) {
+                let details = PacketDetails { ip_address, src_port, dst_port, details: initial };
+                let event = event("quic", &self.hostname, self.sensor_id(), &self.interface, details);
+                self.events.push(event);
+            }
+        }
+
+        if self.options.dhcp && dst_port == 67 {
+            if let Some(fingerprint) = dhcp::parse(data) {
+                let event = event("dhcp", &self.hostname, self.sensor_id(), &self.interface, fingerprint);
+                self.events.push(event);
+            }
+        }
+
+        // Only queries sent by local hosts, i.e. what the hosts we watch resolve
+        let is_local = self.local_ips.contains(&ip_address) || self.mirrored;
+        if self.options.dns && dst_port == 53 && is_local {
+            if let Some((qname, qtype)) = dns::parse_query(data) {
+                let resolver = IpAddr::V4(ip_packet.get_destination());
+                let query = DnsQuery { ip_address, resolver, qname, qtype };
+                let record = event("dns", &self.hostname, self.sensor_id(), &self.interface, query);
+                self.dns_queries.push(record);
+            }
+        }
+    }
+
+    fn inspect_tcp_payload(&mut self, ip_packet: &Ipv4Packet, tcp_header: &[u8]) {
+        let payload = tcp_payload(ip_packet.packet(), tcp_header);
+        if payload.is_empty() {
+            return;
+        }
+        let src_port = u16::from_be_bytes([tcp_header[0], tcp_header[1]]);
+        let dst_port = u16::from_be_bytes([tcp_header[2], tcp_header[3]]);
+        let source_ip = IpAddr::V4(ip_packet.get_source());
+        let destination_ip = IpAddr::V4(ip_packet.get_destination());
+
+        if let Some(ssh_tracker) = self.ssh_tracker.as_mut() {
+            if src_port == 22 || dst_port == 22 {
+                let connection = (source_ip, src_port, destination_ip, dst_port);
+                if let Some(handshake) = ssh_tracker.observe(connection, payload, dst_port == 22) {
+                    let details = PacketDetails { ip_address: source_ip, src_port, dst_port, details: handshake };
+                    let event = event("ssh", &self.hostname, self.sensor_id(), &self.interface, details);
+                    self.events.push(event);
+                }
+            }
+        }
+
+        if let Some(http_tracker) = self.http_tracker.as_mut() {
+            if let Some(request) = http_tracker.observe((source_ip, src_port, destination_ip, dst_port), payload) {
+                let details = PacketDetails { ip_address: source_ip, src_port, dst_port, details: request };
+                let event = event("http", &self.hostname, self.sensor_id(), &self.interface, details);
+                self.events.push(event);
+            }
+        }
+    }
+
+    fn sensor_id(&self) -> &str {
+        self.options.sensor_id.as_deref().unwrap_or(&self.hostname)
+    }
+
+    /// Returns the events raised since the last call, including any periodic
+    /// reports that have come due.
+    pub fn take_events(&mut self) -> Vec<Value> {
+        self.collect_events(false)
+    }
+
+    /// Like `take_events`, with every periodic report cut short, for shutdown.
+    pub fn take_final_events(&mut self) -> Vec<Value> {
+        self.collect_events(true)
+    }
+
+    fn collect_events(&mut self, final_reports: bool) -> Vec<Value> {
+        if let Some(nat_detector) = self.nat_detector.as_mut() {
+            let suspects = nat_detector.report(final_reports);
+            for suspect in suspects {
+                let event = event("nat_suspect", &self.hostname, self.sensor_id(), &self.interface, suspect);
+                self.events.push(event);
+            }
+        }
+        if let Some(deduplicator) = self.deduplicator.as_mut() {
+            let summaries = deduplicator.report(final_reports);
+            for summary in summaries {
+                let event = event("duplicate_summary", &self.hostname, self.sensor_id(), &self.interface, summary);
+                self.events.push(event);
+            }
+        }
+        if let Some(summary) = self.aggregator.as_mut().and_then(|aggregator| aggregator.report(final_reports)) {
+            let event = event("fingerprint_summary", &self.hostname, self.sensor_id(), &self.interface, summary);
+            self.events.push(event);
+        }
+        if let Some(flow_tracker) = self.flow_tracker.as_mut() {
+            flow_tracker.sweep();
+        }
+        if let Some(inventory) = self.inventory.as_mut() {
+            inventory.save_if_due();
+        }
+        std::mem::take(&mut self.events)
+    }
+
+    /// Saves state that outlives the process, such as the asset inventory.
+    pub fn finish(&mut self) {
+        if let Some(inventory) = self.inventory.as_mut() {
+            inventory.save();
+        }
+    }
+
+    /// Returns the DNS queries seen since the last call, which go to their
+    /// own output rather than alongside the fingerprints.
+    pub fn take_dns_queries(&mut self) -> Vec<Value> {
+        std::mem::take(&mut self.dns_queries)
+    }
+
+    /// Returns the fingerprint of a SYN or SYN-ACK frame, or `None` for
+    /// anything that shouldn't be recorded.
+    pub fn fingerprint(&mut self, frame: &[u8]) -> Option<Fingerprint> {
+        // ARP and NDP aren't IPv4, so they're picked out of the raw frame
+        if let Some(neighbor_tracker) = self.neighbor_tracker.as_mut() {
+            if let Some(host) = neighbor_tracker.observe(frame, self.options.oui.as_deref()) {
+                if let Some(inventory) = self.inventory.as_mut() {
+                    inventory.observe_mac(host.ip_address, &host.mac);
+                }
+                let event = event("new_host", &self.hostname, self.sensor_id(), &self.interface, host);
+                self.events.push(event);
+                return None;
+            }
+        }
+
+        let mut encap = Encapsulation::default();
+        let ip_packet = Ipv4Packet::new(decode::ipv4_payload(frame, &mut encap)?)?;
+
+        // Non-first fragments don't carry a TCP header
+        if ip_packet.get_fragment_offset() != 0 {
+            return None;
+        }
+        let is_fragmented = ip_packet.get_flags() & Ipv4Flags::MoreFragments != 0;
+
+        // Other protocols are reported as events rather than fingerprints
+        if ip_packet.get_next_level_protocol().0 == 17 {
+            self.inspect_udp(&ip_packet);
+            return None;
+        }
+
+        let source_ip = IpAddr::V4(ip_packet.get_source());
+        let destination_ip = IpAddr::V4(ip_packet.get_destination());
+
+        // Process packets in both directions
+        let (fingerprint_ip, is_incoming, direction) = if self.local_ips.contains(&destination_ip) {
+            (source_ip, true, "incoming") // Incoming connection
+        } else if self.local_ips.contains(&source_ip) {
+            (destination_ip, false, "outgoing") // Outgoing connection response
+        } else if encap.is_tunneled() || self.mirrored {
+            // Mirrored traffic: neither end is local, so fingerprint the sender
+            let is_syn_only = ip_packet.payload().get(13).map_or(true, |flags| flags & TcpFlags::ACK as u8 == 0);
+            (source_ip, is_syn_only, "mirrored")
+        } else {
+            return None; // Neither source nor destination is local, skip
+        };
+
+        // Skip broadcast, multicast, or unspecified IPs
+        if let IpAddr::V4(ip) = fingerprint_ip {
+            if ip.is_broadcast() || ip.is_multicast() || ip.is_unspecified() {
+                return None;
+            }
+        }
+
+        // Echo requests from remote hosts, many scanners ping before they SYN
+        if ip_packet.get_next_level_protocol().0 == 1 {
+            if let Some(icmp_tracker) = self.icmp_tracker.as_mut().filter(|_| direction != "outgoing") {
+                let df = ip_packet.get_flags() & Ipv4Flags::DontFragment != 0;
+                if let Some(icmp) = icmp_tracker.observe(source_ip, ip_packet.get_ttl(), df, ip_packet.payload()) {
+                    let event = event("icmp", &self.hostname, self.sensor_id(), &self.interface, icmp);
+                    self.events.push(event);
+                }
+            }
+            return None;
+        }
+
+        if ip_packet.get_next_level_protocol().0 != 6 { // TCP protocol
+            return None;
+        }
+        let tcp_header = tcp_header(ip_packet.packet())?;
+        let flags = tcp_header[13];
+        if !is_syn_packet(flags, is_incoming) {
+            if flags & TcpFlags::SYN as u8 == 0 {
+                self.inspect_tcp_payload(&ip_packet, tcp_header);
+            }
+            return None;
+        }
+        let is_syn_ack = flags & TcpFlags::ACK as u8 != 0;
+
+        let window_size = u16::from_be_bytes([tcp_header[14], tcp_header[15]]);
+        let options = extract_tcp_options(tcp_header);
+        let mss_ratio = window_mss_ratio(window_size, &options.mss);
+        let ja4t_string = if self.options.ja4t { Some(ja4t(window_size, &options)) } else { None };
+        let observation = if self.options.p0f || self.options.signatures.is_some() || self.options.catalog.is_some() {
+            Some(p0f::observe(ip_packet.packet(), tcp_header))
+        } else {
+            None
+        };
+
+        let mut fingerprint = Fingerprint::new(
+            self.hostname.clone(),
+            fingerprint_ip,
+            window_size,
+            options.layout,
+            options.mss,
+            options.window_scale
+        );
+        fingerprint.interface = self.interface.clone();
+        if let Some(sensor_id) = &self.options.sensor_id {
+            fingerprint.sensor_id = sensor_id.clone();
+        }
+        fingerprint.tsval = options.ts_val;
+        fingerprint.tsecr = options.ts_ecr;
+        fingerprint.mptcp_subtype = options.mptcp_subtype;
+        fingerprint.tfo = options.tfo_cookie_length.is_some();
+        fingerprint.tfo_cookie_length = options.tfo_cookie_length;
+        fingerprint.window_mss_ratio = mss_ratio;
+        if is_syn_ack {
+            fingerprint.ja4ts = ja4t_string;
+        } else {
+            fingerprint.ja4t = ja4t_string;
+        }
+        if let Some(observation) = &observation {
+            if self.options.p0f {
+                fingerprint.p0f = Some(observation.to_string());
+            }
+            let signatures = self.options.signatures.as_deref();
+            if let Some(best) = signatures.and_then(|db| db.best_match(is_syn_ack, observation, &fingerprint.muonfp_fingerprint)) {
+                fingerprint.os_label = Some(best.label.text());
+                fingerprint.os_class = Some(best.label.class.clone());
+                fingerprint.os_match_quality = Some(best.quality);
+            }
+            let catalog = self.options.catalog.as_deref();
+            if let Some(best) = catalog.and_then(|db| db.best_match(is_syn_ack, observation, &fingerprint.muonfp_fingerprint)) {
+                if best.label.is_tool() {
+                    fingerprint.tool_guess = Some(best.label.text());
+                } else {
+                    fingerprint.os_guess = Some(best.label.text());
+                }
+            }
+        }
+
+        // A sender that is zero hops away is on this segment, so the frame's
+        // source MAC is its own rather than a router's
+        let on_segment = fingerprint_ip == source_ip && encap.tunnel_source.is_none()
+            && ip_packet.get_ttl() == initial_ttl(ip_packet.get_ttl());
+        let segment_mac = frame.get(6..12).and_then(|mac| <[u8; 6]>::try_from(mac).ok()).filter(|_| on_segment);
+        if let Some((oui, mac)) = self.options.oui.as_deref().zip(segment_mac) {
+            fingerprint.mac = Some(format_mac(&mac));
+            fingerprint.mac_vendor = oui.lookup(&mac).map(str::to_string);
+        }
+        if let Some((asn, as_org)) = self.options.asn.as_deref().and_then(|db| db.lookup(fingerprint_ip)) {
+            fingerprint.asn = Some(asn);
+            fingerprint.as_org = as_org;
+        }
+        if let Some(reverse_resolver) = &self.reverse_resolver {
+            fingerprint.ptr = reverse_resolver.lookup(fingerprint_ip);
+        }
+        if self.options.options_hex {
+            fingerprint.options_hex = Some(hex::encode(&tcp_header[20..]));
+        }
+        if self.options.verbose_options {
+            fingerprint.options_detail = Some(decode_tcp_options(tcp_header));
+        }
+        fingerprint.src_port = u16::from_be_bytes([tcp_header[0], tcp_header[1]]);
+        fingerprint.dst_port = u16::from_be_bytes([tcp_header[2], tcp_header[3]]);
+        fingerprint.packet_type = if is_syn_ack { "syn-ack" } else { "syn" };
+        fingerprint.direction = direction;
+        fingerprint.role = if is_syn_ack { "server_synack" } else { "client_syn" };
+        fingerprint.seq = u32::from_be_bytes([tcp_header[4], tcp_header[5], tcp_header[6], tcp_header[7]]);
+        fingerprint.ttl = ip_packet.get_ttl();
+        fingerprint.initial_ttl = initial_ttl(fingerprint.ttl);
+        // ECE+CWR on a SYN requests ECN, ECE alone on a SYN-ACK accepts it
+        fingerprint.ece = flags & TcpFlags::ECE as u8 != 0;
+        fingerprint.cwr = flags & TcpFlags::CWR as u8 != 0;
+        fingerprint.vlan_id = encap.vlan_id();
+        fingerprint.outer_vlan_id = encap.outer_vlan_id();
+        fingerprint.tunnel_source = encap.tunnel_source;
+        fingerprint.vni = encap.vni;
+        // A SYN split across fragments is a classic IDS evasion trick
+        fingerprint.fragmented = is_fragmented;
+        fingerprint.ip_options = ip_options(ip_packet.packet());
+        if self.options.ip_behavior {
+            fingerprint.df = Some(ip_packet.get_flags() & Ipv4Flags::DontFragment != 0);
+            fingerprint.ip_id = self.ip_id_tracker.observe(fingerprint_ip, ip_packet.get_identification());
+        }
+
+        if let Some(threat_intel) = &self.threat_intel {
+            fingerprint.threat_feeds = threat_intel.matches(fingerprint_ip);
+            if !fingerprint.threat_feeds.is_empty() {
+                fingerprint.tags.push("threat_intel");
+            }
+        }
+
+        // Known scanning tools, or any host sweeping many ports
+        if let Some(scan_detector) = self.scan_detector.as_mut() {
+            let sweeping = !is_syn_ack && scan_detector.observe(fingerprint_ip, fingerprint.dst_port);
+            if sweeping || fingerprint.tool_guess.is_some() {
+                fingerprint.tags.push("scanner");
+            }
+        }
+
+        if let Some(nat_detector) = self.nat_detector.as_mut() {
+            if !is_syn_ack {
+                nat_detector.observe(fingerprint_ip, &fingerprint.muonfp_fingerprint, fingerprint.os_guess.as_deref());
+            }
+        }
+
+        if let Some(anomaly_detector) = self.anomaly_detector.as_mut() {
+            if let Some(change) = anomaly_detector.observe(fingerprint_ip, fingerprint.role, &fingerprint.muonfp_fingerprint) {
+                let event = event("anomaly", &self.hostname, self.sensor_id(), &self.interface, change);
+                self.events.push(event);
+            }
+        }
+
+        if let Some(flow_tracker) = self.flow_tracker.as_mut() {
+            let (source_port, destination_port) = (fingerprint.src_port, fingerprint.dst_port);
+            if is_syn_ack {
+                let key = FlowKey {
+                    client_ip: destination_ip,
+                    client_port: destination_port,
+                    server_ip: source_ip,
+                    server_port: source_port,
+                };
+                if let Some(handshake) = flow_tracker.syn_ack(key, &fingerprint.muonfp_fingerprint) {
+                    let event = event("flow", &self.hostname, self.sensor_id(), &self.interface, handshake);
+                    self.events.push(event);
+                }
+            } else {
+                let key = FlowKey {
+                    client_ip: source_ip,
+                    client_port: source_port,
+                    server_ip: destination_ip,
+                    server_port: destination_port,
+                };
+                flow_tracker.syn(key, &fingerprint.muonfp_fingerprint);
+            }
+        }
+
+        if let Some(http_tracker) = self.http_tracker.as_mut() {
+            if !is_syn_ack {
+                let connection = (source_ip, fingerprint.src_port, destination_ip, fingerprint.dst_port);
+                http_tracker.syn(connection, &fingerprint.muonfp_fingerprint);
+            }
+        }
+
+        if let Some(inventory) = self.inventory.as_mut() {
+            inventory.observe_fingerprint(fingerprint_ip, &fingerprint.muonfp_fingerprint, fingerprint.os_guess.as_deref());
+            if let Some(mac) = &segment_mac {
+                inventory.observe_mac(fingerprint_ip, &format_mac(mac));
+            }
+            // A SYN-ACK means the server is listening on its source port
+            if is_syn_ack {
+                inventory.observe_open_port(source_ip, fingerprint.src_port);
+            }
+        }
+
+        if let Some(aggregator) = self.aggregator.as_mut() {
+            let dst_port = if is_syn_ack { None } else { Some(fingerprint.dst_port) };
+            aggregator.observe(fingerprint_ip, &fingerprint.muonfp_fingerprint, dst_port);
+        }
+
+        // Repeats still feed the detectors above but aren't written again
+        if let Some(deduplicator) = self.deduplicator.as_mut() {
+            if !deduplicator.observe(fingerprint_ip, &fingerprint.muonfp_fingerprint) {
+                return None;
+            }
+        }
+
+        Some(fingerprint)
+    }
+}
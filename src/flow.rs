@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+use serde::Serialize;
+
+// Half-open handshakes remembered before the table is reset
+const FLOW_MAX_PENDING: usize = 262144;
+
+/// Client and server address of a connection, as seen in its SYN.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FlowKey {
+    pub client_ip: IpAddr,
+    pub client_port: u16,
+    pub server_ip: IpAddr,
+    pub server_port: u16,
+}
+
+struct PendingSyn {
+    at: Instant,
+    fingerprint: String,
+}
+
+/// Body of a `flow` event.
+#[derive(Serialize)]
+pub struct Handshake {
+    pub client_ip: IpAddr,
+    pub client_port: u16,
+    pub server_ip: IpAddr,
+    pub server_port: u16,
+    pub protocol: &'static str,
+    pub client_fingerprint: String,
+    pub server_fingerprint: String,
+    /// Milliseconds between the SYN and the SYN-ACK at the sensor; about a
+    /// round trip for mirrored traffic, the server's reply latency otherwise
+    pub rtt_ms: f64,
+}
+
+/// Pairs the SYN and SYN-ACK of each handshake.
+pub struct FlowTracker {
+    timeout: Duration,
+    pending: HashMap<FlowKey, PendingSyn>,
+    last_sweep: Instant,
+}
+
+impl FlowTracker {
+    pub fn new(timeout: Duration) -> Self {
+        FlowTracker { timeout, pending: HashMap::new(), last_sweep: Instant::now() }
+    }
+
+    pub fn syn(&mut self, key: FlowKey, fingerprint: &str) {
+        if self.pending.len() >= FLOW_MAX_PENDING {
+            self.expire();
+            if self.pending.len() >= FLOW_MAX_PENDING {
+                self.pending.clear();
+            }
+        }
+        self.pending.insert(key, PendingSyn { at: Instant::now(), fingerprint: fingerprint.to_string() });
+    }
+
+    /// Completes the handshake started by a matching SYN, if one is pending.
+    pub fn syn_ack(&mut self, key: FlowKey, fingerprint: &str) -> Option<Handshake> {
+        let syn = self.pending.remove(&key)?;
+        let elapsed = syn.at.elapsed();
+        if elapsed > self.timeout {
+            return None;
+        }
+        Some(Handshake {
+            client_ip: key.client_ip,
+            client_port: key.client_port,
+            server_ip: key.server_ip,
+            server_port: key.server_port,
+            protocol: "tcp",
+            client_fingerprint: syn.fingerprint,
+            server_fingerprint: fingerprint.to_string(),
+            rtt_ms: (elapsed.as_secs_f64() * 1_000_000.0).round() / 1000.0,
+        })
+    }
+
+    /// Forgets SYNs that never got an answer; runs at most once per timeout.
+    pub fn sweep(&mut self) {
+        if self.last_sweep.elapsed() >= self.timeout {
+            self.expire();
+        }
+    }
+
+    fn expire(&mut self) {
+        let timeout = self.timeout;
+        self.pending.retain(|_, syn| syn.at.elapsed() <= timeout);
+        self.last_sweep = Instant::now();
+    }
+}
@@ -0,0 +1,543 @@
+use chrono::{DateTime, Utc};
+use config::Config;
+use serde_json::{json, Map, Value};
+
+const PRODUCT_VERSION: &str = env!("CARGO_PKG_VERSION");
+// Record fields written by the csv format when `columns` is unset
+const CSV_DEFAULT_COLUMNS: [&str; 14] = [
+    "timestamp",
+    "event_type",
+    "hostname",
+    "sensor_id",
+    "interface",
+    "ip_address",
+    "src_port",
+    "dst_port",
+    "muonfp_fingerprint",
+    "ja4t",
+    "os_guess",
+    "tool_guess",
+    "ttl",
+    "tags",
+];
+
+/// How a record is rendered for an output, chosen with `format=` in the
+/// output's section. Text formats render one record per line, some also
+/// starting each file with a header; binary formats frame each record.
+#[derive(Clone, PartialEq)]
+pub enum Format {
+    /// The record as written to the .out files
+    Json,
+    /// ArcSight Common Event Format
+    Cef,
+    /// IBM QRadar Log Event Extended Format 1.0
+    Leef,
+    /// Suricata EVE JSON envelope with the record under `muonfp`
+    Eve,
+    /// Zeek tab-separated log with `#fields`/`#types` headers
+    Zeek,
+    /// Comma-separated values of the listed record fields, under a header row
+    Csv(Vec<String>),
+    /// Length-delimited `muonfp.v1.Record` messages from proto/muonfp.proto
+    Protobuf,
+    /// MessagePack maps, one after another
+    Msgpack,
+    /// CBOR maps as an RFC 8742 sequence
+    Cbor,
+    /// A user-supplied line with `{{field}}` placeholders
+    Template(Vec<Piece>),
+}
+
+/// Literal text or a (dotted) field path of a template.
+#[derive(Clone, PartialEq)]
+pub enum Piece {
+    Text(String),
+    Field(Vec<String>),
+}
+
+impl Format {
+    /// Reads `<section>.format`, JSON when unset, and `<section>.columns` for
+    /// csv or `<section>.template` for template.
+    pub fn from_settings(settings: &Config, section: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        match settings.get_string(&format!("{}.format", section)) {
+            Err(_) => Ok(Format::Json),
+            Ok(name) => match name.to_lowercase().as_str() {
+                "json" => Ok(Format::Json),
+                "cef" => Ok(Format::Cef),
+                "leef" => Ok(Format::Leef),
+                "eve" => Ok(Format::Eve),
+                "zeek" => Ok(Format::Zeek),
+                "csv" => {
+                    let columns: Vec<String> = match settings.get_string(&format!("{}.columns", section)) {
+                        Ok(columns) => columns.split(',').map(|column| column.trim().to_string()).filter(|column| !column.is_empty()).collect(),
+                        Err(_) => CSV_DEFAULT_COLUMNS.iter().map(|column| column.to_string()).collect(),
+                    };
+                    if columns.is_empty() {
+                        return Err(format!("{}.columns lists no columns", section).into());
+                    }
+                    Ok(Format::Csv(columns))
+                }
+                "protobuf" => Ok(Format::Protobuf),
+                "msgpack" | "messagepack" => Ok(Format::Msgpack),
+                "cbor" => Ok(Format::Cbor),
+                "template" => {
+                    let template = settings
+                        .get_string(&format!("{}.template", section))
+                        .map_err(|_| format!("{}.format=template needs {}.template", section, section))?;
+                    Ok(Format::Template(parse_template(&template).map_err(|e| format!("Bad {}.template: {}", section, e))?))
+                }
+                _ => Err(format!("Unknown {}.format: {}", section, name).into()),
+            },
+        }
+    }
+
+    /// Like `from_settings`, for outputs that can only carry text.
+    pub fn text_from_settings(settings: &Config, section: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let format = Format::from_settings(settings, section)?;
+        if format.is_binary() {
+            return Err(format!("{} output only supports text formats", section).into());
+        }
+        Ok(format)
+    }
+
+    pub fn is_json(&self) -> bool {
+        matches!(self, Format::Json)
+    }
+
+    pub fn is_binary(&self) -> bool {
+        matches!(self, Format::Protobuf | Format::Msgpack | Format::Cbor)
+    }
+
+    /// MIME type for outputs that label their messages.
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Format::Json | Format::Eve => "application/json",
+            Format::Cef | Format::Leef => "text/plain",
+            Format::Zeek => "text/tab-separated-values",
+            Format::Csv(_) => "text/csv",
+            Format::Protobuf => "application/x-protobuf",
+            Format::Msgpack => "application/msgpack",
+            Format::Cbor => "application/cbor",
+            Format::Template(_) => "text/plain",
+        }
+    }
+
+    /// The record as text. Binary formats, which text-only outputs refuse,
+    /// fall back to JSON.
+    pub fn render(&self, record: &Value) -> String {
+        match self {
+            Format::Json | Format::Protobuf | Format::Msgpack | Format::Cbor => record.to_string(),
+            Format::Cef => cef(record),
+            Format::Leef => leef(record),
+            Format::Eve => eve(record).to_string(),
+            Format::Zeek => zeek(record),
+            Format::Csv(columns) => csv(columns, record),
+            Format::Template(pieces) => template(pieces, record),
+        }
+    }
+
+    /// The record as written to a file or stream: a line, a length-prefixed
+    /// protobuf message, or a self-delimiting MessagePack or CBOR map.
+    pub fn encode(&self, record: &Value) -> Vec<u8> {
+        match self {
+            Format::Protobuf => {
+                let message = protobuf(record);
+                let mut framed = Vec::with_capacity(message.len() + 5);
+                put_varint(&mut framed, message.len() as u64);
+                framed.extend_from_slice(&message);
+                framed
+            }
+            // Serializing a JSON value can't fail
+            Format::Msgpack => rmp_serde::to_vec_named(record).unwrap_or_default(),
+            Format::Cbor => {
+                let mut buffer = Vec::new();
+                let _ = ciborium::into_writer(record, &mut buffer);
+                buffer
+            }
+            _ => {
+                let mut line = self.render(record).into_bytes();
+                line.push(b'\n');
+                line
+            }
+        }
+    }
+
+    /// Lines to start every file with, ending in a newline.
+    pub fn header(&self) -> Option<String> {
+        match self {
+            Format::Zeek => Some(zeek_header()),
+            Format::Csv(columns) => Some(format!("{}\n", columns.iter().map(|column| escape_csv(column)).collect::<Vec<_>>().join(","))),
+            _ => None,
+        }
+    }
+}
+
+fn text<'a>(record: &'a Value, name: &str) -> Option<&'a str> {
+    record[name].as_str().filter(|value| !value.is_empty())
+}
+
+// Signature ID and name for the header; events carry their type
+fn signature(record: &Value) -> (&str, String) {
+    match text(record, "event_type") {
+        Some(event_type) => (event_type, format!("muonfp {}", event_type.replace('_', " "))),
+        None => ("fingerprint", "TCP fingerprint".to_string()),
+    }
+}
+
+// 0-10: threat feed hits stand out, then anomalies and known scanners
+fn severity(record: &Value) -> u8 {
+    let tagged = |tag: &str| record["tags"].as_array().is_some_and(|tags| tags.iter().any(|t| t == tag));
+    if tagged("threat_intel") {
+        8
+    } else if matches!(text(record, "event_type"), Some("anomaly" | "nat_suspect")) || text(record, "tool_guess").is_some() {
+        5
+    } else {
+        3
+    }
+}
+
+fn epoch_millis(record: &Value) -> Option<i64> {
+    Some(DateTime::parse_from_rfc3339(text(record, "timestamp")?).ok()?.timestamp_millis())
+}
+
+// CEF:0|Vendor|Product|Version|Signature ID|Name|Severity|Extension
+fn cef(record: &Value) -> String {
+    let (signature_id, name) = signature(record);
+    let mut extension = Vec::new();
+    if let Some(millis) = epoch_millis(record) {
+        extension.push(("rt", millis.to_string()));
+    }
+    if let Some(ip_address) = text(record, "ip_address") {
+        // `src` only takes IPv4 addresses
+        if ip_address.contains(':') {
+            extension.push(("c6a2", ip_address.to_string()));
+            extension.push(("c6a2Label", "Source IPv6 Address".to_string()));
+        } else {
+            extension.push(("src", ip_address.to_string()));
+        }
+    }
+    if let Some(port) = record["src_port"].as_u64() {
+        extension.push(("spt", port.to_string()));
+    }
+    if let Some(port) = record["dst_port"].as_u64() {
+        extension.push(("dpt", port.to_string()));
+    }
+    if let Some(hostname) = text(record, "hostname") {
+        extension.push(("dvchost", hostname.to_string()));
+    }
+    let custom = [
+        ("cs1", "cs1Label", "muonfpFingerprint", "muonfp_fingerprint"),
+        ("cs2", "cs2Label", "osGuess", "os_guess"),
+        ("cs3", "cs3Label", "ja4t", "ja4t"),
+        ("cs4", "cs4Label", "toolGuess", "tool_guess"),
+        ("cs5", "cs5Label", "sensorId", "sensor_id"),
+    ];
+    for (key, label_key, label, field) in custom {
+        if let Some(value) = text(record, field) {
+            extension.push((key, value.to_string()));
+            extension.push((label_key, label.to_string()));
+        }
+    }
+    // Events have no fixed shape, so their details travel as JSON
+    if record.get("event_type").is_some() {
+        extension.push(("msg", record.to_string()));
+    }
+    let extension: Vec<String> = extension.iter().map(|(key, value)| format!("{}={}", key, escape_cef_extension(value))).collect();
+    format!(
+        "CEF:0|muonfp|muonfp|{}|{}|{}|{}|{}",
+        PRODUCT_VERSION,
+        escape_cef_header(signature_id),
+        escape_cef_header(&name),
+        severity(record),
+        extension.join(" ")
+    )
+}
+
+// LEEF:1.0|Vendor|Product|Version|EventID| then tab-separated key=value pairs
+fn leef(record: &Value) -> String {
+    let (event_id, _) = signature(record);
+    let mut attributes = vec![("cat", event_id.to_string()), ("sev", severity(record).to_string())];
+    // Epoch milliseconds, the default when devTimeFormat is absent
+    if let Some(millis) = epoch_millis(record) {
+        attributes.push(("devTime", millis.to_string()));
+    }
+    let fields = [
+        ("src", "ip_address"),
+        ("identHostName", "hostname"),
+        ("muonfpFingerprint", "muonfp_fingerprint"),
+        ("osGuess", "os_guess"),
+        ("ja4t", "ja4t"),
+        ("toolGuess", "tool_guess"),
+        ("sensorId", "sensor_id"),
+    ];
+    for (key, field) in fields {
+        if let Some(value) = text(record, field) {
+            attributes.push((key, value.to_string()));
+        }
+    }
+    if let Some(port) = record["src_port"].as_u64() {
+        attributes.push(("srcPort", port.to_string()));
+    }
+    if let Some(port) = record["dst_port"].as_u64() {
+        attributes.push(("dstPort", port.to_string()));
+    }
+    if record.get("event_type").is_some() {
+        attributes.push(("msg", record.to_string()));
+    }
+    let attributes: Vec<String> = attributes.iter().map(|(key, value)| format!("{}={}", key, escape_leef_attribute(value))).collect();
+    format!(
+        "LEEF:1.0|muonfp|muonfp|{}|{}|{}",
+        PRODUCT_VERSION,
+        escape_cef_header(event_id),
+        attributes.join("\t")
+    )
+}
+
+// Fields EVE consumers expect at the top level, renamed where EVE differs
+const EVE_FIELDS: [(&str, &str); 5] = [
+    ("hostname", "host"),
+    ("interface", "in_iface"),
+    ("ip_address", "src_ip"),
+    ("src_port", "src_port"),
+    ("dst_port", "dest_port"),
+];
+
+// {"timestamp", "event_type": "muonfp", "src_ip", ..., "muonfp": {"type", ...}}
+fn eve(record: &Value) -> Value {
+    let Some(fields) = record.as_object() else { return record.clone() };
+    let mut details: Map<String, Value> = fields.clone();
+    let mut eve = Map::new();
+    // Suricata's own timestamp layout, e.g. 2024-05-01T12:00:00.123456+0000
+    let timestamp = text(record, "timestamp")
+        .and_then(|timestamp| DateTime::parse_from_rfc3339(timestamp).ok())
+        .map(|timestamp| timestamp.format("%Y-%m-%dT%H:%M:%S%.6f%z").to_string());
+    details.remove("timestamp");
+    if let Some(timestamp) = timestamp {
+        eve.insert("timestamp".to_string(), json!(timestamp));
+    }
+    eve.insert("event_type".to_string(), json!("muonfp"));
+    for (field, eve_field) in EVE_FIELDS {
+        if let Some(value) = details.remove(field) {
+            eve.insert(eve_field.to_string(), value);
+        }
+    }
+    let event_type = details.remove("event_type");
+    if event_type.is_none() {
+        eve.insert("proto".to_string(), json!("TCP"));
+    }
+    details.insert("type".to_string(), event_type.unwrap_or_else(|| json!("fingerprint")));
+    eve.insert("muonfp".to_string(), Value::Object(details));
+    Value::Object(eve)
+}
+
+// Record field, Zeek column name and Zeek type of every muonfp.log column
+const ZEEK_COLUMNS: [(&str, &str, &str); 15] = [
+    ("timestamp", "ts", "time"),
+    ("hostname", "host", "string"),
+    ("sensor_id", "sensor_id", "string"),
+    ("interface", "interface", "string"),
+    ("event_type", "event_type", "string"),
+    ("ip_address", "ip", "addr"),
+    ("src_port", "src_port", "port"),
+    ("dst_port", "dst_port", "port"),
+    ("muonfp_fingerprint", "fingerprint", "string"),
+    ("ja4t", "ja4t", "string"),
+    ("os_guess", "os_guess", "string"),
+    ("tool_guess", "tool_guess", "string"),
+    ("ttl", "ttl", "count"),
+    ("initial_ttl", "initial_ttl", "count"),
+    ("tags", "tags", "set[string]"),
+];
+
+fn zeek_header() -> String {
+    let (names, types): (Vec<&str>, Vec<&str>) = ZEEK_COLUMNS.iter().map(|(_, name, kind)| (*name, *kind)).unzip();
+    format!(
+        "#separator \\x09\n#set_separator\t,\n#empty_field\t(empty)\n#unset_field\t-\n#path\tmuonfp\n#open\t{}\n#fields\t{}\n#types\t{}\n",
+        Utc::now().format("%Y-%m-%d-%H-%M-%S"),
+        names.join("\t"),
+        types.join("\t")
+    )
+}
+
+fn zeek(record: &Value) -> String {
+    let columns: Vec<String> = ZEEK_COLUMNS
+        .iter()
+        .map(|(field, _, _)| match (*field, &record[*field]) {
+            // Fingerprints have no event type of their own
+            ("event_type", Value::Null) => "fingerprint".to_string(),
+            ("timestamp", Value::String(timestamp)) => DateTime::parse_from_rfc3339(timestamp)
+                .map(|timestamp| format!("{}.{:06}", timestamp.timestamp(), timestamp.timestamp_subsec_micros()))
+                .unwrap_or_else(|_| "-".to_string()),
+            (_, value) => zeek_value(value),
+        })
+        .collect();
+    columns.join("\t")
+}
+
+fn zeek_value(value: &Value) -> String {
+    match value {
+        Value::Null => "-".to_string(),
+        Value::Bool(value) => if *value { "T" } else { "F" }.to_string(),
+        Value::String(value) if value.is_empty() => "(empty)".to_string(),
+        Value::String(value) => escape_zeek(value),
+        Value::Array(values) if values.is_empty() => "(empty)".to_string(),
+        Value::Array(values) => values.iter().map(|value| zeek_value(value).replace(',', "\\x2c")).collect::<Vec<_>>().join(","),
+        value => escape_zeek(&value.to_string()),
+    }
+}
+
+// Separators inside a value are written as \x escapes, as Zeek does
+fn escape_zeek(value: &str) -> String {
+    value.replace('\\', "\\x5c").replace('\t', "\\x09").replace('\n', "\\x0a")
+}
+
+fn csv(columns: &[String], record: &Value) -> String {
+    let fields: Vec<String> = columns
+        .iter()
+        .map(|column| match (column.as_str(), &record[column.as_str()]) {
+            ("event_type", Value::Null) => "fingerprint".to_string(),
+            (_, value) => escape_csv(&csv_value(value)),
+        })
+        .collect();
+    fields.join(",")
+}
+
+fn csv_value(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(value) => value.clone(),
+        // Tags and other lists stay in one cell
+        Value::Array(values) => values.iter().map(csv_value).collect::<Vec<_>>().join(";"),
+        value => value.to_string(),
+    }
+}
+
+// RFC 4180: quote fields holding a delimiter, quote or line break
+fn escape_csv(value: &str) -> String {
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// Field numbers of the string fields of muonfp.v1.Record; the record itself is 15
+const PROTOBUF_TEXT_FIELDS: [(u64, &str); 9] = [
+    (2, "timestamp"),
+    (3, "hostname"),
+    (4, "sensor_id"),
+    (5, "interface"),
+    (6, "ip_address"),
+    (9, "muonfp_fingerprint"),
+    (10, "os_guess"),
+    (11, "tool_guess"),
+    (12, "ja4t"),
+];
+const PROTOBUF_PORT_FIELDS: [(u64, &str); 2] = [(7, "src_port"), (8, "dst_port")];
+const WIRE_TYPE_VARINT: u64 = 0;
+const WIRE_TYPE_LEN: u64 = 2;
+
+// Encodes a muonfp.v1.Record by hand, so files and streams don't need the
+// grpc feature. Fields at their default value are left out, as proto3 does.
+fn protobuf(record: &Value) -> Vec<u8> {
+    let mut message = Vec::new();
+    let put_text = |message: &mut Vec<u8>, number: u64, value: &str| {
+        if !value.is_empty() {
+            put_varint(message, number << 3 | WIRE_TYPE_LEN);
+            put_varint(message, value.len() as u64);
+            message.extend_from_slice(value.as_bytes());
+        }
+    };
+    put_text(&mut message, 1, record["event_type"].as_str().unwrap_or("fingerprint"));
+    for (number, field) in PROTOBUF_TEXT_FIELDS {
+        put_text(&mut message, number, record[field].as_str().unwrap_or_default());
+    }
+    for (number, field) in PROTOBUF_PORT_FIELDS {
+        if let Some(port) = record[field].as_u64().filter(|port| *port != 0) {
+            put_varint(&mut message, number << 3 | WIRE_TYPE_VARINT);
+            put_varint(&mut message, port);
+        }
+    }
+    put_text(&mut message, 15, &record.to_string());
+    message
+}
+
+fn put_varint(buffer: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buffer.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
+
+// Splits `{{ip_address}} {{os_guess}}` into text and field pieces. `\t` and
+// `\n` stand for tab and newline, as INI values can't hold them.
+fn parse_template(template: &str) -> Result<Vec<Piece>, String> {
+    let template = template.replace("\\t", "\t").replace("\\n", "\n");
+    let mut pieces = Vec::new();
+    let mut rest = template.as_str();
+    while let Some(start) = rest.find("{{") {
+        if start > 0 {
+            pieces.push(Piece::Text(rest[..start].to_string()));
+        }
+        let end = rest[start..].find("}}").ok_or("unclosed {{")? + start;
+        let path = rest[start + 2..end].trim();
+        if path.is_empty() {
+            return Err("empty {{}}".to_string());
+        }
+        pieces.push(Piece::Field(path.split('.').map(str::to_string).collect()));
+        rest = &rest[end + 2..];
+    }
+    if !rest.is_empty() {
+        pieces.push(Piece::Text(rest.to_string()));
+    }
+    Ok(pieces)
+}
+
+// Missing fields render as nothing, strings without quotes (control
+// characters escaped so a value can't start a new record), anything else as JSON
+fn template(pieces: &[Piece], record: &Value) -> String {
+    let mut line = String::new();
+    for piece in pieces {
+        match piece {
+            Piece::Text(text) => line.push_str(text),
+            Piece::Field(path) => match path.iter().try_fold(record, |value, name| match name.parse::<usize>() {
+                Ok(index) => value.get(index),
+                Err(_) => value.get(name),
+            }) {
+                Some(Value::String(value)) => line.push_str(&escape_template(value)),
+                Some(Value::Null) | None => {}
+                Some(value) => line.push_str(&value.to_string()),
+            },
+        }
+    }
+    line
+}
+
+fn escape_template(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\r' => escaped.push_str("\\r"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => escaped.push_str(&format!("\\x{:02x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn escape_cef_header(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('|', "\\|")
+}
+
+fn escape_cef_extension(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('=', "\\=").replace('\r', "\\r").replace('\n', "\\n")
+}
+
+// The tab delimits attributes, so it can't appear inside one
+fn escape_leef_attribute(value: &str) -> String {
+    value.replace('\t', " ").replace('\r', "\\r").replace('\n', "\\n")
+}
@@ -0,0 +1,52 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use chrono::Utc;
+use config::Config;
+use log::warn;
+use crate::sink::Sink;
+
+/// Rewrites a liveness file while the capture loop is making progress, so
+/// monit or a Kubernetes exec probe can check its age.
+pub struct Heartbeat {
+    path: PathBuf,
+    interval: Duration,
+    last_beat: Option<Instant>,
+    failing: bool,
+}
+
+impl Heartbeat {
+    /// Set up when `health.heartbeat_file` is set.
+    pub fn from_settings(settings: &Config) -> Result<Option<Self>, Box<dyn std::error::Error>> {
+        let Ok(path) = settings.get_string("health.heartbeat_file") else { return Ok(None) };
+        let mut heartbeat = Heartbeat {
+            path: PathBuf::from(path),
+            interval: Duration::from_secs(settings.get_int("health.heartbeat_interval").unwrap_or(10).max(1) as u64),
+            last_beat: None,
+            failing: false,
+        };
+        // Fail early on an unwritable path rather than at the first beat
+        fs::write(&heartbeat.path, Utc::now().to_rfc3339())?;
+        heartbeat.last_beat = Some(Instant::now());
+        Ok(Some(heartbeat))
+    }
+}
+
+impl Sink for Heartbeat {
+    fn send(&mut self, _record: &serde_json::Value) {}
+
+    fn heartbeat(&mut self) {
+        if self.last_beat.is_some_and(|last_beat| last_beat.elapsed() < self.interval) {
+            return;
+        }
+        match fs::write(&self.path, Utc::now().to_rfc3339()) {
+            Ok(()) => self.failing = false,
+            Err(e) if !self.failing => {
+                warn!("Failed to write heartbeat file {}: {}", self.path.display(), e);
+                self.failing = true;
+            }
+            Err(_) => {}
+        }
+        self.last_beat = Some(Instant::now());
+    }
+}
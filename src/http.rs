@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+use serde::Serialize;
+use crate::fingerprint::Connection;
+
+const METHODS: [&str; 9] = ["GET", "POST", "HEAD", "PUT", "DELETE", "OPTIONS", "PATCH", "CONNECT", "TRACE"];
+// Connections remembered before the table is reset
+const HTTP_MAX_CONNECTIONS: usize = 65536;
+
+/// Body of an `http` event.
+#[derive(Serialize)]
+pub struct HttpRequest {
+    pub method: String,
+    pub uri: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+    /// Fingerprint of the connection's SYN, when it was seen
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub muonfp_fingerprint: Option<String>,
+}
+
+/// Reports the first plaintext HTTP request of each connection alongside
+/// the fingerprint of the SYN that opened it.
+#[derive(Default)]
+pub struct HttpTracker {
+    // Client SYN fingerprint, or `None` once the connection has been reported
+    connections: HashMap<Connection, Option<String>>,
+}
+
+impl HttpTracker {
+    pub fn new() -> Self {
+        HttpTracker { connections: HashMap::new() }
+    }
+
+    /// Remembers the fingerprint of a client SYN opening `connection`.
+    pub fn syn(&mut self, connection: Connection, fingerprint: &str) {
+        self.make_room();
+        self.connections.insert(connection, Some(fingerprint.to_string()));
+    }
+
+    /// Inspects a segment sent by the client side of `connection`.
+    pub fn observe(&mut self, connection: Connection, payload: &[u8]) -> Option<HttpRequest> {
+        let mut request = parse_request(payload)?;
+        match self.connections.get_mut(&connection) {
+            Some(entry) => request.muonfp_fingerprint = Some(entry.take()?),
+            None => {
+                self.make_room();
+                self.connections.insert(connection, None);
+            }
+        }
+        Some(request)
+    }
+
+    fn make_room(&mut self) {
+        if self.connections.len() >= HTTP_MAX_CONNECTIONS {
+            self.connections.clear();
+        }
+    }
+}
+
+fn parse_request(payload: &[u8]) -> Option<HttpRequest> {
+    let space = payload.iter().take(8).position(|&byte| byte == b' ')?;
+    let method = std::str::from_utf8(&payload[..space]).ok()?;
+    if !METHODS.contains(&method) {
+        return None;
+    }
+
+    let text = String::from_utf8_lossy(payload);
+    let mut lines = text.split("\r\n");
+    let mut request_line = lines.next()?.split(' ');
+    let mut request = HttpRequest {
+        method: request_line.next()?.to_string(),
+        uri: request_line.next().unwrap_or("").to_string(),
+        host: None,
+        user_agent: None,
+        muonfp_fingerprint: None,
+    };
+    for line in lines.take_while(|line| !line.is_empty()) {
+        let Some((name, value)) = line.split_once(':') else { continue };
+        if name.eq_ignore_ascii_case("host") {
+            request.host = Some(value.trim().to_string());
+        } else if name.eq_ignore_ascii_case("user-agent") {
+            request.user_agent = Some(value.trim().to_string());
+        }
+    }
+    Some(request)
+}
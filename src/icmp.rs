@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use serde::Serialize;
+use crate::fingerprint::initial_ttl;
+
+const ICMP_ECHO_REQUEST: u8 = 8;
+// Hosts remembered before the history is reset
+const ICMP_MAX_HOSTS: usize = 65536;
+
+/// Body of an `icmp` event.
+#[derive(Serialize)]
+pub struct IcmpFingerprint {
+    pub ip_address: IpAddr,
+    /// `initial_ttl:df:payload_size:pattern`
+    pub icmp_fingerprint: String,
+    pub ttl: u8,
+    pub df: bool,
+    pub payload_size: usize,
+    pub pattern: String,
+}
+
+/// Fingerprints ICMP echo requests, reporting each source again only when
+/// its fingerprint changes, since pings tend to come in long runs.
+#[derive(Default)]
+pub struct IcmpTracker {
+    last_fingerprints: HashMap<IpAddr, String>,
+}
+
+impl IcmpTracker {
+    pub fn new() -> Self {
+        IcmpTracker { last_fingerprints: HashMap::new() }
+    }
+
+    /// Inspects an ICMP message sent by `ip`.
+    pub fn observe(&mut self, ip: IpAddr, ttl: u8, df: bool, icmp: &[u8]) -> Option<IcmpFingerprint> {
+        if icmp.len() < 8 || icmp[0] != ICMP_ECHO_REQUEST {
+            return None;
+        }
+        let payload = &icmp[8..];
+        let pattern = pattern(payload);
+        let icmp_fingerprint = format!("{}:{}:{}:{}", initial_ttl(ttl), df as u8, payload.len(), pattern);
+
+        if self.last_fingerprints.len() >= ICMP_MAX_HOSTS && !self.last_fingerprints.contains_key(&ip) {
+            self.last_fingerprints.clear();
+        }
+        if self.last_fingerprints.get(&ip) == Some(&icmp_fingerprint) {
+            return None;
+        }
+        self.last_fingerprints.insert(ip, icmp_fingerprint.clone());
+
+        Some(IcmpFingerprint { ip_address: ip, icmp_fingerprint, ttl, df, payload_size: payload.len(), pattern })
+    }
+}
+
+// Classifies the echo payload: Windows sends the alphabet, Unix pings an
+// optional timestamp followed by incrementing bytes, scanners often zeros.
+fn pattern(payload: &[u8]) -> String {
+    if payload.is_empty() {
+        return "empty".to_string();
+    }
+    if payload.iter().all(|&byte| byte == 0) {
+        return "zero".to_string();
+    }
+    if payload.starts_with(b"abcdefghijklmnopqrstuvw") {
+        return "alphabet".to_string();
+    }
+    for offset in [0, 8, 16] {
+        let Some(rest) = payload.get(offset..).filter(|rest| rest.len() >= 4) else { continue };
+        if rest.windows(2).all(|pair| pair[1] == pair[0].wrapping_add(1)) {
+            return format!("sequential@{}", offset);
+        }
+    }
+    "other".to_string()
+}
@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+use log::{info, warn};
+
+#[derive(Clone)]
+pub struct IntelSettings {
+    /// Blocklist files or http(s) URLs, one IP or CIDR per line
+    pub feeds: Vec<String>,
+    /// How often every feed is reloaded
+    pub refresh: Duration,
+}
+
+// Feed names by masked network, one table per prefix length
+type Networks = Vec<HashMap<u32, Vec<String>>>;
+
+/// IP blocklists, reloaded in the background so a slow feed server never
+/// holds up the capture loop.
+pub struct ThreatIntel {
+    networks: Arc<RwLock<Networks>>,
+}
+
+impl ThreatIntel {
+    /// Loads every feed once, then keeps refreshing them on a background thread.
+    pub fn new(settings: IntelSettings) -> Self {
+        let networks = Arc::new(RwLock::new(load_feeds(&settings.feeds)));
+        let shared = Arc::clone(&networks);
+        thread::spawn(move || loop {
+            thread::sleep(settings.refresh);
+            let reloaded = load_feeds(&settings.feeds);
+            if let Ok(mut networks) = shared.write() {
+                *networks = reloaded;
+            }
+        });
+        ThreatIntel { networks }
+    }
+
+    /// Names of the feeds listing `ip`.
+    pub fn matches(&self, ip: IpAddr) -> Vec<String> {
+        let IpAddr::V4(ip) = ip else { return Vec::new() };
+        let Ok(networks) = self.networks.read() else { return Vec::new() };
+        let address = u32::from(ip);
+        let mut feeds: Vec<String> = Vec::new();
+        for (length, table) in networks.iter().enumerate() {
+            for feed in table.get(&(address & mask(length))).into_iter().flatten() {
+                if !feeds.contains(feed) {
+                    feeds.push(feed.clone());
+                }
+            }
+        }
+        feeds
+    }
+}
+
+// A feed that fails to load is skipped until the next refresh.
+fn load_feeds(feeds: &[String]) -> Networks {
+    let mut networks = vec![HashMap::new(); 33];
+    for feed in feeds {
+        let text = if feed.starts_with("http://") || feed.starts_with("https://") {
+            ureq::get(feed).call().map_err(|e| e.to_string()).and_then(|response| response.into_string().map_err(|e| e.to_string()))
+        } else {
+            std::fs::read_to_string(feed).map_err(|e| e.to_string())
+        };
+        let text = match text {
+            Ok(text) => text,
+            Err(e) => {
+                warn!("Failed to load threat feed {}: {}", feed, e);
+                continue;
+            }
+        };
+        let name = feed_name(feed);
+        let mut entries = 0;
+        for (network, length) in text.lines().filter_map(parse_entry) {
+            let names: &mut Vec<String> = networks[length].entry(network).or_default();
+            if !names.contains(&name) {
+                names.push(name.clone());
+            }
+            entries += 1;
+        }
+        info!("Loaded {} entries from threat feed {}", entries, feed);
+    }
+    networks
+}
+
+// `drop` for both /etc/muonfp/drop.txt and https://www.spamhaus.org/drop/drop.txt
+fn feed_name(feed: &str) -> String {
+    let file_name = feed.trim_end_matches('/').rsplit('/').next().unwrap_or(feed);
+    file_name.split('.').next().filter(|stem| !stem.is_empty()).unwrap_or(file_name).to_string()
+}
+
+// Takes the first token of a line, so trailing `; SBL123` or `# comment` notes are ignored.
+fn parse_entry(line: &str) -> Option<(u32, usize)> {
+    let token = line.split_whitespace().next()?;
+    let (address, length) = match token.split_once('/') {
+        Some((address, length)) => (address, length.parse().ok().filter(|&length| length <= 32)?),
+        None => (token, 32),
+    };
+    let address: Ipv4Addr = address.parse().ok()?;
+    Some((u32::from(address) & mask(length), length))
+}
+
+fn mask(length: usize) -> u32 {
+    if length == 0 { 0 } else { u32::MAX << (32 - length) }
+}
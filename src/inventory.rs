@@ -0,0 +1,144 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Utc};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+// Distinct fingerprints kept per host, oldest dropped first
+const INVENTORY_MAX_FINGERPRINTS: usize = 16;
+// Hosts tracked before new ones are ignored
+const INVENTORY_MAX_HOSTS: usize = 65536;
+
+#[derive(Clone)]
+pub struct InventorySettings {
+    pub path: PathBuf,
+    /// How often the inventory is written back to `path`
+    pub interval: Duration,
+}
+
+/// Everything passively learned about one internal host.
+#[derive(Serialize, Deserialize)]
+pub struct Asset {
+    pub ip_address: IpAddr,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mac: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub os_guess: Option<String>,
+    #[serde(default)]
+    pub fingerprints: Vec<String>,
+    /// Ports the host answered a SYN on
+    #[serde(default)]
+    pub open_ports: BTreeSet<u16>,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+}
+
+/// A per-host inventory of the internal network, kept in a JSON file that
+/// survives restarts.
+pub struct Inventory {
+    settings: InventorySettings,
+    assets: HashMap<IpAddr, Asset>,
+    last_save: Instant,
+    dirty: bool,
+}
+
+impl Inventory {
+    /// Picks up where the existing file left off, if there is one.
+    pub fn new(settings: InventorySettings) -> Self {
+        let assets = match fs::read_to_string(&settings.path) {
+            Ok(text) => match serde_json::from_str::<Vec<Asset>>(&text) {
+                Ok(assets) => assets.into_iter().map(|asset| (asset.ip_address, asset)).collect(),
+                Err(e) => {
+                    warn!("Ignoring unreadable inventory {}: {}", settings.path.display(), e);
+                    HashMap::new()
+                }
+            },
+            Err(_) => HashMap::new(),
+        };
+        Inventory { settings, assets, last_save: Instant::now(), dirty: false }
+    }
+
+    /// Only private and link-local addresses are inventoried.
+    pub fn is_internal(ip: IpAddr) -> bool {
+        match ip {
+            IpAddr::V4(ip) => ip.is_private() || ip.is_link_local(),
+            IpAddr::V6(ip) => (ip.segments()[0] & 0xfe00) == 0xfc00 || (ip.segments()[0] & 0xffc0) == 0xfe80,
+        }
+    }
+
+    fn asset(&mut self, ip: IpAddr) -> Option<&mut Asset> {
+        if !Inventory::is_internal(ip) || (self.assets.len() >= INVENTORY_MAX_HOSTS && !self.assets.contains_key(&ip)) {
+            return None;
+        }
+        let now = Utc::now();
+        self.dirty = true;
+        let asset = self.assets.entry(ip).or_insert_with(|| Asset {
+            ip_address: ip,
+            mac: None,
+            os_guess: None,
+            fingerprints: Vec::new(),
+            open_ports: BTreeSet::new(),
+            first_seen: now,
+            last_seen: now,
+        });
+        asset.last_seen = now;
+        Some(asset)
+    }
+
+    pub fn observe_mac(&mut self, ip: IpAddr, mac: &str) {
+        if let Some(asset) = self.asset(ip) {
+            asset.mac = Some(mac.to_string());
+        }
+    }
+
+    pub fn observe_fingerprint(&mut self, ip: IpAddr, fingerprint: &str, os_guess: Option<&str>) {
+        if let Some(asset) = self.asset(ip) {
+            if !asset.fingerprints.iter().any(|known| known == fingerprint) {
+                if asset.fingerprints.len() >= INVENTORY_MAX_FINGERPRINTS {
+                    asset.fingerprints.remove(0);
+                }
+                asset.fingerprints.push(fingerprint.to_string());
+            }
+            if os_guess.is_some() {
+                asset.os_guess = os_guess.map(str::to_string);
+            }
+        }
+    }
+
+    pub fn observe_open_port(&mut self, ip: IpAddr, port: u16) {
+        if let Some(asset) = self.asset(ip) {
+            asset.open_ports.insert(port);
+        }
+    }
+
+    /// Writes the inventory out once per interval, if anything changed.
+    pub fn save_if_due(&mut self) {
+        if self.last_save.elapsed() >= self.settings.interval {
+            self.save();
+        }
+    }
+
+    /// Writes the inventory through a temporary file so readers never see
+    /// a partial one.
+    pub fn save(&mut self) {
+        self.last_save = Instant::now();
+        if !self.dirty {
+            return;
+        }
+        let mut assets: Vec<&Asset> = self.assets.values().collect();
+        assets.sort_by_key(|asset| asset.ip_address);
+        let path = &self.settings.path;
+        let temporary = path.with_extension("tmp");
+        let result = serde_json::to_vec_pretty(&assets)
+            .map_err(std::io::Error::from)
+            .and_then(|json| fs::write(&temporary, json))
+            .and_then(|()| fs::rename(&temporary, path));
+        match result {
+            Ok(()) => self.dirty = false,
+            Err(e) => warn!("Failed to save inventory {}: {}", path.display(), e),
+        }
+    }
+}
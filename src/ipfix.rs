@@ -0,0 +1,141 @@
+use std::io;
+use std::net::{IpAddr, UdpSocket};
+use std::time::{Duration, Instant};
+use config::Config;
+use log::warn;
+use crate::fingerprint::Fingerprint;
+
+const IPFIX_VERSION: u16 = 10;
+const TEMPLATE_SET_ID: u16 = 2;
+const TEMPLATE_ID: u16 = 256;
+// Templates are resent periodically since UDP collectors may restart
+const TEMPLATE_REFRESH: Duration = Duration::from_secs(60);
+// RFC 5612 documentation PEN, used until a real enterprise number is configured
+const DEFAULT_ENTERPRISE: u32 = 32473;
+const VARIABLE_LENGTH: u16 = 65535;
+
+// Enterprise-specific information elements
+const IE_MUONFP_FINGERPRINT: u16 = 1;
+const IE_OS_GUESS: u16 = 2;
+
+// (IANA element ID, length) for the standard fields of the template
+const FIELDS: [(u16, u16); 7] = [
+    (152, 8), // flowStartMilliseconds
+    (8, 4),   // sourceIPv4Address
+    (7, 2),   // sourceTransportPort
+    (11, 2),  // destinationTransportPort
+    (4, 1),   // protocolIdentifier
+    (6, 2),   // tcpControlBits
+    (192, 1), // ipTTL
+];
+
+/// Sends fingerprints as IPFIX data records to a UDP collector, so flow
+/// pipelines can ingest them without tailing files.
+pub struct IpfixExporter {
+    socket: UdpSocket,
+    domain: u32,
+    enterprise: u32,
+    sequence: u32,
+    template_sent: Option<Instant>,
+}
+
+impl IpfixExporter {
+    /// Builds the exporter from the `[ipfix]` section. Returns `None` when no
+    /// collector is configured.
+    pub fn from_settings(settings: &Config) -> Result<Option<Self>, Box<dyn std::error::Error>> {
+        let Ok(collector) = settings.get_string("ipfix.collector") else {
+            return Ok(None);
+        };
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect(&collector)?;
+        Ok(Some(IpfixExporter {
+            socket,
+            domain: settings.get_int("ipfix.domain").unwrap_or(0) as u32,
+            enterprise: settings.get_int("ipfix.enterprise").unwrap_or(DEFAULT_ENTERPRISE as i64) as u32,
+            sequence: 0,
+            template_sent: None,
+        }))
+    }
+
+    /// Exports one fingerprint. Send failures are logged, never fatal.
+    pub fn export(&mut self, fingerprint: &Fingerprint) {
+        if let Err(e) = self.send(fingerprint) {
+            warn!("Failed to export IPFIX record: {}", e);
+        }
+    }
+
+    fn send(&mut self, fingerprint: &Fingerprint) -> io::Result<()> {
+        let IpAddr::V4(ip) = fingerprint.ip_address else {
+            return Ok(());
+        };
+
+        let mut sets = Vec::new();
+        if self.template_sent.map_or(true, |at| at.elapsed() >= TEMPLATE_REFRESH) {
+            sets.extend(self.template_set());
+            self.template_sent = Some(Instant::now());
+        }
+
+        let mut record = Vec::new();
+        record.extend((fingerprint.timestamp.timestamp_millis() as u64).to_be_bytes());
+        record.extend(ip.octets());
+        record.extend(fingerprint.src_port.to_be_bytes());
+        record.extend(fingerprint.dst_port.to_be_bytes());
+        record.push(6);
+        let flags: u16 = if fingerprint.packet_type == "syn-ack" { 0x12 } else { 0x02 };
+        record.extend(flags.to_be_bytes());
+        record.push(fingerprint.ttl);
+        push_string(&mut record, &fingerprint.muonfp_fingerprint);
+        push_string(&mut record, fingerprint.os_guess.as_deref().unwrap_or(""));
+        sets.extend(set(TEMPLATE_ID, &record));
+
+        let mut message = Vec::with_capacity(16 + sets.len());
+        message.extend(IPFIX_VERSION.to_be_bytes());
+        message.extend(((16 + sets.len()) as u16).to_be_bytes());
+        message.extend((fingerprint.timestamp.timestamp() as u32).to_be_bytes());
+        message.extend(self.sequence.to_be_bytes());
+        message.extend(self.domain.to_be_bytes());
+        message.extend(sets);
+        self.socket.send(&message)?;
+
+        // The sequence number counts data records, not messages
+        self.sequence = self.sequence.wrapping_add(1);
+        Ok(())
+    }
+
+    fn template_set(&self) -> Vec<u8> {
+        let enterprise_fields = [IE_MUONFP_FINGERPRINT, IE_OS_GUESS];
+        let mut template = Vec::new();
+        template.extend(TEMPLATE_ID.to_be_bytes());
+        template.extend(((FIELDS.len() + enterprise_fields.len()) as u16).to_be_bytes());
+        for (id, length) in FIELDS {
+            template.extend(id.to_be_bytes());
+            template.extend(length.to_be_bytes());
+        }
+        for id in enterprise_fields {
+            template.extend((id | 0x8000).to_be_bytes());
+            template.extend(VARIABLE_LENGTH.to_be_bytes());
+            template.extend(self.enterprise.to_be_bytes());
+        }
+        set(TEMPLATE_SET_ID, &template)
+    }
+}
+
+fn set(id: u16, body: &[u8]) -> Vec<u8> {
+    let mut set = Vec::with_capacity(4 + body.len());
+    set.extend(id.to_be_bytes());
+    set.extend(((4 + body.len()) as u16).to_be_bytes());
+    set.extend(body);
+    set
+}
+
+// Variable-length encoding from RFC 7011 section 7
+fn push_string(record: &mut Vec<u8>, value: &str) {
+    let bytes = &value.as_bytes()[..value.len().min(u16::MAX as usize)];
+    if bytes.len() < 255 {
+        record.push(bytes.len() as u8);
+    } else {
+        record.push(255);
+        record.extend((bytes.len() as u16).to_be_bytes());
+    }
+    record.extend(bytes);
+}
@@ -0,0 +1,96 @@
+use std::io;
+use std::os::unix::net::UnixDatagram;
+use std::sync::OnceLock;
+use log::{Level, Log, Metadata, Record};
+
+const JOURNAL_SOCKET: &str = "/run/systemd/journal/socket";
+pub const SYSLOG_IDENTIFIER: &str = "muonfp";
+
+/// Opens a datagram socket to journald's native protocol socket.
+pub fn connect() -> io::Result<UnixDatagram> {
+    let socket = UnixDatagram::unbound()?;
+    socket.connect(JOURNAL_SOCKET)?;
+    Ok(socket)
+}
+
+/// Encodes journal fields; values containing newlines use the
+/// length-prefixed binary form.
+pub fn encode(fields: &[(&str, &str)]) -> Vec<u8> {
+    let mut entry = Vec::new();
+    for (name, value) in fields {
+        entry.extend_from_slice(name.as_bytes());
+        if value.contains('\n') {
+            entry.push(b'\n');
+            entry.extend_from_slice(&(value.len() as u64).to_le_bytes());
+        } else {
+            entry.push(b'=');
+        }
+        entry.extend_from_slice(value.as_bytes());
+        entry.push(b'\n');
+    }
+    entry
+}
+
+/// The process logger: env_logger until `log_to_journal` succeeds, journald after.
+struct Logger {
+    env_logger: env_logger::Logger,
+    journal: OnceLock<UnixDatagram>,
+}
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+/// Installs the logger, filtered by RUST_LOG like before.
+pub fn init_logger() {
+    let env_logger = env_logger::Builder::from_default_env().build();
+    let max_level = env_logger.filter();
+    let logger = LOGGER.get_or_init(|| Logger { env_logger, journal: OnceLock::new() });
+    if log::set_logger(logger).is_ok() {
+        log::set_max_level(max_level);
+    }
+}
+
+/// Sends operational logs to journald from now on.
+pub fn log_to_journal() -> io::Result<()> {
+    let socket = connect()?;
+    if let Some(logger) = LOGGER.get() {
+        let _ = logger.journal.set(socket);
+    }
+    Ok(())
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.env_logger.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.env_logger.matches(record) {
+            return;
+        }
+        let Some(journal) = self.journal.get() else {
+            self.env_logger.log(record);
+            return;
+        };
+        let priority = match record.level() {
+            Level::Error => "3",
+            Level::Warn => "4",
+            Level::Info => "6",
+            Level::Debug | Level::Trace => "7",
+        };
+        let message = record.args().to_string();
+        let line = record.line().map(|line| line.to_string()).unwrap_or_default();
+        let entry = encode(&[
+            ("MESSAGE", &message),
+            ("PRIORITY", priority),
+            ("SYSLOG_IDENTIFIER", SYSLOG_IDENTIFIER),
+            ("CODE_FILE", record.file().unwrap_or_default()),
+            ("CODE_LINE", &line),
+            ("TARGET", record.target()),
+        ]);
+        if journal.send(&entry).is_err() {
+            self.env_logger.log(record);
+        }
+    }
+
+    fn flush(&self) {}
+}
@@ -1,33 +1,140 @@
 use std::io::Write;
-use std::net::IpAddr;
 use std::path::{Path, PathBuf};
 use std::env;
+use std::sync::Arc;
 use std::time::Duration;
 use pnet::packet::Packet;
-use pnet::packet::ipv4::Ipv4Packet;
 use log::{info, error, warn};
 use hostname;
 use config::{Config, File as ConfigFile, FileFormat};
-use ctrlc;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
 
 mod fingerprint;
 mod rotating_writer;
 mod network_tap;
+mod stats;
+mod output;
+mod supervisor;
+mod uploader;
+mod sink;
+mod api;
+mod health;
+#[cfg(feature = "tui")]
+mod top;
+mod signals;
+mod journald;
+mod format;
+mod decode;
+mod fingerprinter;
+mod p0f;
+mod signatures;
+mod scanner;
+mod nat;
+mod event;
+mod anomaly;
+mod asn;
+mod dedup;
+mod aggregate;
+mod flow;
+mod ipfix;
+mod sflow;
+mod quic;
+mod rdns;
+mod tls;
+mod ssh;
+mod http;
+mod dhcp;
+mod dns;
+mod icmp;
+mod intel;
+mod inventory;
+mod neighbors;
+mod oui;
+
+use rotating_writer::{RotatingFileWriter, FileOwnership, lookup_uid, lookup_gid};
+use network_tap::{NetworkTap, pcap_global_header, pcap_record};
+use stats::Stats;
+use output::FingerprintOutput;
+use supervisor::{child_stats_line, Supervisor};
+use uploader::Uploader;
+use sink::Sinks;
+use signals::Signals;
+use format::Format;
+use fingerprinter::{Fingerprinter, FingerprintOptions};
+use signatures::SignatureDb;
+use oui::OuiDb;
+use asn::AsnDb;
+use scanner::ScannerSettings;
+use nat::NatSettings;
+use dedup::DedupSettings;
+use aggregate::SummarySettings;
+use ipfix::IpfixExporter;
+use rdns::RdnsSettings;
+use intel::IntelSettings;
+use inventory::InventorySettings;
 
-use fingerprint::{Fingerprint, extract_tcp_options, is_syn_packet};
-use rotating_writer::RotatingFileWriter;
-use network_tap::{NetworkTap, pcap_global_header, pcap_packet_header};
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60); // Flush every 60 seconds
+const STATS_INTERVAL: Duration = Duration::from_secs(5); // Counters handed to the sinks
 
 struct AppConfig {
-    interface: String,
+    interfaces: Vec<String>,
+    fingerprints_enabled: bool,
+    fingerprint_files: bool,
+    fingerprint_stdout: bool,
     fingerprints_dir: String,
+    fingerprint_options: FingerprintOptions,
+    pcap_enabled: bool,
     pcap_dir: String,
+    pcap_mode: PcapMode,
+    dns_dir: Option<String>,
     max_file_size: u64,
+    shutdown_timeout: Duration,
+    stats_interval: Option<Duration>,
+    output_sync: bool,
+    output_ownership: FileOwnership,
+    output_manifest: bool,
+    output_format: Format,
+    supervisor: bool,
+    restart_delay: Duration,
+    settings: Config,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum PcapMode {
+    /// Every captured frame
+    Full,
+    /// Only the SYN/SYN-ACK frames that produced a fingerprint
+    SynOnly,
+}
+
+struct CliArgs {
+    interface: Option<String>,
+    child: bool,
+    /// Fingerprints go to stdout instead of the .out files
+    stdout: bool,
+    /// `muonfp top`, showing a running sensor's API at `url`
+    top: bool,
+    url: Option<String>,
+    token: Option<String>,
+}
+
+fn parse_args() -> Result<CliArgs, Box<dyn std::error::Error>> {
+    let mut cli = CliArgs { interface: None, child: false, stdout: false, top: false, url: None, token: None };
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--interface" => cli.interface = Some(args.next().ok_or("--interface requires a value")?),
+            "--child" => cli.child = true,
+            "--stdout" => cli.stdout = true,
+            "top" => cli.top = true,
+            "--url" => cli.url = Some(args.next().ok_or("--url requires a value")?),
+            "--token" => cli.token = Some(args.next().ok_or("--token requires a value")?),
+            _ => return Err(format!("Unknown argument: {}", arg).into()),
+        }
+    }
+    Ok(cli)
 }
 
-fn read_config() -> Result<AppConfig, Box<dyn std::error::Error>> {
+fn read_config(stdout: bool) -> Result<AppConfig, Box<dyn std::error::Error>> {
     let config_paths = [
         PathBuf::from("muonfp.conf"),
         PathBuf::from("/etc/muonfp.conf"),
@@ -46,16 +153,229 @@ fn read_config() -> Result<AppConfig, Box<dyn std::error::Error>> {
 
     let settings = builder.build()?;
 
+    // `pcap=<dir>` is the classic form; a `[pcap]` section takes `dir=` instead
+    let pcap_enabled = settings.get_bool("pcap.enabled").unwrap_or(true);
+    let pcap_dir = match settings.get_string("pcap").or_else(|_| settings.get_string("pcap.dir")) {
+        Ok(dir) => dir,
+        Err(e) if pcap_enabled => return Err(e.into()),
+        Err(_) => String::new(),
+    };
+
+    // Same for `fingerprints=<dir>` and a `[fingerprints]` section
+    let fingerprints_enabled = settings.get_bool("fingerprints.enabled").unwrap_or(true);
+    // Records can go only to the configured sinks or stdout, without .out files
+    let fingerprint_files = !stdout && settings.get_bool("fingerprints.files").unwrap_or(true);
+    let fingerprints_dir = match settings.get_string("fingerprints").or_else(|_| settings.get_string("fingerprints.dir")) {
+        Ok(dir) => dir,
+        Err(e) if fingerprints_enabled && fingerprint_files => return Err(e.into()),
+        Err(_) => String::new(),
+    };
+    if !pcap_enabled && !fingerprints_enabled {
+        return Err("Both pcap and fingerprints are disabled, nothing to do".into());
+    }
+
+    // Additional formats rendered next to the muonfp string, e.g. `formats = muonfp,ja4t`
+    let formats: Vec<String> = settings
+        .get_string("fingerprints.formats")
+        .unwrap_or_else(|_| "muonfp".to_string())
+        .split(',')
+        .map(|format| format.trim_matches(|c: char| c.is_whitespace() || "[]\"'".contains(c)).to_lowercase())
+        .collect();
+    for format in &formats {
+        if !matches!(format.as_str(), "muonfp" | "ja4t" | "ja4ts" | "p0f") {
+            return Err(format!("Unknown fingerprint format: {}", format).into());
+        }
+    }
+
+    let signatures = match settings.get_string("fingerprints.signatures") {
+        Ok(path) => {
+            let db = SignatureDb::load(Path::new(&path))?;
+            info!("Loaded {} signatures from {}", db.len(), path);
+            Some(Arc::new(db))
+        }
+        Err(_) => None,
+    };
+
+    // The built-in catalog can be replaced by an external file, or turned off
+    let catalog = match settings.get_string("fingerprints.catalog").as_deref() {
+        Ok("none") => None,
+        Ok(path) => {
+            let db = SignatureDb::load(Path::new(path))?;
+            info!("Loaded {} catalog entries from {}", db.len(), path);
+            Some(Arc::new(db))
+        }
+        Err(_) => Some(Arc::new(SignatureDb::builtin())),
+    };
+
+    // MAC vendors are opt-in, from the embedded subset or a full IEEE oui.txt
+    let oui = match settings.get_string("fingerprints.oui").as_deref() {
+        Ok("builtin") => Some(Arc::new(OuiDb::builtin())),
+        Ok(path) => {
+            let db = OuiDb::load(Path::new(path))?;
+            info!("Loaded {} OUI entries from {}", db.len(), path);
+            Some(Arc::new(db))
+        }
+        Err(_) => None,
+    };
+
+    let asn = match settings.get_string("asn.db") {
+        Ok(path) => {
+            let names = settings.get_string("asn.names").ok();
+            let db = AsnDb::load(Path::new(&path), names.as_deref().map(Path::new))?;
+            info!("Loaded ASN database {}", path);
+            Some(Arc::new(db))
+        }
+        Err(_) => None,
+    };
+
+    let pcap_mode = match settings.get_string("pcap.mode").as_deref() {
+        Ok("full") | Err(_) => PcapMode::Full,
+        Ok("syn-only") => PcapMode::SynOnly,
+        Ok(other) => return Err(format!("Unknown pcap.mode: {}", other).into()),
+    };
+    if pcap_mode == PcapMode::SynOnly && !fingerprints_enabled {
+        return Err("pcap.mode = syn-only requires fingerprints to be enabled".into());
+    }
+
+    // Passive DNS goes to its own rotating files
+    let dns_dir = if settings.get_bool("dns.enabled").unwrap_or(false) {
+        if !fingerprints_enabled {
+            return Err("dns requires fingerprints to be enabled".into());
+        }
+        Some(settings.get_string("dns.dir")?)
+    } else {
+        None
+    };
+
+    let output_ownership = FileOwnership {
+        mode: match settings.get_string("output.mode") {
+            Ok(mode) => Some(u32::from_str_radix(mode.trim_start_matches("0o"), 8)
+                .map_err(|_| format!("Invalid output.mode: {}", mode))?),
+            Err(_) => None,
+        },
+        uid: settings.get_string("output.user").ok().map(|user| lookup_uid(&user)).transpose()?,
+        gid: settings.get_string("output.group").ok().map(|group| lookup_gid(&group)).transpose()?,
+    };
+
     Ok(AppConfig {
-        interface: settings.get_string("interface")?,
-        fingerprints_dir: settings.get_string("fingerprints")?,
-        pcap_dir: settings.get_string("pcap")?,
+        // sFlow replaces the capture interface, whose name then only labels records
+        interfaces: settings
+            .get_string("interface")
+            .or_else(|e| if settings.get_string("sflow.listen").is_ok() { Ok("sflow".to_string()) } else { Err(e) })?
+            .split(',')
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty())
+            .collect(),
+        fingerprints_enabled,
+        fingerprint_files,
+        fingerprint_stdout: stdout,
+        fingerprints_dir,
+        fingerprint_options: FingerprintOptions {
+            sensor_id: settings.get_string("sensor_id").ok(),
+            ip_behavior: settings.get_bool("fingerprints.ip_behavior").unwrap_or(false),
+            options_hex: settings.get_bool("fingerprints.options_hex").unwrap_or(false),
+            verbose_options: settings.get_bool("fingerprints.verbose_options").unwrap_or(false),
+            ja4t: formats.iter().any(|format| format.starts_with("ja4t")),
+            p0f: formats.iter().any(|format| format == "p0f"),
+            quic: settings.get_bool("fingerprints.quic").unwrap_or(false),
+            ssh: settings.get_bool("fingerprints.ssh").unwrap_or(false),
+            http: settings.get_bool("fingerprints.http").unwrap_or(false),
+            dhcp: settings.get_bool("fingerprints.dhcp").unwrap_or(false),
+            icmp: settings.get_bool("fingerprints.icmp").unwrap_or(false),
+            neighbors: settings.get_bool("fingerprints.neighbors").unwrap_or(false),
+            dns: dns_dir.is_some(),
+            signatures,
+            catalog,
+            oui,
+            asn,
+            rdns: if settings.get_bool("rdns.enabled").unwrap_or(false) {
+                Some(RdnsSettings {
+                    rate: settings.get_int("rdns.rate").unwrap_or(10) as u32,
+                    ttl: Duration::from_secs(settings.get_int("rdns.ttl").unwrap_or(3600) as u64),
+                })
+            } else {
+                None
+            },
+            intel: match settings.get_string("intel.feeds") {
+                Ok(feeds) => Some(IntelSettings {
+                    feeds: feeds.split(',').map(|feed| feed.trim().to_string()).filter(|feed| !feed.is_empty()).collect(),
+                    refresh: Duration::from_secs(settings.get_int("intel.refresh").unwrap_or(3600) as u64),
+                }),
+                Err(_) => None,
+            },
+            inventory: if settings.get_bool("inventory.enabled").unwrap_or(false) {
+                Some(InventorySettings {
+                    path: PathBuf::from(settings.get_string("inventory.file")?),
+                    interval: Duration::from_secs(settings.get_int("inventory.interval").unwrap_or(60) as u64),
+                })
+            } else {
+                None
+            },
+            scanner: if settings.get_bool("scanner.enabled").unwrap_or(false) {
+                Some(ScannerSettings {
+                    window: Duration::from_secs(settings.get_int("scanner.window").unwrap_or(60) as u64),
+                    ports: settings.get_int("scanner.ports").unwrap_or(25) as usize,
+                })
+            } else {
+                None
+            },
+            nat: if settings.get_bool("nat.enabled").unwrap_or(false) {
+                Some(NatSettings {
+                    window: Duration::from_secs(settings.get_int("nat.window").unwrap_or(300) as u64),
+                    interval: Duration::from_secs(settings.get_int("nat.interval").unwrap_or(60) as u64),
+                })
+            } else {
+                None
+            },
+            anomaly_ttl: if settings.get_bool("anomaly.enabled").unwrap_or(false) {
+                Some(Duration::from_secs(settings.get_int("anomaly.ttl").unwrap_or(3600) as u64))
+            } else {
+                None
+            },
+            dedup: if settings.get_bool("dedup.enabled").unwrap_or(false) {
+                Some(DedupSettings {
+                    ttl: Duration::from_secs(settings.get_int("dedup.ttl").unwrap_or(300) as u64),
+                    interval: Duration::from_secs(settings.get_int("dedup.interval").unwrap_or(60) as u64),
+                })
+            } else {
+                None
+            },
+            summary: if settings.get_bool("summary.enabled").unwrap_or(false) {
+                Some(SummarySettings {
+                    interval: Duration::from_secs(settings.get_int("summary.interval").unwrap_or(300) as u64),
+                    top: settings.get_int("summary.top").unwrap_or(100) as usize,
+                })
+            } else {
+                None
+            },
+            flow_timeout: if settings.get_bool("flows.enabled").unwrap_or(false) {
+                Some(Duration::from_secs(settings.get_int("flows.timeout").unwrap_or(30) as u64))
+            } else {
+                None
+            },
+        },
+        pcap_enabled,
+        pcap_dir,
+        pcap_mode,
+        dns_dir,
         max_file_size: settings.get_int("max_file_size")? as u64 * 1024 * 1024,
+        shutdown_timeout: Duration::from_secs(settings.get_int("shutdown_timeout").unwrap_or(10) as u64),
+        // 0 turns the periodic summary off
+        stats_interval: Some(settings.get_int("stats.interval").unwrap_or(60))
+            .filter(|&seconds| seconds > 0)
+            .map(|seconds| Duration::from_secs(seconds as u64)),
+        output_sync: settings.get_bool("output.sync").unwrap_or(false),
+        output_ownership,
+        output_manifest: settings.get_bool("output.manifest").unwrap_or(false),
+        output_format: Format::from_settings(&settings, "output")?,
+        supervisor: settings.get_bool("supervisor.enabled").unwrap_or(false),
+        restart_delay: Duration::from_secs(settings.get_int("supervisor.restart_delay").unwrap_or(5) as u64),
+        settings,
     })
 }
 
 fn main() {
-    env_logger::init();
+    journald::init_logger();
     info!("MuonFP v.1.3");
 
     if let Err(e) = run() {
@@ -65,111 +385,379 @@ fn main() {
 }
 
 fn run() -> Result<(), Box<dyn std::error::Error>> {
-    let config = read_config()?;
+    let args = parse_args()?;
+    if args.top {
+        let url = args.url.as_deref().unwrap_or("http://127.0.0.1:8080");
+        #[cfg(feature = "tui")]
+        return top::run(url, args.token.as_deref());
+        #[cfg(not(feature = "tui"))]
+        return Err(format!("muonfp top requires building with --features tui (API at {})", url).into());
+    }
+    let config = read_config(args.stdout)?;
 
-    // Validate directories
-    if !Path::new(&config.fingerprints_dir).is_dir() {
-        return Err(format!("Fingerprints directory does not exist: {}", config.fingerprints_dir).into());
+    if config.settings.get_bool("journald.logs").unwrap_or(false) {
+        journald::log_to_journal()?;
     }
-    if !Path::new(&config.pcap_dir).is_dir() {
-        return Err(format!("PCAP directory does not exist: {}", config.pcap_dir).into());
+
+    let interfaces = match &args.interface {
+        Some(interface) => vec![interface.clone()],
+        None => config.interfaces.clone(),
+    };
+    if interfaces.is_empty() {
+        return Err("No capture interface configured".into());
     }
 
-    let mut network_tap = NetworkTap::new(&config.interface)?;
-    let local_ips = network_tap.local_ips.clone();
+    // Setup graceful shutdown and rotate-on-demand
+    let signals = Signals::install()?;
 
-    // Create rotating writers
+    let hostname = hostname::get()?.to_string_lossy().into_owned();
+
+    let mut uploader = Uploader::from_settings(&config.settings, &hostname)?;
+
+    // Supervised children leave the sinks to their parent
+    let mut sinks = if args.child { Sinks::default() } else { Sinks::from_settings(&config.settings)? };
+
+    let result = if !args.child && (config.supervisor || interfaces.len() > 1) {
+        run_supervisor(&config, &interfaces, uploader.as_ref(), &mut sinks, &signals)
+    } else {
+        run_capture(&config, &interfaces[0], &hostname, args.child, uploader.as_ref(), &mut sinks, &signals)
+    };
+
+    sinks.close();
+    if let Some(uploader) = uploader.as_mut() {
+        uploader.finish();
+    }
+
+    result
+}
+
+// Queues every file the writer completes for upload.
+fn attach_uploader(writer: &mut RotatingFileWriter, uploader: Option<&Uploader>) {
+    if let Some(uploader) = uploader {
+        let sender = uploader.sender();
+        writer.set_on_complete(move |path| {
+            let _ = sender.send(path.to_path_buf());
+        });
+    }
+}
+
+fn open_fingerprint_writer(config: &AppConfig, uploader: Option<&Uploader>) -> std::io::Result<RotatingFileWriter> {
+    let header = config.output_format.header();
+    let mut fingerprint_writer = RotatingFileWriter::new(
+        Path::new(&config.fingerprints_dir).join("muonfp"),
+        config.max_file_size,
+        "out",
+        move |file| match &header {
+            Some(header) => file.write_all(header.as_bytes()),
+            None => Ok(()),
+        }
+    )?;
+    fingerprint_writer.set_sync(config.output_sync);
+    fingerprint_writer.set_manifest(config.output_manifest);
+    fingerprint_writer.set_ownership(config.output_ownership.clone())?;
+    attach_uploader(&mut fingerprint_writer, uploader);
+    Ok(fingerprint_writer)
+}
+
+// For --stdout, starting with the format's header like a new file would
+fn open_stdout(config: &AppConfig) -> std::io::Result<FingerprintOutput> {
+    let mut stdout = std::io::stdout();
+    if let Some(header) = config.output_format.header() {
+        stdout.write_all(header.as_bytes())?;
+    }
+    Ok(FingerprintOutput::Stdout(stdout))
+}
+
+fn open_pcap_writer(config: &AppConfig, name: &str, uploader: Option<&Uploader>) -> std::io::Result<RotatingFileWriter> {
     let pcap_global_header = pcap_global_header();
     let mut pcap_writer = RotatingFileWriter::new(
-        Path::new(&config.pcap_dir).join("packets"),
+        Path::new(&config.pcap_dir).join(name),
         config.max_file_size,
         "pcap",
         move |file| file.write_all(&pcap_global_header)
     )?;
-    let mut fingerprint_writer = RotatingFileWriter::new(
-        Path::new(&config.fingerprints_dir).join("muonfp"),
+    pcap_writer.set_sync(config.output_sync);
+    pcap_writer.set_manifest(config.output_manifest);
+    pcap_writer.set_ownership(config.output_ownership.clone())?;
+    attach_uploader(&mut pcap_writer, uploader);
+    Ok(pcap_writer)
+}
+
+fn open_dns_writer(config: &AppConfig, dir: &str, name: &str, uploader: Option<&Uploader>) -> std::io::Result<RotatingFileWriter> {
+    let mut dns_writer = RotatingFileWriter::new(
+        Path::new(dir).join(name),
         config.max_file_size,
         "out",
         |_| Ok(())
     )?;
+    dns_writer.set_sync(config.output_sync);
+    dns_writer.set_manifest(config.output_manifest);
+    dns_writer.set_ownership(config.output_ownership.clone())?;
+    attach_uploader(&mut dns_writer, uploader);
+    Ok(dns_writer)
+}
 
-    info!("Listening on interface: {}", config.interface);
+// Sizes of the files currently being written, for the stats summary
+fn file_sizes(fingerprint_writer: Option<&FingerprintOutput>, pcap_writer: Option<&RotatingFileWriter>) -> String {
+    let mut sizes = Vec::new();
+    if let Some(size) = fingerprint_writer.and_then(FingerprintOutput::current_size) {
+        sizes.push(format!("fingerprints {} bytes", size));
+    }
+    if let Some(pcap_writer) = pcap_writer {
+        sizes.push(format!("pcap {} bytes", pcap_writer.current_size()));
+    }
+    if sizes.is_empty() { "none".to_string() } else { sizes.join(", ") }
+}
 
-    // Setup graceful shutdown
-    let running = Arc::new(AtomicBool::new(true));
-    let r = running.clone();
-    ctrlc::set_handler(move || {
-        r.store(false, Ordering::SeqCst);
-    })?;
+// Don't let a hung filesystem keep the process alive forever. The uploader
+// bounds its own wait, which is added to the timeout.
+fn spawn_shutdown_watchdog(shutdown_timeout: Duration) {
+    std::thread::spawn(move || {
+        std::thread::sleep(shutdown_timeout);
+        error!("Shutdown did not complete within {}s, exiting", shutdown_timeout.as_secs());
+        std::process::exit(1);
+    });
+}
 
-    let hostname = hostname::get()?.to_string_lossy().into_owned();
+fn run_supervisor(
+    config: &AppConfig,
+    interfaces: &[String],
+    uploader: Option<&Uploader>,
+    sinks: &mut Sinks,
+    signals: &Signals,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if config.fingerprints_enabled && config.fingerprint_files && !Path::new(&config.fingerprints_dir).is_dir() {
+        return Err(format!("Fingerprints directory does not exist: {}", config.fingerprints_dir).into());
+    }
+
+    let mut fingerprint_output = if config.fingerprints_enabled && config.fingerprint_stdout {
+        Some(open_stdout(config)?)
+    } else if config.fingerprints_enabled && config.fingerprint_files {
+        Some(FingerprintOutput::File(open_fingerprint_writer(config, uploader)?))
+    } else {
+        None
+    };
+
+    info!("Supervising capture on interfaces: {}", interfaces.join(", "));
+    let mut supervisor = Supervisor::new(interfaces, config.restart_delay);
+    let fingerprints_written = supervisor.run(&mut fingerprint_output, &config.output_format, sinks, signals, FLUSH_INTERVAL, config.stats_interval)?;
+
+    info!("Shutting down...");
+    spawn_shutdown_watchdog(config.shutdown_timeout + uploader.map_or(Duration::ZERO, Uploader::shutdown_timeout));
+    if let Some(fingerprint_output) = fingerprint_output.as_mut() {
+        fingerprint_output.close()?;
+    }
+
+    let stats = supervisor.stats();
+    if stats.kernel_drops > 0 {
+        warn!("The kernel dropped {} packets that were never captured; the sensor may be undersized", stats.kernel_drops);
+    }
+    info!("Shutdown summary: {}; {} records merged", stats.summary(), fingerprints_written);
+
+    Ok(())
+}
+
+// Captures on a single interface. As a supervised child, fingerprints are
+// streamed to stdout for the parent to merge instead of written to disk.
+fn run_capture(
+    config: &AppConfig,
+    interface: &str,
+    hostname: &str,
+    child: bool,
+    uploader: Option<&Uploader>,
+    sinks: &mut Sinks,
+    signals: &Signals,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Validate directories
+    if config.fingerprints_enabled && config.fingerprint_files && !child && !Path::new(&config.fingerprints_dir).is_dir() {
+        return Err(format!("Fingerprints directory does not exist: {}", config.fingerprints_dir).into());
+    }
+    if config.pcap_enabled && !Path::new(&config.pcap_dir).is_dir() {
+        return Err(format!("PCAP directory does not exist: {}", config.pcap_dir).into());
+    }
+    if let Some(dns_dir) = config.dns_dir.as_deref().filter(|dir| !Path::new(dir).is_dir()) {
+        return Err(format!("DNS directory does not exist: {}", dns_dir).into());
+    }
+
+    // pnet's raw sockets unless capture=pcap asks for libpcap
+    let capture = config.settings.get_string("capture").unwrap_or_else(|_| "packet".to_string());
+    let mut network_tap = match config.settings.get_string("sflow.listen") {
+        Ok(listen) => NetworkTap::sflow(&listen)?,
+        Err(_) => match capture.as_str() {
+            "packet" => NetworkTap::new(interface)?,
+            #[cfg(feature = "pcap")]
+            "pcap" => NetworkTap::pcap(interface)?,
+            #[cfg(not(feature = "pcap"))]
+            "pcap" => return Err("capture=pcap requires building with --features pcap".into()),
+            _ => return Err(format!("Unknown capture backend: {}", capture).into()),
+        },
+    };
+    let mut fingerprint_options = config.fingerprint_options.clone();
+    // Each supervised child keeps its own inventory, like its own pcap files
+    if let Some(inventory) = fingerprint_options.inventory.as_mut().filter(|_| child) {
+        let stem = inventory.path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+        inventory.path.set_file_name(format!("{}_{}.json", stem, interface));
+    }
+    let mut fingerprinter = Fingerprinter::new(
+        hostname,
+        interface,
+        network_tap.local_ips.clone(),
+        fingerprint_options
+    );
+    fingerprinter.set_mirrored(network_tap.is_sflow());
+
+    // Create rotating writers
+    let mut pcap_writer = if config.pcap_enabled {
+        let pcap_name = if child { format!("packets_{}", interface) } else { "packets".to_string() };
+        Some(open_pcap_writer(config, &pcap_name, uploader)?)
+    } else {
+        None
+    };
+    let mut dns_writer = match config.dns_dir.as_deref() {
+        Some(dir) => {
+            let dns_name = if child { format!("dns_{}", interface) } else { "dns".to_string() };
+            Some(open_dns_writer(config, dir, &dns_name, uploader)?)
+        }
+        None => None,
+    };
+    let mut fingerprint_writer = if !config.fingerprints_enabled {
+        None
+    } else if child {
+        Some(FingerprintOutput::Stdout(std::io::stdout()))
+    } else if config.fingerprint_stdout {
+        Some(open_stdout(config)?)
+    } else if !config.fingerprint_files {
+        None
+    } else {
+        Some(FingerprintOutput::File(open_fingerprint_writer(config, uploader)?))
+    };
+
+    let mut ipfix_exporter = IpfixExporter::from_settings(&config.settings)?;
+    // Children always hand JSON to their supervisor, which does the formatting
+    let output_format = if child { Format::Json } else { config.output_format.clone() };
+
+    info!("Listening on interface: {}", interface);
 
-    let flush_interval = Duration::from_secs(60); // Flush every 60 seconds
     let mut last_flush = std::time::Instant::now();
+    let mut last_stats = std::time::Instant::now();
+    let mut last_summary = std::time::Instant::now();
+    let mut stats = Stats::new();
 
     // Capture and log packets
-    while running.load(Ordering::SeqCst) {
+    while signals.running() {
+        if signals.take_rotate_request() {
+            info!("Rotating output files on request");
+            if let Some(fingerprint_writer) = fingerprint_writer.as_mut() {
+                fingerprint_writer.rotate()?;
+            }
+            if let Some(pcap_writer) = pcap_writer.as_mut() {
+                pcap_writer.rotate()?;
+            }
+            if let Some(dns_writer) = dns_writer.as_mut() {
+                dns_writer.rotate()?;
+            }
+        }
+
+        // Check if we need to flush the writers
+        if last_flush.elapsed() >= FLUSH_INTERVAL {
+            if let Some(fingerprint_writer) = fingerprint_writer.as_mut() {
+                fingerprint_writer.flush()?;
+            }
+            if let Some(pcap_writer) = pcap_writer.as_mut() {
+                pcap_writer.flush()?;
+            }
+            if let Some(dns_writer) = dns_writer.as_mut() {
+                dns_writer.flush()?;
+            }
+            last_flush = std::time::Instant::now();
+        }
+        if last_stats.elapsed() >= STATS_INTERVAL {
+            if let Some(kernel_drops) = network_tap.kernel_drops() {
+                stats.kernel_drops = kernel_drops;
+            }
+            sinks.update_stats(&stats);
+            sinks.heartbeat();
+            if child {
+                writeln!(std::io::stdout(), "{}", child_stats_line(interface, &stats))?;
+            }
+            last_stats = std::time::Instant::now();
+        }
+        if config.stats_interval.is_some_and(|interval| last_summary.elapsed() >= interval) {
+            info!(
+                "Stats: {}; outputs: {}; files: {}",
+                stats.interval_summary(),
+                sinks.queue_status(),
+                file_sizes(fingerprint_writer.as_ref(), pcap_writer.as_ref())
+            );
+            last_summary = std::time::Instant::now();
+        }
+        if signals.take_dump_request() {
+            info!(
+                "Statistics: {}; outputs: {}; files: {}",
+                stats.summary(),
+                sinks.queue_status(),
+                file_sizes(fingerprint_writer.as_ref(), pcap_writer.as_ref())
+            );
+        }
+
+        // Alerts and periodic reports share the fingerprint output
+        for event in fingerprinter.take_events() {
+            if let Some(fingerprint_writer) = fingerprint_writer.as_mut() {
+                fingerprint_writer.write_all(&output_format.encode(&event))?;
+            }
+            sinks.send(&event);
+            stats.events_written += 1;
+        }
+        if let Some(dns_writer) = dns_writer.as_mut() {
+            for query in fingerprinter.take_dns_queries() {
+                writeln!(dns_writer, "{}", query)?;
+            }
+        }
+
         match network_tap.next_packet() {
             Ok(ethernet) => {
-                let packet_header = pcap_packet_header(ethernet.packet().len() as u32);
-                let mut full_packet = Vec::with_capacity(packet_header.len() + ethernet.packet().len());
-                full_packet.extend_from_slice(&packet_header);
-                full_packet.extend_from_slice(ethernet.packet());
-                pcap_writer.write_packet(&full_packet)?;
-
-                if let Some(ip_packet) = Ipv4Packet::new(ethernet.payload()) {
-                    let source_ip = IpAddr::V4(ip_packet.get_source());
-                    let destination_ip = IpAddr::V4(ip_packet.get_destination());
-
-                    // Process packets in both directions
-                    let (fingerprint_ip, is_incoming) = if local_ips.contains(&destination_ip) {
-                        (source_ip, true) // Incoming connection
-                    } else if local_ips.contains(&source_ip) {
-                        (destination_ip, false) // Outgoing connection response
-                    } else {
-                        continue; // Neither source nor destination is local, skip
-                    };
-
-                    // Skip broadcast, multicast, or unspecified IPs
-                    if let IpAddr::V4(ip) = fingerprint_ip {
-                        if ip.is_broadcast() || ip.is_multicast() || ip.is_unspecified() {
-                            continue;
-                        }
+                stats.packets_captured += 1;
+                stats.bytes_captured += ethernet.packet().len() as u64;
+
+                if config.pcap_mode == PcapMode::Full {
+                    if let Some(pcap_writer) = pcap_writer.as_mut() {
+                        pcap_writer.write_packet(&pcap_record(ethernet.packet()))?;
                     }
+                }
 
-                    if ip_packet.get_next_level_protocol().0 == 6 { // TCP protocol
-                        let tcp_payload = ip_packet.payload();
-                        if tcp_payload.len() >= 20 { // Minimum TCP header size
-                            let flags = tcp_payload[13];
-                            
-                            if is_syn_packet(flags, is_incoming) {
-                                let window_size = u16::from_be_bytes([tcp_payload[14], tcp_payload[15]]);
-                                let (options_str, mss, window_scale) = extract_tcp_options(tcp_payload);
-
-                                let fingerprint = Fingerprint::new(
-                                    hostname.clone(),
-                                    fingerprint_ip,
-                                    window_size,
-                                    options_str,
-                                    mss,
-                                    window_scale
-                                );
-
-                                // Write JSON line to file
-                                writeln!(fingerprint_writer, "{}", fingerprint.to_json())?;
-                            }
+                // Pcap-only mode skips TCP parsing entirely
+                if !config.fingerprints_enabled {
+                    continue;
+                }
+
+                if let Some(fingerprint) = fingerprinter.fingerprint(ethernet.packet()) {
+                    // Write JSON line to file
+                    if let Some(fingerprint_writer) = fingerprint_writer.as_mut() {
+                        if output_format.is_json() {
+                            writeln!(fingerprint_writer, "{}", fingerprint.to_json())?;
+                        } else {
+                            fingerprint_writer.write_all(&output_format.encode(&fingerprint.to_value()))?;
                         }
                     }
-                }
+                    if !sinks.is_empty() {
+                        sinks.send(&fingerprint.to_value());
+                    }
+                    stats.fingerprints_written += 1;
 
-                // Check if we need to flush the writers
-                if last_flush.elapsed() >= flush_interval {
-                    fingerprint_writer.flush()?;
-                    pcap_writer.flush()?;
-                    last_flush = std::time::Instant::now();
+                    if let Some(ipfix_exporter) = ipfix_exporter.as_mut() {
+                        ipfix_exporter.export(&fingerprint);
+                    }
+
+                    if config.pcap_mode == PcapMode::SynOnly {
+                        if let Some(pcap_writer) = pcap_writer.as_mut() {
+                            pcap_writer.write_packet(&pcap_record(ethernet.packet()))?;
+                        }
+                    }
                 }
             }
+            Err(e) if e.kind() == std::io::ErrorKind::TimedOut => {}
             Err(e) => {
+                stats.capture_errors += 1;
                 warn!("Error capturing packet: {}", e);
             }
         }
@@ -177,8 +765,44 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
 
     // Graceful shutdown
     info!("Shutting down...");
-    fingerprint_writer.flush_and_close()?;
-    pcap_writer.flush_and_close()?;
+
+    spawn_shutdown_watchdog(config.shutdown_timeout + uploader.map_or(Duration::ZERO, Uploader::shutdown_timeout));
+
+    // Events raised on the last iteration, and reports cut short by shutdown
+    for event in fingerprinter.take_final_events() {
+        if let Some(fingerprint_writer) = fingerprint_writer.as_mut() {
+            fingerprint_writer.write_all(&output_format.encode(&event))?;
+        }
+        sinks.send(&event);
+        stats.events_written += 1;
+    }
+    if let Some(dns_writer) = dns_writer.as_mut() {
+        for query in fingerprinter.take_dns_queries() {
+            writeln!(dns_writer, "{}", query)?;
+        }
+    }
+
+    if let Some(fingerprint_writer) = fingerprint_writer.as_mut() {
+        fingerprint_writer.close()?;
+    }
+    if let Some(pcap_writer) = pcap_writer.as_mut() {
+        pcap_writer.flush_and_close()?;
+    }
+    if let Some(dns_writer) = dns_writer.as_mut() {
+        dns_writer.flush_and_close()?;
+    }
+    fingerprinter.finish();
+
+    if let Some(kernel_drops) = network_tap.kernel_drops() {
+        stats.kernel_drops = kernel_drops;
+    }
+    if child {
+        writeln!(std::io::stdout(), "{}", child_stats_line(interface, &stats))?;
+    }
+    if stats.kernel_drops > 0 {
+        warn!("The kernel dropped {} packets that were never captured; the sensor may be undersized", stats.kernel_drops);
+    }
+    info!("Shutdown summary: {}", stats.summary());
 
     Ok(())
 }
\ No newline at end of file
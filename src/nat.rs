@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+use serde::Serialize;
+
+// Hosts remembered before the fingerprint history is reset
+const NAT_MAX_HOSTS: usize = 65536;
+
+/// Settings from the `[nat]` section.
+#[derive(Clone)]
+pub struct NatSettings {
+    /// How long a fingerprint counts as current for its IP
+    pub window: Duration,
+    /// How often `nat_suspect` records are emitted
+    pub interval: Duration,
+}
+
+struct Sighting {
+    last_seen: Instant,
+    os_guess: Option<String>,
+}
+
+/// Body of a `nat_suspect` event.
+#[derive(Serialize)]
+pub struct NatSuspect {
+    pub ip_address: IpAddr,
+    pub fingerprints: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub os_guesses: Vec<String>,
+    pub window: u64,
+}
+
+/// Flags IPs that present several client fingerprints at once, which
+/// usually means several hosts behind a NAT or proxy.
+pub struct NatDetector {
+    settings: NatSettings,
+    hosts: HashMap<IpAddr, HashMap<String, Sighting>>,
+    last_report: Instant,
+}
+
+impl NatDetector {
+    pub fn new(settings: NatSettings) -> Self {
+        NatDetector { settings, hosts: HashMap::new(), last_report: Instant::now() }
+    }
+
+    pub fn observe(&mut self, ip: IpAddr, fingerprint: &str, os_guess: Option<&str>) {
+        if self.hosts.len() >= NAT_MAX_HOSTS && !self.hosts.contains_key(&ip) {
+            self.hosts.clear();
+        }
+        let sighting = Sighting { last_seen: Instant::now(), os_guess: os_guess.map(str::to_string) };
+        self.hosts.entry(ip).or_default().insert(fingerprint.to_string(), sighting);
+    }
+
+    /// Once per interval, or right away with `force`, forgets stale
+    /// fingerprints and returns every IP still showing more than one.
+    pub fn report(&mut self, force: bool) -> Vec<NatSuspect> {
+        if !force && self.last_report.elapsed() < self.settings.interval {
+            return Vec::new();
+        }
+        self.last_report = Instant::now();
+
+        let window = self.settings.window;
+        self.hosts.retain(|_, sightings| {
+            sightings.retain(|_, sighting| sighting.last_seen.elapsed() <= window);
+            !sightings.is_empty()
+        });
+
+        let mut suspects = Vec::new();
+        for (ip, sightings) in &self.hosts {
+            if sightings.len() < 2 {
+                continue;
+            }
+            let mut fingerprints: Vec<String> = sightings.keys().cloned().collect();
+            fingerprints.sort();
+            let mut os_guesses: Vec<String> = sightings.values().filter_map(|sighting| sighting.os_guess.clone()).collect();
+            os_guesses.sort();
+            os_guesses.dedup();
+            suspects.push(NatSuspect { ip_address: *ip, fingerprints, os_guesses, window: window.as_secs() });
+        }
+        suspects
+    }
+}
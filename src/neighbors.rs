@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use serde::Serialize;
+use crate::oui::OuiDb;
+
+const ETHERTYPE_ARP: u16 = 0x0806;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+const ETHERTYPE_VLAN: [u16; 3] = [0x8100, 0x88a8, 0x9100];
+const IPV6_NEXT_HEADER_ICMPV6: u8 = 58;
+const ICMPV6_NEIGHBOR_SOLICITATION: u8 = 135;
+const ICMPV6_NEIGHBOR_ADVERTISEMENT: u8 = 136;
+const NDP_OPTION_TARGET_LINK_LAYER_ADDRESS: u8 = 2;
+// Pairings remembered before the table is reset
+const NEIGHBOR_MAX_HOSTS: usize = 65536;
+
+/// Body of a `new_host` event.
+#[derive(Serialize)]
+pub struct NewHost {
+    pub ip_address: IpAddr,
+    pub mac: String,
+    /// The MAC the address was paired with before, if it moved
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_mac: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mac_vendor: Option<String>,
+    /// `arp` or `ndp`
+    pub protocol: &'static str,
+}
+
+/// Watches ARP and IPv6 neighbor discovery for MAC/IP pairings that haven't
+/// been seen before.
+#[derive(Default)]
+pub struct NeighborTracker {
+    macs: HashMap<IpAddr, [u8; 6]>,
+}
+
+impl NeighborTracker {
+    pub fn new() -> Self {
+        NeighborTracker { macs: HashMap::new() }
+    }
+
+    /// Inspects a raw Ethernet frame, naming the MAC's vendor when `oui` is given.
+    pub fn observe(&mut self, frame: &[u8], oui: Option<&OuiDb>) -> Option<NewHost> {
+        let (ip, mac, protocol) = neighbor(frame)?;
+        if ip.is_unspecified() || mac == [0; 6] {
+            return None;
+        }
+        if self.macs.len() >= NEIGHBOR_MAX_HOSTS && !self.macs.contains_key(&ip) {
+            self.macs.clear();
+        }
+        let previous = self.macs.insert(ip, mac);
+        if previous == Some(mac) {
+            return None;
+        }
+        Some(NewHost {
+            ip_address: ip,
+            mac: format_mac(&mac),
+            previous_mac: previous.as_ref().map(format_mac),
+            mac_vendor: oui.and_then(|oui| oui.lookup(&mac)).map(str::to_string),
+            protocol,
+        })
+    }
+}
+
+pub fn format_mac(mac: &[u8; 6]) -> String {
+    mac.iter().map(|byte| format!("{:02x}", byte)).collect::<Vec<_>>().join(":")
+}
+
+fn mac_at(data: &[u8], offset: usize) -> Option<[u8; 6]> {
+    data.get(offset..offset + 6)?.try_into().ok()
+}
+
+// Returns the address and MAC a frame announces for its sender
+fn neighbor(frame: &[u8]) -> Option<(IpAddr, [u8; 6], &'static str)> {
+    let source_mac = mac_at(frame, 6)?;
+    let mut offset = 12;
+    let mut ethertype = u16::from_be_bytes([*frame.get(offset)?, *frame.get(offset + 1)?]);
+    while ETHERTYPE_VLAN.contains(&ethertype) {
+        offset += 4;
+        ethertype = u16::from_be_bytes([*frame.get(offset)?, *frame.get(offset + 1)?]);
+    }
+    let payload = frame.get(offset + 2..)?;
+
+    match ethertype {
+        ETHERTYPE_ARP => {
+            // Ethernet/IPv4 only: sender hardware address at 8, protocol address at 14
+            if payload.get(..6)? != [0x00, 0x01, 0x08, 0x00, 6, 4] {
+                return None;
+            }
+            let ip: [u8; 4] = payload.get(14..18)?.try_into().ok()?;
+            Some((IpAddr::V4(Ipv4Addr::from(ip)), mac_at(payload, 8)?, "arp"))
+        }
+        ETHERTYPE_IPV6 => {
+            if *payload.get(6)? != IPV6_NEXT_HEADER_ICMPV6 {
+                return None;
+            }
+            let icmp = payload.get(40..)?;
+            match *icmp.first()? {
+                ICMPV6_NEIGHBOR_SOLICITATION => {
+                    let source: [u8; 16] = payload.get(8..24)?.try_into().ok()?;
+                    Some((IpAddr::V6(Ipv6Addr::from(source)), source_mac, "ndp"))
+                }
+                ICMPV6_NEIGHBOR_ADVERTISEMENT => {
+                    let target: [u8; 16] = icmp.get(8..24)?.try_into().ok()?;
+                    // Prefer the target link-layer address option over the frame's source
+                    let mut mac = source_mac;
+                    let mut options = icmp.get(24..)?;
+                    while options.len() >= 8 {
+                        let length = options[1] as usize * 8;
+                        if length == 0 || options.len() < length {
+                            break;
+                        }
+                        if options[0] == NDP_OPTION_TARGET_LINK_LAYER_ADDRESS {
+                            mac = mac_at(options, 2)?;
+                        }
+                        options = &options[length..];
+                    }
+                    Some((IpAddr::V6(Ipv6Addr::from(target)), mac, "ndp"))
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
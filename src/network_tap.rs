@@ -3,9 +3,30 @@ use pnet::packet::ethernet::EthernetPacket;
 use std::io;
 use std::net::IpAddr;
 use std::collections::HashSet;
+use std::time::Duration;
+use crate::sflow::SflowReceiver;
+
+// libpcap snapshot length, enough for any Ethernet frame
+#[cfg(feature = "pcap")]
+const PCAP_SNAPLEN: i32 = 65535;
+#[cfg(feature = "pcap")]
+const PCAP_TIMEOUT_MS: i32 = 1000;
+
+enum Source {
+    Datalink(Box<dyn datalink::DataLinkReceiver>),
+    #[cfg(feature = "pcap")]
+    Pcap(pcap::Capture<pcap::Active>),
+    Sflow(SflowReceiver),
+}
 
 pub struct NetworkTap {
-    rx: Box<dyn datalink::DataLinkReceiver>,
+    source: Source,
+    // The AF_PACKET socket handed to pnet, kept to read its drop counter
+    socket_fd: Option<i32>,
+    kernel_drops: u64,
+    // Owns the current sFlow frame, which unlike datalink frames isn't
+    // borrowed from the receiver
+    frame: Vec<u8>,
     pub local_ips: HashSet<IpAddr>,
 }
 
@@ -20,20 +41,136 @@ impl NetworkTap {
             .iter()
             .map(|ip_network| ip_network.ip())
             .collect();
-        let (_, rx) = match datalink::channel(&interface, Default::default()) {
+        let socket_fd = packet_socket()?;
+        // Wake up periodically so the capture loop can notice shutdown requests on quiet links
+        let channel_config = datalink::Config {
+            read_timeout: Some(Duration::from_secs(1)),
+            socket_fd,
+            ..Default::default()
+        };
+        let (_, rx) = match datalink::channel(&interface, channel_config) {
             Ok(Ethernet(tx, rx)) => (tx, rx),
             Ok(_) => return Err(io::Error::new(io::ErrorKind::Other, "Unhandled channel type")),
             Err(e) => return Err(io::Error::new(io::ErrorKind::Other, format!("Error creating datalink channel: {}", e))),
         };
-        Ok(NetworkTap { rx, local_ips })
+        Ok(NetworkTap { source: Source::Datalink(rx), socket_fd, kernel_drops: 0, frame: Vec::new(), local_ips })
+    }
+
+    /// Captures through libpcap instead of pnet's raw sockets.
+    /// `interface_name` is the device name or its description.
+    #[cfg(feature = "pcap")]
+    pub fn pcap(interface_name: &str) -> io::Result<Self> {
+        let devices = pcap::Device::list().map_err(pcap_error)?;
+        let device = devices
+            .into_iter()
+            .find(|device| device.name == interface_name || device.desc.as_deref() == Some(interface_name))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("Network interface {} not found", interface_name)))?;
+        let local_ips: HashSet<IpAddr> = device.addresses.iter().map(|address| address.addr).collect();
+        let capture = pcap::Capture::from_device(device)
+            .and_then(|capture| capture.promisc(true).snaplen(PCAP_SNAPLEN).timeout(PCAP_TIMEOUT_MS).open())
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("Error opening pcap capture: {}", e)))?;
+        if capture.get_datalink() != pcap::Linktype::ETHERNET {
+            return Err(io::Error::new(io::ErrorKind::Other, "Unhandled channel type"));
+        }
+        Ok(NetworkTap { source: Source::Pcap(capture), socket_fd: None, kernel_drops: 0, frame: Vec::new(), local_ips })
+    }
+
+    /// Reads the packet headers sampled by switches from sFlow datagrams sent
+    /// to `listen`. No address is local, so all traffic counts as mirrored.
+    pub fn sflow(listen: &str) -> io::Result<Self> {
+        let receiver = SflowReceiver::bind(listen)
+            .map_err(|e| io::Error::new(e.kind(), format!("Failed to listen for sFlow on {}: {}", listen, e)))?;
+        Ok(NetworkTap { source: Source::Sflow(receiver), socket_fd: None, kernel_drops: 0, frame: Vec::new(), local_ips: HashSet::new() })
     }
 
     pub fn next_packet(&mut self) -> io::Result<EthernetPacket> {
-        match self.rx.next() {
-            Ok(packet) => Ok(EthernetPacket::new(packet).unwrap()),
-            Err(e) => Err(io::Error::new(io::ErrorKind::Other, format!("Failed to read packet: {}", e))),
+        match &mut self.source {
+            Source::Datalink(rx) => match rx.next() {
+                Ok(packet) => Ok(EthernetPacket::new(packet).unwrap()),
+                Err(e) => Err(io::Error::new(e.kind(), format!("Failed to read packet: {}", e))),
+            },
+            #[cfg(feature = "pcap")]
+            Source::Pcap(capture) => match capture.next_packet() {
+                // The read timeout surfaces like pnet's, so quiet links still return to the loop
+                Ok(packet) => EthernetPacket::new(packet.data)
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Truncated Ethernet frame")),
+                Err(pcap::Error::TimeoutExpired) => Err(io::Error::new(io::ErrorKind::TimedOut, "Timed out waiting for a packet")),
+                Err(e) => Err(io::Error::new(io::ErrorKind::Other, format!("Failed to read packet: {}", e))),
+            },
+            Source::Sflow(receiver) => {
+                self.frame = receiver.next_frame()?;
+                Ok(EthernetPacket::new(&self.frame).unwrap())
+            }
+        }
+    }
+
+    pub fn is_sflow(&self) -> bool {
+        matches!(self.source, Source::Sflow(_))
+    }
+
+    /// Packets the kernel dropped because capture didn't keep up, since
+    /// startup. `None` where the counter isn't available.
+    #[cfg(target_os = "linux")]
+    pub fn kernel_drops(&mut self) -> Option<u64> {
+        #[cfg(feature = "pcap")]
+        if let Source::Pcap(capture) = &mut self.source {
+            return pcap_drops(capture);
         }
+        let socket_fd = self.socket_fd?;
+        let mut stats = libc::tpacket_stats { tp_packets: 0, tp_drops: 0 };
+        let mut length = std::mem::size_of::<libc::tpacket_stats>() as libc::socklen_t;
+        let result = unsafe {
+            libc::getsockopt(
+                socket_fd,
+                libc::SOL_PACKET,
+                libc::PACKET_STATISTICS,
+                &mut stats as *mut libc::tpacket_stats as *mut libc::c_void,
+                &mut length,
+            )
+        };
+        if result != 0 {
+            return None;
+        }
+        // Reading the statistics resets them
+        self.kernel_drops += stats.tp_drops as u64;
+        Some(self.kernel_drops)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn kernel_drops(&mut self) -> Option<u64> {
+        match &mut self.source {
+            #[cfg(feature = "pcap")]
+            Source::Pcap(capture) => pcap_drops(capture),
+            _ => None,
+        }
+    }
+}
+
+// libpcap's counters are cumulative, unlike PACKET_STATISTICS
+#[cfg(feature = "pcap")]
+fn pcap_drops(capture: &mut pcap::Capture<pcap::Active>) -> Option<u64> {
+    let stats = capture.stats().ok()?;
+    Some(stats.dropped as u64 + stats.if_dropped as u64)
+}
+
+#[cfg(feature = "pcap")]
+fn pcap_error(e: pcap::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, e.to_string())
+}
+
+// Opens the capture socket for pnet, so its statistics can be read later.
+#[cfg(target_os = "linux")]
+fn packet_socket() -> io::Result<Option<i32>> {
+    let socket_fd = unsafe { libc::socket(libc::AF_PACKET, libc::SOCK_RAW, (libc::ETH_P_ALL as u16).to_be() as i32) };
+    if socket_fd < 0 {
+        return Err(io::Error::last_os_error());
     }
+    Ok(Some(socket_fd))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn packet_socket() -> io::Result<Option<i32>> {
+    Ok(None)
 }
 
 pub fn pcap_global_header() -> [u8; 24] {
@@ -47,6 +184,15 @@ pub fn pcap_global_header() -> [u8; 24] {
     ]
 }
 
+/// Returns a complete pcap record (header followed by the frame) for `packet`.
+pub fn pcap_record(packet: &[u8]) -> Vec<u8> {
+    let packet_header = pcap_packet_header(packet.len() as u32);
+    let mut record = Vec::with_capacity(packet_header.len() + packet.len());
+    record.extend_from_slice(&packet_header);
+    record.extend_from_slice(packet);
+    record
+}
+
 pub fn pcap_packet_header(packet_length: u32) -> [u8; 16] {
     let timestamp = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
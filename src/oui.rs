@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// MAC address prefixes (OUIs) and the vendors they're assigned to.
+#[derive(Default)]
+pub struct OuiDb {
+    vendors: HashMap<[u8; 3], String>,
+}
+
+impl OuiDb {
+    /// The small list embedded in the binary, see `src/oui.txt`.
+    pub fn builtin() -> OuiDb {
+        OuiDb::parse(include_str!("oui.txt"))
+    }
+
+    pub fn load(path: &Path) -> Result<OuiDb, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let db = OuiDb::parse(&text);
+        if db.vendors.is_empty() {
+            return Err(format!("{}: no OUI entries found", path.display()));
+        }
+        Ok(db)
+    }
+
+    /// Parses the IEEE `oui.txt` layout, `00-00-0C   (hex)		Cisco Systems, Inc`.
+    /// Every other line (the `(base 16)` duplicates, addresses) is skipped.
+    pub fn parse(text: &str) -> OuiDb {
+        let mut db = OuiDb::default();
+        for line in text.lines() {
+            let Some((prefix, vendor)) = line.split_once("(hex)") else { continue };
+            let bytes: Vec<u8> = prefix
+                .trim()
+                .split(['-', ':'])
+                .filter_map(|byte| u8::from_str_radix(byte, 16).ok())
+                .collect();
+            if let [a, b, c] = bytes[..] {
+                db.vendors.insert([a, b, c], vendor.trim().to_string());
+            }
+        }
+        db
+    }
+
+    pub fn len(&self) -> usize {
+        self.vendors.len()
+    }
+
+    pub fn lookup(&self, mac: &[u8]) -> Option<&str> {
+        let prefix: [u8; 3] = mac.get(..3)?.try_into().ok()?;
+        self.vendors.get(&prefix).map(String::as_str)
+    }
+}
@@ -0,0 +1,49 @@
+use std::io::{self, Write};
+use crate::rotating_writer::RotatingFileWriter;
+
+/// Destination for fingerprint JSON lines.
+pub enum FingerprintOutput {
+    File(RotatingFileWriter),
+    Stdout(io::Stdout),
+}
+
+impl FingerprintOutput {
+    /// Finalizes the current file early; a no-op for stdout.
+    pub fn rotate(&mut self) -> io::Result<()> {
+        match self {
+            FingerprintOutput::File(writer) => writer.rotate(),
+            FingerprintOutput::Stdout(_) => Ok(()),
+        }
+    }
+
+    /// Size of the current file; `None` for stdout.
+    pub fn current_size(&self) -> Option<u64> {
+        match self {
+            FingerprintOutput::File(writer) => Some(writer.current_size()),
+            FingerprintOutput::Stdout(_) => None,
+        }
+    }
+
+    pub fn close(&mut self) -> io::Result<()> {
+        match self {
+            FingerprintOutput::File(writer) => writer.flush_and_close(),
+            FingerprintOutput::Stdout(stdout) => stdout.flush(),
+        }
+    }
+}
+
+impl Write for FingerprintOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            FingerprintOutput::File(writer) => writer.write(buf),
+            FingerprintOutput::Stdout(stdout) => stdout.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            FingerprintOutput::File(writer) => writer.flush(),
+            FingerprintOutput::Stdout(stdout) => stdout.flush(),
+        }
+    }
+}
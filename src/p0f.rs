@@ -0,0 +1,163 @@
+use std::fmt;
+use crate::fingerprint::initial_ttl;
+
+/// The fields of a SYN or SYN-ACK that p0f v3 signatures are built from.
+pub struct Observation {
+    pub ttl: u8,
+    pub olen: usize,
+    pub mss: Option<u16>,
+    pub window: u16,
+    pub scale: u8,
+    pub layout: Vec<String>,
+    pub quirks: Vec<&'static str>,
+    pub has_payload: bool,
+}
+
+/// Extracts the p0f view of an IPv4 packet and its TCP header (as returned
+/// by `fingerprint::tcp_header`).
+pub fn observe(ip: &[u8], tcp_header: &[u8]) -> Observation {
+    let ip_header_length = ((ip[0] & 0x0f) as usize) * 4;
+    let total_length = u16::from_be_bytes([ip[2], ip[3]]) as usize;
+    let ip_id = u16::from_be_bytes([ip[4], ip[5]]);
+    let ip_flags = ip[6];
+    let flags = tcp_header[13];
+    let is_ack = flags & 0x10 != 0;
+
+    let mut quirks = Vec::new();
+    if ip_flags & 0x40 != 0 {
+        quirks.push("df");
+        if ip_id != 0 {
+            quirks.push("id+");
+        }
+    } else if ip_id == 0 {
+        quirks.push("id-");
+    }
+    // ECT/CE in the TOS byte, or ECE/CWR/NS on the segment itself
+    if ip[1] & 0x03 != 0 || flags & 0xc0 != 0 || tcp_header[12] & 0x01 != 0 {
+        quirks.push("ecn");
+    }
+    if ip_flags & 0x80 != 0 {
+        quirks.push("0+");
+    }
+    if tcp_header[4..8] == [0, 0, 0, 0] {
+        quirks.push("seq-");
+    }
+    let ack_zero = tcp_header[8..12] == [0, 0, 0, 0];
+    if is_ack && ack_zero {
+        quirks.push("ack-");
+    } else if !is_ack && !ack_zero {
+        quirks.push("ack+");
+    }
+    if flags & 0x20 != 0 {
+        quirks.push("urgf+");
+    } else if tcp_header[18..20] != [0, 0] {
+        quirks.push("uptr+");
+    }
+    if flags & 0x08 != 0 {
+        quirks.push("pushf+");
+    }
+
+    let options = &tcp_header[20..];
+    let mut layout = Vec::new();
+    let mut mss = None;
+    let mut scale = 0;
+    let mut i = 0;
+    while i < options.len() {
+        let kind = options[i];
+        match kind {
+            0 => {
+                let padding = &options[i + 1..];
+                layout.push(format!("eol+{}", padding.len()));
+                if padding.iter().any(|&byte| byte != 0) {
+                    quirks.push("opt+");
+                }
+                break;
+            }
+            1 => {
+                layout.push("nop".to_string());
+                i += 1;
+                continue;
+            }
+            _ => {}
+        }
+
+        let Some(&length) = options.get(i + 1) else {
+            quirks.push("bad");
+            break;
+        };
+        let length = length as usize;
+        if length < 2 || i + length > options.len() {
+            quirks.push("bad");
+            break;
+        }
+        let data = &options[i + 2..i + length];
+        match (kind, length) {
+            (2, 4) => {
+                layout.push("mss".to_string());
+                mss = Some(u16::from_be_bytes([data[0], data[1]]));
+            }
+            (3, 3) => {
+                layout.push("ws".to_string());
+                scale = data[0];
+                if scale > 14 {
+                    quirks.push("exws");
+                }
+            }
+            (4, 2) => layout.push("sok".to_string()),
+            (5, _) => layout.push("sack".to_string()),
+            (8, 10) => {
+                layout.push("ts".to_string());
+                if data[0..4] == [0, 0, 0, 0] {
+                    quirks.push("ts1-");
+                }
+                if !is_ack && data[4..8] != [0, 0, 0, 0] {
+                    quirks.push("ts2+");
+                }
+            }
+            (2, _) | (3, _) | (4, _) | (8, _) => {
+                quirks.push("bad");
+                break;
+            }
+            _ => layout.push(format!("?{}", kind)),
+        }
+        i += length;
+    }
+
+    // Payload on a SYN is unusual enough (e.g. TFO data) to form its own class
+    let end = if total_length == 0 { ip.len() } else { total_length.min(ip.len()) };
+    let payload_length = end.saturating_sub(ip_header_length + tcp_header.len());
+
+    Observation {
+        ttl: ip[8],
+        olen: ip_header_length - 20,
+        mss,
+        window: u16::from_be_bytes([tcp_header[14], tcp_header[15]]),
+        scale,
+        layout,
+        quirks,
+        has_payload: payload_length > 0,
+    }
+}
+
+/// Renders the observation as a p0f v3 TCP signature in the
+/// `ver:ittl:olen:mss:wsize,scale:olayout:quirks:pclass` form used by p0f.fp.
+impl fmt::Display for Observation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let window_size = match self.mss {
+            Some(mss) if mss > 0 && self.window > 0 && self.window % mss == 0 => format!("mss*{}", self.window / mss),
+            _ => self.window.to_string(),
+        };
+        write!(
+            f,
+            "4:{}:{}:{}:{},{}:{}:{}:{}",
+            initial_ttl(self.ttl),
+            self.olen,
+            self.mss.map_or("*".to_string(), |mss| mss.to_string()),
+            window_size,
+            self.scale,
+            self.layout.join(","),
+            self.quirks.join(","),
+            if self.has_payload { "+" } else { "0" }
+        )
+    }
+}
@@ -0,0 +1,286 @@
+use aes::Aes128;
+use aes::cipher::BlockEncrypt;
+use aes_gcm::aead::{Aead, Payload};
+use aes_gcm::{Aes128Gcm, KeyInit, Nonce};
+use hkdf::Hkdf;
+use serde::Serialize;
+use sha2::Sha256;
+use crate::tls::{self, ClientHello};
+
+const QUIC_V1: u32 = 0x0000_0001;
+const QUIC_V2: u32 = 0x6b33_43cf;
+// Initial salts from RFC 9001 section 5.2 and RFC 9369 section 3.3.1
+const INITIAL_SALT_V1: [u8; 20] = [
+    0x38, 0x76, 0x2c, 0xf7, 0xf5, 0x59, 0x34, 0xb3, 0x4d, 0x17,
+    0x9a, 0xe6, 0xa4, 0xc8, 0x0c, 0xad, 0xcc, 0xbb, 0x7f, 0x0a,
+];
+const INITIAL_SALT_V2: [u8; 20] = [
+    0x0d, 0xed, 0xe3, 0xde, 0xf7, 0x00, 0xa6, 0xdb, 0x81, 0x93,
+    0x81, 0xbe, 0x6e, 0x26, 0x9d, 0xcb, 0xf9, 0xbd, 0x2e, 0xd9,
+];
+const FRAME_PADDING: u64 = 0x00;
+const FRAME_PING: u64 = 0x01;
+const FRAME_ACK: u64 = 0x02;
+const FRAME_ACK_ECN: u64 = 0x03;
+const FRAME_CRYPTO: u64 = 0x06;
+
+/// Body of a `quic` event, describing a client's Initial packet.
+#[derive(Serialize)]
+pub struct QuicInitial {
+    /// QUIC version as hex, e.g. `0x00000001`
+    pub version: String,
+    pub dcid_length: u8,
+    pub scid_length: u8,
+    pub token_length: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sni: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub alpn: Vec<String>,
+    /// Left out when the ClientHello continues in a later packet, as the
+    /// extensions seen so far would give a different JA4
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ja4: Option<String>,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, length: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.position..self.position.checked_add(length)?)?;
+        self.position += length;
+        Some(bytes)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.bytes(1)?[0])
+    }
+
+    // Variable-length integer from RFC 9000 section 16
+    fn varint(&mut self) -> Option<u64> {
+        let first = self.u8()?;
+        let length = 1 << (first >> 6);
+        let mut value = (first & 0x3f) as u64;
+        for &byte in self.bytes(length - 1)? {
+            value = (value << 8) | byte as u64;
+        }
+        Some(value)
+    }
+}
+
+/// Decrypts a client Initial packet from the start of a UDP payload and
+/// describes it, or returns `None` for anything else (short headers, server
+/// Initials, unknown versions, Initials without the start of a ClientHello).
+pub fn parse_initial(datagram: &[u8]) -> Option<QuicInitial> {
+    let mut reader = Reader { data: datagram, position: 0 };
+    let first = reader.u8()?;
+    if first & 0xc0 != 0xc0 {
+        return None; // Not a long header
+    }
+    let version = u32::from_be_bytes(reader.bytes(4)?.try_into().ok()?);
+    let (salt, label_prefix, initial_type) = match version {
+        QUIC_V1 => (&INITIAL_SALT_V1, "quic", 0),
+        QUIC_V2 => (&INITIAL_SALT_V2, "quicv2", 1),
+        _ => return None,
+    };
+    if (first >> 4) & 0x03 != initial_type {
+        return None;
+    }
+    let dcid_length = reader.u8()?;
+    let dcid = reader.bytes(dcid_length as usize)?;
+    let scid_length = reader.u8()?;
+    reader.bytes(scid_length as usize)?;
+    let token_length = reader.varint()?;
+    reader.bytes(usize::try_from(token_length).ok()?)?;
+    let length = usize::try_from(reader.varint()?).ok()?;
+    let packet_number_offset = reader.position;
+    let packet = datagram.get(..packet_number_offset.checked_add(length)?)?;
+
+    let keys = InitialKeys::client(salt, label_prefix, dcid)?;
+    let payload = keys.decrypt(packet, packet_number_offset)?;
+    let client_hello = client_hello(&payload)?;
+
+    Some(QuicInitial {
+        version: format!("0x{:08x}", version),
+        dcid_length,
+        scid_length,
+        token_length,
+        ja4: client_hello.complete.then(|| tls::ja4(&client_hello, 'q')),
+        sni: client_hello.server_name,
+        alpn: client_hello.alpn,
+    })
+}
+
+struct InitialKeys {
+    key: [u8; 16],
+    iv: [u8; 12],
+    hp: [u8; 16],
+}
+
+impl InitialKeys {
+    // RFC 9001 section 5.2
+    fn client(salt: &[u8], label_prefix: &str, dcid: &[u8]) -> Option<InitialKeys> {
+        let (initial_secret, _) = Hkdf::<Sha256>::extract(Some(salt), dcid);
+        let initial = Hkdf::<Sha256>::from_prk(&initial_secret).ok()?;
+        let mut client_secret = [0; 32];
+        expand_label(&initial, "client in", &mut client_secret)?;
+
+        let client = Hkdf::<Sha256>::from_prk(&client_secret).ok()?;
+        let mut keys = InitialKeys { key: [0; 16], iv: [0; 12], hp: [0; 16] };
+        expand_label(&client, &format!("{} key", label_prefix), &mut keys.key)?;
+        expand_label(&client, &format!("{} iv", label_prefix), &mut keys.iv)?;
+        expand_label(&client, &format!("{} hp", label_prefix), &mut keys.hp)?;
+        Some(keys)
+    }
+
+    /// Removes header protection and decrypts the payload of `packet`.
+    fn decrypt(&self, packet: &[u8], packet_number_offset: usize) -> Option<Vec<u8>> {
+        let (header, packet_number) = self.unprotect_header(packet, packet_number_offset)?;
+        let mut nonce = self.iv;
+        for (i, byte) in packet_number.to_be_bytes().iter().enumerate() {
+            nonce[4 + i] ^= byte;
+        }
+        let ciphertext = &packet[header.len()..];
+        Aes128Gcm::new(&self.key.into())
+            .decrypt(Nonce::from_slice(&nonce), Payload { msg: ciphertext, aad: &header })
+            .ok()
+    }
+
+    // Returns the header with the first byte and packet number unmasked,
+    // which is the AEAD's associated data, and the packet number.
+    fn unprotect_header(&self, packet: &[u8], packet_number_offset: usize) -> Option<(Vec<u8>, u64)> {
+        let sample = packet.get(packet_number_offset + 4..packet_number_offset + 20)?;
+        let mut mask = aes::Block::clone_from_slice(sample);
+        Aes128::new(&self.hp.into()).encrypt_block(&mut mask);
+
+        let mut header = packet[..packet_number_offset].to_vec();
+        header[0] ^= mask[0] & 0x0f;
+        let packet_number_length = (header[0] & 0x03) as usize + 1;
+        let mut packet_number = 0u64;
+        for i in 0..packet_number_length {
+            let byte = packet[packet_number_offset + i] ^ mask[1 + i];
+            header.push(byte);
+            packet_number = (packet_number << 8) | byte as u64;
+        }
+        Some((header, packet_number))
+    }
+}
+
+// HKDF-Expand-Label from RFC 8446 section 7.1, with an empty context
+fn expand_label(hkdf: &Hkdf<Sha256>, label: &str, output: &mut [u8]) -> Option<()> {
+    let label = format!("tls13 {}", label);
+    let mut info = Vec::with_capacity(4 + label.len());
+    info.extend((output.len() as u16).to_be_bytes());
+    info.push(label.len() as u8);
+    info.extend(label.as_bytes());
+    info.push(0);
+    hkdf.expand(&info, output).ok()
+}
+
+// Reassembles the CRYPTO frames of a decrypted Initial, which clients may
+// send out of order, and parses the ClientHello at offset zero.
+fn client_hello(payload: &[u8]) -> Option<ClientHello> {
+    let mut reader = Reader { data: payload, position: 0 };
+    let mut fragments = Vec::new();
+    while reader.position < payload.len() {
+        match reader.varint()? {
+            FRAME_PADDING | FRAME_PING => {}
+            frame @ (FRAME_ACK | FRAME_ACK_ECN) => {
+                reader.varint()?; // largest acknowledged
+                reader.varint()?; // delay
+                let ranges = reader.varint()?;
+                reader.varint()?; // first range
+                for _ in 0..ranges {
+                    reader.varint()?; // gap
+                    reader.varint()?; // range length
+                }
+                if frame == FRAME_ACK_ECN {
+                    for _ in 0..3 {
+                        reader.varint()?;
+                    }
+                }
+            }
+            FRAME_CRYPTO => {
+                let offset = usize::try_from(reader.varint()?).ok()?;
+                let length = usize::try_from(reader.varint()?).ok()?;
+                fragments.push((offset, reader.bytes(length)?));
+            }
+            // Nothing else is allowed in an Initial packet
+            _ => break,
+        }
+    }
+
+    fragments.sort_by_key(|&(offset, _)| offset);
+    let mut stream = Vec::new();
+    for (offset, data) in fragments {
+        if offset > stream.len() {
+            break; // The rest of the hello is in a later packet
+        }
+        let skip = stream.len() - offset;
+        if skip < data.len() {
+            stream.extend_from_slice(&data[skip..]);
+        }
+    }
+    tls::parse_client_hello(&stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 9001 Appendix A: the client's Destination Connection ID
+    const DCID: [u8; 8] = [0x83, 0x94, 0xc8, 0xf0, 0x3e, 0x51, 0x57, 0x08];
+    // Offset of the packet number in the Appendix A.2 header
+    const PACKET_NUMBER_OFFSET: usize = 18;
+
+    fn hex(value: &str) -> Vec<u8> {
+        hex::decode(value).unwrap()
+    }
+
+    #[test]
+    fn client_initial_keys_match_rfc_9001() {
+        let keys = InitialKeys::client(&INITIAL_SALT_V1, "quic", &DCID).unwrap();
+        assert_eq!(keys.key.to_vec(), hex("1f369613dd76d5467730efcbe3b1a22d"));
+        assert_eq!(keys.iv.to_vec(), hex("fa044b2f42a3fd3b46fb255c"));
+        assert_eq!(keys.hp.to_vec(), hex("9f50449e04a0e810283a1e9933adedd2"));
+    }
+
+    #[test]
+    fn header_protection_matches_rfc_9001() {
+        let keys = InitialKeys::client(&INITIAL_SALT_V1, "quic", &DCID).unwrap();
+        // Protected header followed by the sample taken from the ciphertext
+        let mut packet = hex("c000000001088394c8f03e5157080000449e7b9aec34");
+        packet.extend(hex("d1b1c98dd7689fb8ec11d242b123dc9b"));
+        let (header, packet_number) = keys.unprotect_header(&packet, PACKET_NUMBER_OFFSET).unwrap();
+        assert_eq!(header, hex("c300000001088394c8f03e5157080000449e00000002"));
+        assert_eq!(packet_number, 2);
+    }
+
+    #[test]
+    fn decrypts_a_protected_initial() {
+        let keys = InitialKeys::client(&INITIAL_SALT_V1, "quic", &DCID).unwrap();
+        let header = hex("c300000001088394c8f03e5157080000449e00000002");
+        let payload = vec![FRAME_PING as u8; 64];
+
+        // Protect the packet the way a client would, per RFC 9001 section 5
+        let mut nonce = keys.iv;
+        nonce[11] ^= 2;
+        let ciphertext = Aes128Gcm::new(&keys.key.into())
+            .encrypt(Nonce::from_slice(&nonce), Payload { msg: &payload, aad: &header })
+            .unwrap();
+        let mut mask = aes::Block::clone_from_slice(&ciphertext[..16]);
+        Aes128::new(&keys.hp.into()).encrypt_block(&mut mask);
+        let mut packet = header.clone();
+        packet[0] ^= mask[0] & 0x0f;
+        for i in 0..4 {
+            packet[PACKET_NUMBER_OFFSET + i] ^= mask[1 + i];
+        }
+        packet.extend(ciphertext);
+
+        assert_eq!(keys.decrypt(&packet, PACKET_NUMBER_OFFSET), Some(payload));
+        packet[30] ^= 1;
+        assert_eq!(keys.decrypt(&packet, PACKET_NUMBER_OFFSET), None);
+    }
+}
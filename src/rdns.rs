@@ -0,0 +1,122 @@
+use std::collections::{HashMap, HashSet};
+use std::ffi::CStr;
+use std::net::IpAddr;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Addresses waiting for the resolver thread; more are dropped, not queued
+const RDNS_QUEUE_SIZE: usize = 1024;
+// Names remembered before the cache is reset
+const RDNS_MAX_CACHED: usize = 65536;
+// NI_MAXHOST, which libc doesn't export on every platform
+const RDNS_MAX_NAME: usize = 1025;
+
+#[derive(Clone)]
+pub struct RdnsSettings {
+    /// Lookups per second
+    pub rate: u32,
+    /// How long a name (or its absence) is cached
+    pub ttl: Duration,
+}
+
+#[derive(Default)]
+struct Cache {
+    names: HashMap<IpAddr, (Option<String>, Instant)>,
+    pending: HashSet<IpAddr>,
+}
+
+/// Looks up PTR names on a background thread. `lookup` only ever reads the
+/// cache, so an address is annotated from the first record after its name
+/// has been resolved.
+pub struct ReverseResolver {
+    ttl: Duration,
+    cache: Arc<Mutex<Cache>>,
+    tx: SyncSender<IpAddr>,
+}
+
+impl ReverseResolver {
+    pub fn new(settings: RdnsSettings) -> Self {
+        let cache = Arc::new(Mutex::new(Cache::default()));
+        let (tx, rx) = mpsc::sync_channel(RDNS_QUEUE_SIZE);
+        let interval = Duration::from_secs(1) / settings.rate.max(1);
+        let worker_cache = Arc::clone(&cache);
+        thread::spawn(move || resolve_queued(rx, worker_cache, interval));
+        ReverseResolver { ttl: settings.ttl, cache, tx }
+    }
+
+    /// Returns the cached PTR name of `ip`, queueing a lookup when there is
+    /// none yet or it has expired.
+    pub fn lookup(&self, ip: IpAddr) -> Option<String> {
+        let mut cache = self.cache.lock().ok()?;
+        if let Some((name, resolved_at)) = cache.names.get(&ip) {
+            if resolved_at.elapsed() < self.ttl {
+                return name.clone();
+            }
+        }
+        if !cache.pending.contains(&ip) && self.tx.try_send(ip).is_ok() {
+            cache.pending.insert(ip);
+        }
+        None
+    }
+}
+
+// Runs until the resolver is dropped, pacing lookups to the configured rate.
+fn resolve_queued(rx: Receiver<IpAddr>, cache: Arc<Mutex<Cache>>, interval: Duration) {
+    for ip in rx {
+        let started = Instant::now();
+        let name = resolve(ip);
+        let Ok(mut cache) = cache.lock() else { return };
+        if cache.names.len() >= RDNS_MAX_CACHED {
+            cache.names.clear();
+        }
+        cache.names.insert(ip, (name, Instant::now()));
+        cache.pending.remove(&ip);
+        drop(cache);
+        thread::sleep(interval.saturating_sub(started.elapsed()));
+    }
+}
+
+fn resolve(ip: IpAddr) -> Option<String> {
+    let mut host = [0 as libc::c_char; RDNS_MAX_NAME];
+    let result = match ip {
+        IpAddr::V4(ip) => {
+            let mut address: libc::sockaddr_in = unsafe { std::mem::zeroed() };
+            address.sin_family = libc::AF_INET as libc::sa_family_t;
+            address.sin_addr.s_addr = u32::from(ip).to_be();
+            unsafe {
+                libc::getnameinfo(
+                    &address as *const libc::sockaddr_in as *const libc::sockaddr,
+                    std::mem::size_of::<libc::sockaddr_in>() as libc::socklen_t,
+                    host.as_mut_ptr(),
+                    host.len() as libc::socklen_t,
+                    std::ptr::null_mut(),
+                    0,
+                    libc::NI_NAMEREQD,
+                )
+            }
+        }
+        IpAddr::V6(ip) => {
+            let mut address: libc::sockaddr_in6 = unsafe { std::mem::zeroed() };
+            address.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+            address.sin6_addr.s6_addr = ip.octets();
+            unsafe {
+                libc::getnameinfo(
+                    &address as *const libc::sockaddr_in6 as *const libc::sockaddr,
+                    std::mem::size_of::<libc::sockaddr_in6>() as libc::socklen_t,
+                    host.as_mut_ptr(),
+                    host.len() as libc::socklen_t,
+                    std::ptr::null_mut(),
+                    0,
+                    libc::NI_NAMEREQD,
+                )
+            }
+        }
+    };
+    if result != 0 {
+        return None;
+    }
+    let name = unsafe { CStr::from_ptr(host.as_ptr()) };
+    Some(name.to_string_lossy().into_owned())
+}
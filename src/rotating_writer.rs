@@ -1,7 +1,42 @@
+use std::ffi::CString;
 use std::fs::{File, OpenOptions};
 use std::io::{self, Write, BufWriter};
-use std::path::PathBuf;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+/// Permissions and ownership applied to every file the writer creates.
+#[derive(Clone, Default)]
+pub struct FileOwnership {
+    pub mode: Option<u32>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+}
+
+impl FileOwnership {
+    fn apply(&self, path: &Path) -> io::Result<()> {
+        if let Some(mode) = self.mode {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+        }
+        if self.uid.is_some() || self.gid.is_some() {
+            std::os::unix::fs::chown(path, self.uid, self.gid)?;
+        }
+        Ok(())
+    }
+}
+
+// One line of the manifest kept next to the rotated files.
+#[derive(Serialize)]
+struct ManifestEntry {
+    file: String,
+    size: u64,
+    sha256: String,
+    first_timestamp: Option<DateTime<Utc>>,
+    last_timestamp: Option<DateTime<Utc>>,
+}
 
 pub struct RotatingFileWriter {
     base_path: PathBuf,
@@ -11,7 +46,13 @@ pub struct RotatingFileWriter {
     file_count: u32,
     current_path: Option<PathBuf>,
     file_extension: String,
+    sync: bool,
+    ownership: FileOwnership,
+    manifest: bool,
+    first_write: Option<DateTime<Utc>>,
+    last_write: Option<DateTime<Utc>>,
     init_new_file: Box<dyn Fn(&mut BufWriter<File>) -> io::Result<()>>,
+    on_complete: Option<Box<dyn FnMut(&Path)>>,
 }
 
 impl RotatingFileWriter {
@@ -27,22 +68,54 @@ impl RotatingFileWriter {
             file_count: 0,
             current_path: None,
             file_extension: file_extension.to_string(),
+            sync: false,
+            ownership: FileOwnership::default(),
+            manifest: false,
+            first_write: None,
+            last_write: None,
             init_new_file: Box::new(init_new_file),
+            on_complete: None,
         };
         writer.rotate()?;
         Ok(writer)
     }
 
-    fn rotate(&mut self) -> io::Result<()> {
-        if let Some(mut file) = self.current_file.take() {
-            file.flush()?;
-        }
-        if let Some(current_path) = self.current_path.take() {
-            if current_path.exists() {
-                let new_path = current_path.with_extension(&self.file_extension);
-                std::fs::rename(current_path, new_path)?;
-            }
+    /// Bytes written to the current file so far.
+    pub fn current_size(&self) -> u64 {
+        self.current_size
+    }
+
+    /// When enabled, completed files and periodic flushes are fsynced to disk.
+    pub fn set_sync(&mut self, sync: bool) {
+        self.sync = sync;
+    }
+
+    /// Applies `ownership` to the current file and every file created after it.
+    pub fn set_ownership(&mut self, ownership: FileOwnership) -> io::Result<()> {
+        if let Some(path) = &self.current_path {
+            ownership.apply(path)?;
         }
+        self.ownership = ownership;
+        Ok(())
+    }
+
+    /// When enabled, every completed file is recorded with its size, SHA-256
+    /// and first/last write time in `manifest.jsonl` in the output directory.
+    pub fn set_manifest(&mut self, manifest: bool) {
+        self.manifest = manifest;
+    }
+
+    /// Registers a callback invoked with the final path of every completed file.
+    pub fn set_on_complete<F>(&mut self, on_complete: F)
+    where
+        F: FnMut(&Path) + 'static,
+    {
+        self.on_complete = Some(Box::new(on_complete));
+    }
+
+    /// Finalizes the current file and starts a new one.
+    pub fn rotate(&mut self) -> io::Result<()> {
+        self.finalize_current()?;
         let timestamp = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .expect("Time went backwards")
@@ -53,17 +126,20 @@ impl RotatingFileWriter {
             timestamp,
             self.file_count
         );
-        let new_path = self.base_path.with_file_name(&file_name);
+        let new_path = unique_path(self.base_path.with_file_name(&file_name));
         let file = OpenOptions::new()
             .write(true)
-            .create(true)
+            .create_new(true)
             .open(&new_path)?;
+        self.ownership.apply(&new_path)?;
         let mut buf_writer = BufWriter::new(file);
         (self.init_new_file)(&mut buf_writer)?;
         buf_writer.flush()?;
         self.current_file = Some(buf_writer);
         self.current_path = Some(new_path);
         self.current_size = 0;
+        self.first_write = None;
+        self.last_write = None;
         self.file_count += 1;
         Ok(())
     }
@@ -76,6 +152,11 @@ impl RotatingFileWriter {
         if let Some(file) = self.current_file.as_mut() {
             file.write_all(packet)?;
             self.current_size += packet_size;
+            if self.manifest {
+                let now = Utc::now();
+                self.first_write.get_or_insert(now);
+                self.last_write = Some(now);
+            }
             Ok(())
         } else {
             Err(io::Error::new(io::ErrorKind::Other, "No file currently open"))
@@ -83,17 +164,127 @@ impl RotatingFileWriter {
     }
 
     pub fn flush_and_close(&mut self) -> io::Result<()> {
+        self.finalize_current()
+    }
+
+    // Flushes the open .part file and moves it to its final extension.
+    fn finalize_current(&mut self) -> io::Result<()> {
         if let Some(mut file) = self.current_file.take() {
             file.flush()?;
+            if self.sync {
+                file.get_ref().sync_all()?;
+            }
         }
         if let Some(current_path) = self.current_path.take() {
             if current_path.exists() {
-                let new_path = current_path.with_extension(&self.file_extension);
-                std::fs::rename(current_path, new_path)?;
+                let new_path = unique_path(current_path.with_extension(&self.file_extension));
+                move_file(&current_path, &new_path)?;
+                if self.sync {
+                    sync_parent_dir(&new_path)?;
+                }
+                if self.manifest {
+                    self.append_manifest(&new_path)?;
+                }
+                if let Some(on_complete) = self.on_complete.as_mut() {
+                    on_complete(&new_path);
+                }
             }
         }
         Ok(())
     }
+
+    fn append_manifest(&self, path: &Path) -> io::Result<()> {
+        let mut hasher = Sha256::new();
+        let size = io::copy(&mut File::open(path)?, &mut hasher)?;
+        let entry = ManifestEntry {
+            file: path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default(),
+            size,
+            sha256: hex::encode(hasher.finalize()),
+            first_timestamp: self.first_write,
+            last_timestamp: self.last_write,
+        };
+        let line = format!("{}\n", serde_json::to_string(&entry).unwrap_or_else(|_| String::from("{}")));
+        let manifest_path = path.with_file_name("manifest.jsonl");
+        let mut manifest = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&manifest_path)?;
+        // A single write keeps lines intact when several writers share the directory
+        manifest.write_all(line.as_bytes())?;
+        if self.sync {
+            manifest.sync_data()?;
+        }
+        Ok(())
+    }
+}
+
+/// Resolves a user name (or numeric uid) to a uid.
+pub fn lookup_uid(user: &str) -> io::Result<u32> {
+    if let Ok(uid) = user.parse() {
+        return Ok(uid);
+    }
+    let name = CString::new(user).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let passwd = unsafe { libc::getpwnam(name.as_ptr()) };
+    if passwd.is_null() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, format!("Unknown user: {}", user)));
+    }
+    Ok(unsafe { (*passwd).pw_uid })
+}
+
+/// Resolves a group name (or numeric gid) to a gid.
+pub fn lookup_gid(group: &str) -> io::Result<u32> {
+    if let Ok(gid) = group.parse() {
+        return Ok(gid);
+    }
+    let name = CString::new(group).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let entry = unsafe { libc::getgrnam(name.as_ptr()) };
+    if entry.is_null() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, format!("Unknown group: {}", group)));
+    }
+    Ok(unsafe { (*entry).gr_gid })
+}
+
+// Returns `path` if nothing exists there yet, otherwise the first free
+// `<stem>_<n>.<ext>` sibling so an existing file is never clobbered.
+fn unique_path(path: PathBuf) -> PathBuf {
+    if !path.exists() {
+        return path;
+    }
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let extension = path.extension().map(|e| e.to_string_lossy().into_owned());
+    let mut n = 1;
+    loop {
+        let file_name = match &extension {
+            Some(ext) => format!("{}_{}.{}", stem, n, ext),
+            None => format!("{}_{}", stem, n),
+        };
+        let candidate = path.with_file_name(file_name);
+        if !candidate.exists() {
+            return candidate;
+        }
+        n += 1;
+    }
+}
+
+// Persists the directory entry of a freshly renamed file.
+fn sync_parent_dir(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(dir) => File::open(dir)?.sync_all(),
+        None => Ok(()),
+    }
+}
+
+// Renames `from` to `to`, falling back to copy + remove when the two paths
+// live on different filesystems (bind mounts, tmpfs staging dirs).
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    match std::fs::rename(from, to) {
+        Ok(()) => Ok(()),
+        Err(e) if e.raw_os_error() == Some(libc::EXDEV) => {
+            std::fs::copy(from, to)?;
+            std::fs::remove_file(from)
+        }
+        Err(e) => Err(e),
+    }
 }
 
 impl Write for RotatingFileWriter {
@@ -104,7 +295,11 @@ impl Write for RotatingFileWriter {
 
     fn flush(&mut self) -> io::Result<()> {
         if let Some(file) = self.current_file.as_mut() {
-            file.flush()
+            file.flush()?;
+            if self.sync {
+                file.get_ref().sync_data()?;
+            }
+            Ok(())
         } else {
             Ok(())
         }
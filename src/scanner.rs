@@ -0,0 +1,53 @@
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+// Hosts remembered before the port history is reset
+const SCAN_MAX_HOSTS: usize = 65536;
+
+/// Thresholds from the `[scanner]` section.
+#[derive(Clone)]
+pub struct ScannerSettings {
+    pub window: Duration,
+    pub ports: usize,
+}
+
+struct HostActivity {
+    window_start: Instant,
+    ports: HashSet<u16>,
+}
+
+/// Flags hosts that SYN many distinct ports within a time window.
+pub struct ScanDetector {
+    settings: ScannerSettings,
+    hosts: HashMap<IpAddr, HostActivity>,
+}
+
+impl ScanDetector {
+    pub fn new(settings: ScannerSettings) -> Self {
+        ScanDetector { settings, hosts: HashMap::new() }
+    }
+
+    /// Records a SYN from `ip` to `port` and returns whether the host has now
+    /// reached the distinct port threshold in its current window.
+    pub fn observe(&mut self, ip: IpAddr, port: u16) -> bool {
+        if self.hosts.len() >= SCAN_MAX_HOSTS && !self.hosts.contains_key(&ip) {
+            self.hosts.clear();
+        }
+        let now = Instant::now();
+        let activity = self.hosts.entry(ip).or_insert_with(|| HostActivity {
+            window_start: now,
+            ports: HashSet::new(),
+        });
+        if now.duration_since(activity.window_start) > self.settings.window {
+            activity.window_start = now;
+            activity.ports.clear();
+        }
+        // A flagged host stays flagged for the window, so its ports stop
+        // being recorded once the threshold is reached
+        if activity.ports.len() < self.settings.ports {
+            activity.ports.insert(port);
+        }
+        activity.ports.len() >= self.settings.ports
+    }
+}
@@ -0,0 +1,116 @@
+use std::collections::VecDeque;
+use std::io;
+use std::net::UdpSocket;
+use std::time::Duration;
+
+const SFLOW_VERSION: u32 = 5;
+// Sample formats (enterprise 0)
+const FLOW_SAMPLE: u32 = 1;
+const EXPANDED_FLOW_SAMPLE: u32 = 3;
+// Flow record format of a sampled packet header
+const RAW_PACKET_HEADER: u32 = 1;
+const HEADER_PROTOCOL_ETHERNET: u32 = 1;
+
+/// Receives sFlow v5 datagrams and hands out the Ethernet headers sampled
+/// by the switch. Counter samples are ignored.
+pub struct SflowReceiver {
+    socket: UdpSocket,
+    buffer: Vec<u8>,
+    frames: VecDeque<Vec<u8>>,
+}
+
+impl SflowReceiver {
+    pub fn bind(listen: &str) -> io::Result<Self> {
+        let socket = UdpSocket::bind(listen)?;
+        // Wake up periodically so the capture loop can notice shutdown requests
+        socket.set_read_timeout(Some(Duration::from_secs(1)))?;
+        Ok(SflowReceiver { socket, buffer: vec![0; 65535], frames: VecDeque::new() })
+    }
+
+    /// Returns the next sampled frame, waiting for a datagram if needed.
+    pub fn next_frame(&mut self) -> io::Result<Vec<u8>> {
+        loop {
+            if let Some(frame) = self.frames.pop_front() {
+                return Ok(frame);
+            }
+            let length = match self.socket.recv(&mut self.buffer) {
+                Ok(length) => length,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    return Err(io::Error::new(io::ErrorKind::TimedOut, "sFlow read timed out"));
+                }
+                Err(e) => return Err(e),
+            };
+            // Malformed datagrams are dropped with whatever they yielded so far
+            let _ = parse_datagram(&self.buffer[..length], &mut self.frames);
+        }
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn u32(&mut self) -> Option<u32> {
+        let bytes = self.bytes(4)?;
+        Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn bytes(&mut self, length: usize) -> Option<&'a [u8]> {
+        if self.data.len() < length {
+            return None;
+        }
+        let (bytes, rest) = self.data.split_at(length);
+        self.data = rest;
+        Some(bytes)
+    }
+
+    // XDR opaque data is padded to a multiple of four bytes
+    fn opaque(&mut self, length: usize) -> Option<&'a [u8]> {
+        let bytes = self.bytes(length)?;
+        self.bytes((4 - length % 4) % 4)?;
+        Some(bytes)
+    }
+}
+
+fn parse_datagram(datagram: &[u8], frames: &mut VecDeque<Vec<u8>>) -> Option<()> {
+    let mut reader = Reader { data: datagram };
+    if reader.u32()? != SFLOW_VERSION {
+        return None;
+    }
+    let agent_length = match reader.u32()? {
+        1 => 4,
+        2 => 16,
+        _ => return None,
+    };
+    reader.bytes(agent_length)?;
+    reader.bytes(12)?; // sub-agent ID, sequence number, uptime
+
+    for _ in 0..reader.u32()? {
+        let format = reader.u32()?;
+        let length = reader.u32()? as usize;
+        let mut sample = Reader { data: reader.opaque(length)? };
+        let header_words = match format {
+            FLOW_SAMPLE => 7,
+            EXPANDED_FLOW_SAMPLE => 10,
+            _ => continue,
+        };
+        sample.bytes(header_words * 4)?;
+
+        for _ in 0..sample.u32()? {
+            let record_format = sample.u32()?;
+            let record_length = sample.u32()? as usize;
+            let mut record = Reader { data: sample.opaque(record_length)? };
+            if record_format != RAW_PACKET_HEADER || record.u32()? != HEADER_PROTOCOL_ETHERNET {
+                continue;
+            }
+            record.bytes(8)?; // frame length, bytes stripped
+            let header_length = record.u32()? as usize;
+            let header = record.opaque(header_length)?;
+            if header.len() >= 14 {
+                frames.push_back(header.to_vec());
+            }
+        }
+    }
+    Some(())
+}
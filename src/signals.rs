@@ -0,0 +1,54 @@
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use signal_hook::consts::{SIGUSR1, SIGUSR2};
+
+/// Process-wide flags set from signal handlers and polled by the capture loop.
+pub struct Signals {
+    running: Arc<AtomicBool>,
+    rotate: Arc<AtomicBool>,
+    dump: Arc<AtomicBool>,
+}
+
+impl Signals {
+    /// SIGINT/SIGTERM request shutdown, SIGUSR1 a statistics dump and SIGUSR2
+    /// an immediate rotation.
+    pub fn install() -> Result<Self, Box<dyn std::error::Error>> {
+        let running = Arc::new(AtomicBool::new(true));
+        let r = running.clone();
+        ctrlc::set_handler(move || {
+            r.store(false, Ordering::SeqCst);
+        })?;
+
+        let rotate = Arc::new(AtomicBool::new(false));
+        signal_hook::flag::register(SIGUSR2, rotate.clone())?;
+
+        let dump = Arc::new(AtomicBool::new(false));
+        signal_hook::flag::register(SIGUSR1, dump.clone())?;
+
+        Ok(Signals { running, rotate, dump })
+    }
+
+    pub fn running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    /// Returns true once per received SIGUSR2.
+    pub fn take_rotate_request(&self) -> bool {
+        self.rotate.swap(false, Ordering::SeqCst)
+    }
+
+    /// Returns true once per received SIGUSR1.
+    pub fn take_dump_request(&self) -> bool {
+        self.dump.swap(false, Ordering::SeqCst)
+    }
+}
+
+/// Forwards `signal` to a child process.
+pub fn forward(pid: u32, signal: i32) -> io::Result<()> {
+    if unsafe { libc::kill(pid as libc::pid_t, signal) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
@@ -0,0 +1,278 @@
+use std::fs;
+use std::path::Path;
+use crate::fingerprint::initial_ttl;
+use crate::p0f::Observation;
+
+// Furthest a signature's initial TTL may be from the observed TTL
+const MAX_DISTANCE: u8 = 35;
+
+// Quirks p0f tolerates in a fuzzy match, since middleboxes commonly rewrite them
+const FUZZY_QUIRKS: [&str; 4] = ["df", "id+", "id-", "ecn"];
+
+/// Which handshake packet a signature describes.
+#[derive(Clone, Copy, PartialEq)]
+enum Section {
+    Request,
+    Response,
+    Any,
+    Ignored,
+}
+
+/// A p0f style label, `type:class:name:flavor`.
+pub struct Label {
+    /// `unix`, `win` ... or `!` for tools and userland stacks
+    pub class: String,
+    pub name: String,
+    pub flavor: String,
+}
+
+impl Label {
+    fn parse(text: &str) -> Option<Label> {
+        let mut fields = text.splitn(4, ':');
+        let kind = fields.next()?;
+        let label = Label {
+            class: fields.next()?.to_string(),
+            name: fields.next()?.to_string(),
+            flavor: fields.next().unwrap_or("").to_string(),
+        };
+        (kind == "s" || kind == "g").then_some(label)
+    }
+
+    /// Tools and userland stacks rather than operating systems.
+    pub fn is_tool(&self) -> bool {
+        self.class == "!"
+    }
+
+    /// `name flavor`, e.g. `Linux 3.11 and newer`.
+    pub fn text(&self) -> String {
+        if self.flavor.is_empty() { self.name.clone() } else { format!("{} {}", self.name, self.flavor) }
+    }
+}
+
+enum WindowSize {
+    Any,
+    Value(u16),
+    Mss(u32),
+    Mtu(u32),
+    Multiple(u32),
+}
+
+struct P0fSignature {
+    ittl: u8,
+    olen: usize,
+    mss: Option<u16>,
+    window: WindowSize,
+    scale: Option<u8>,
+    layout: String,
+    quirks: Vec<String>,
+    payload: Option<bool>,
+}
+
+impl P0fSignature {
+    fn parse(fields: &[&str]) -> Option<P0fSignature> {
+        if fields[0] != "4" && fields[0] != "*" {
+            return None; // IPv6 only
+        }
+        let (window, scale) = fields[4].split_once(',')?;
+        let any = |value: &str| value == "*";
+        Some(P0fSignature {
+            ittl: fields[1].split(['+', '-']).next()?.parse().ok()?,
+            olen: fields[2].parse().ok()?,
+            mss: if any(fields[3]) { None } else { Some(fields[3].parse().ok()?) },
+            window: if any(window) {
+                WindowSize::Any
+            } else if let Some(n) = window.strip_prefix("mss*") {
+                WindowSize::Mss(n.parse().ok()?)
+            } else if let Some(n) = window.strip_prefix("mtu*") {
+                WindowSize::Mtu(n.parse().ok()?)
+            } else if let Some(n) = window.strip_prefix('%') {
+                WindowSize::Multiple(n.parse().ok()?)
+            } else {
+                WindowSize::Value(window.parse().ok()?)
+            },
+            scale: if any(scale) { None } else { Some(scale.parse().ok()?) },
+            layout: fields[5].to_string(),
+            quirks: fields[6].split(',').filter(|quirk| !quirk.is_empty()).map(str::to_string).collect(),
+            payload: match fields[7] {
+                "0" => Some(false),
+                "+" => Some(true),
+                _ => None,
+            },
+        })
+    }
+
+    fn quality(&self, observed: &Observation) -> Option<&'static str> {
+        let mut fuzzy = false;
+
+        if observed.ttl > self.ittl || self.ittl - observed.ttl > MAX_DISTANCE {
+            return None;
+        }
+        if initial_ttl(observed.ttl) != self.ittl {
+            fuzzy = true;
+        }
+        if observed.olen != self.olen
+            || self.mss.is_some_and(|mss| observed.mss != Some(mss))
+            || self.scale.is_some_and(|scale| observed.scale != scale)
+            || self.payload.is_some_and(|payload| observed.has_payload != payload)
+            || observed.layout.join(",") != self.layout
+        {
+            return None;
+        }
+
+        let window = observed.window as u32;
+        let mss = observed.mss.unwrap_or(0) as u32;
+        let window_matches = match self.window {
+            WindowSize::Any => true,
+            WindowSize::Value(value) => observed.window == value,
+            WindowSize::Mss(n) => mss > 0 && window == mss * n,
+            WindowSize::Mtu(n) => mss > 0 && window == (mss + 40) * n,
+            WindowSize::Multiple(n) => n > 0 && window % n == 0,
+        };
+        if !window_matches {
+            return None;
+        }
+
+        let same_quirks = |tolerated: &[&str]| {
+            let observed_quirks: Vec<&str> = observed.quirks.iter().copied().filter(|quirk| !tolerated.contains(quirk)).collect();
+            let expected_quirks: Vec<&str> = self.quirks.iter().map(String::as_str).filter(|quirk| !tolerated.contains(quirk)).collect();
+            observed_quirks.len() == expected_quirks.len() && observed_quirks.iter().all(|quirk| expected_quirks.contains(quirk))
+        };
+        if !same_quirks(&[]) {
+            if !same_quirks(&FUZZY_QUIRKS) {
+                return None;
+            }
+            fuzzy = true;
+        }
+
+        Some(if fuzzy { "fuzzy" } else { "exact" })
+    }
+}
+
+enum Signature {
+    P0f(P0fSignature),
+    /// muonfp string with `*` allowed for any of its four fields
+    Muonfp(Vec<String>),
+}
+
+struct Entry {
+    section: Section,
+    label: usize,
+    signature: Signature,
+}
+
+/// Best signature for a fingerprint.
+pub struct Match<'a> {
+    pub label: &'a Label,
+    /// `exact`, or `fuzzy` when the TTL or p0f's tolerated quirks differ
+    pub quality: &'static str,
+}
+
+/// Signatures loaded from a p0f.fp database or a muonfp signature file.
+///
+/// Both use the p0f.fp layout: a `label = s:class:name:flavor` line followed
+/// by its `sig =` lines. A sig is either a p0f v3 TCP signature or a muonfp
+/// fingerprint (`window:options:mss:scale`, `*` matches anything). p0f's
+/// `[tcp:request]` and `[tcp:response]` sections are honoured; all other
+/// sections are skipped.
+#[derive(Default)]
+pub struct SignatureDb {
+    labels: Vec<Label>,
+    entries: Vec<Entry>,
+}
+
+impl SignatureDb {
+    /// The catalog of well-known stacks and scanners built into the binary.
+    pub fn builtin() -> SignatureDb {
+        SignatureDb::parse(include_str!("catalog.fp")).expect("built-in catalog is valid")
+    }
+
+    pub fn load(path: &Path) -> Result<SignatureDb, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        SignatureDb::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn parse(text: &str) -> Result<SignatureDb, String> {
+        let mut db = SignatureDb::default();
+        let mut section = Section::Any;
+        let mut label = None;
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+                continue;
+            }
+            if line.starts_with('[') {
+                section = match line {
+                    "[tcp:request]" => Section::Request,
+                    "[tcp:response]" => Section::Response,
+                    "[tcp]" => Section::Any,
+                    _ => Section::Ignored,
+                };
+                label = None;
+                continue;
+            }
+            if section == Section::Ignored {
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(format!("line {}: expected key = value", number + 1));
+            };
+            match key.trim() {
+                "label" => {
+                    let parsed = Label::parse(value.trim())
+                        .ok_or_else(|| format!("line {}: invalid label", number + 1))?;
+                    db.labels.push(parsed);
+                    label = Some(db.labels.len() - 1);
+                }
+                "sig" => {
+                    let label = label.ok_or_else(|| format!("line {}: sig without a label", number + 1))?;
+                    let fields: Vec<&str> = value.trim().split(':').collect();
+                    let signature = match fields.len() {
+                        4 => Signature::Muonfp(fields.iter().map(|field| field.to_string()).collect()),
+                        8 => match P0fSignature::parse(&fields) {
+                            Some(signature) => Signature::P0f(signature),
+                            None if fields[0] == "6" => continue,
+                            None => return Err(format!("line {}: invalid p0f signature", number + 1)),
+                        },
+                        _ => return Err(format!("line {}: unrecognized signature", number + 1)),
+                    };
+                    db.entries.push(Entry { section, label, signature });
+                }
+                // sys = ... and other p0f metadata
+                _ => {}
+            }
+        }
+
+        Ok(db)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns the first exact match, or failing that the first fuzzy one.
+    pub fn best_match(&self, is_syn_ack: bool, observed: &Observation, muonfp_fingerprint: &str) -> Option<Match<'_>> {
+        let section = if is_syn_ack { Section::Response } else { Section::Request };
+        let observed_fields: Vec<&str> = muonfp_fingerprint.split(':').collect();
+        let mut fuzzy = None;
+
+        for entry in self.entries.iter().filter(|entry| entry.section == section || entry.section == Section::Any) {
+            let quality = match &entry.signature {
+                Signature::P0f(signature) => signature.quality(observed),
+                Signature::Muonfp(fields) => {
+                    let matches = fields.len() == observed_fields.len()
+                        && fields.iter().zip(&observed_fields).all(|(field, observed)| field == "*" || field == observed);
+                    matches.then_some("exact")
+                }
+            };
+            match quality {
+                Some("exact") => return Some(Match { label: &self.labels[entry.label], quality: "exact" }),
+                Some(quality) if fuzzy.is_none() => fuzzy = Some(Match { label: &self.labels[entry.label], quality }),
+                _ => {}
+            }
+        }
+
+        fuzzy
+    }
+}
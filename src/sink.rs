@@ -0,0 +1,394 @@
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use config::Config;
+use log::{info, warn};
+use serde_json::Value;
+use crate::api::Api;
+use crate::health::Heartbeat;
+use crate::stats::Stats;
+
+mod clickhouse;
+mod redis;
+mod nats;
+mod syslog;
+mod journald;
+mod socket;
+mod net;
+mod websocket;
+mod statsd;
+mod otlp;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+#[cfg(feature = "postgres")]
+mod postgres;
+#[cfg(feature = "kafka")]
+mod kafka;
+#[cfg(feature = "zeromq")]
+mod zeromq;
+#[cfg(feature = "amqp")]
+mod amqp;
+#[cfg(feature = "grpc")]
+mod grpc;
+#[cfg(feature = "parquet")]
+mod parquet;
+
+use clickhouse::ClickhouseTransport;
+use redis::RedisTransport;
+use nats::NatsTransport;
+use syslog::SyslogTransport;
+use self::journald::JournaldTransport;
+use socket::SocketTransport;
+use net::NetTransport;
+use websocket::WebsocketTransport;
+use statsd::StatsdSink;
+use otlp::OtlpSink;
+#[cfg(feature = "sqlite")]
+use sqlite::SqliteTransport;
+#[cfg(feature = "postgres")]
+use self::postgres::PostgresTransport;
+#[cfg(feature = "kafka")]
+use kafka::KafkaTransport;
+#[cfg(feature = "zeromq")]
+use zeromq::ZeromqTransport;
+#[cfg(feature = "amqp")]
+use amqp::AmqpTransport;
+#[cfg(feature = "grpc")]
+use grpc::{GrpcClientTransport, GrpcServerTransport};
+#[cfg(feature = "parquet")]
+use self::parquet::ParquetTransport;
+
+// Records waiting for a sink's background thread; more are dropped, not queued
+const SINK_QUEUE_SIZE: usize = 65536;
+
+/// A destination for fingerprint and event records besides the .out files.
+pub trait Sink {
+    /// Hands over one record. Must not block the capture loop.
+    fn send(&mut self, record: &Value);
+
+    /// Receives the capture counters every few seconds.
+    fn update_stats(&mut self, _stats: &Stats) {}
+
+    /// Called every few seconds while the capture or supervisor loop is
+    /// making progress.
+    fn heartbeat(&mut self) {}
+
+    /// Records waiting and dropped so far, for sinks with a queue.
+    fn queue_status(&self) -> Option<String> {
+        None
+    }
+
+    /// Delivers everything sent so far and releases the destination.
+    fn close(&mut self) {}
+}
+
+/// Delivers batches of records for a sink running on a background thread.
+pub trait Transport: Send + 'static {
+    /// Human readable destination, used in log messages.
+    fn describe(&self) -> String;
+
+    fn deliver(&mut self, batch: &[Value]) -> io::Result<()>;
+}
+
+/// How a background sink groups records before delivering them.
+#[derive(Clone, Copy)]
+pub struct Batching {
+    pub size: usize,
+    pub interval: Duration,
+}
+
+impl Batching {
+    /// Reads `<section>.batch_size` and `<section>.batch_interval` (seconds).
+    pub fn from_settings(settings: &Config, section: &str, size: usize, interval: u64) -> Self {
+        Batching {
+            size: settings.get_int(&format!("{}.batch_size", section)).map_or(size, |size| size.max(1) as usize),
+            interval: Duration::from_secs(settings.get_int(&format!("{}.batch_interval", section)).unwrap_or(interval as i64) as u64),
+        }
+    }
+}
+
+/// Runs a transport on its own thread behind a bounded queue, so a slow or
+/// unreachable destination costs records rather than capture time.
+pub struct Background {
+    name: String,
+    tx: Option<SyncSender<Value>>,
+    handle: Option<JoinHandle<()>>,
+    queued: Arc<AtomicUsize>,
+    dropped: u64,
+}
+
+impl Background {
+    pub fn spawn(transport: impl Transport, batching: Batching) -> Self {
+        let name = transport.describe();
+        let (tx, rx) = mpsc::sync_channel(SINK_QUEUE_SIZE);
+        let queued = Arc::new(AtomicUsize::new(0));
+        let dequeued = Arc::clone(&queued);
+        let handle = thread::spawn(move || deliver_batches(transport, rx, batching, &dequeued));
+        Background { name, tx: Some(tx), handle: Some(handle), queued, dropped: 0 }
+    }
+}
+
+impl Sink for Background {
+    fn send(&mut self, record: &Value) {
+        let Some(tx) = self.tx.as_ref() else { return };
+        match tx.try_send(record.clone()) {
+            Ok(()) => {
+                self.queued.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(TrySendError::Full(_)) => {
+                if self.dropped == 0 {
+                    warn!("Output to {} is falling behind, dropping records", self.name);
+                }
+                self.dropped += 1;
+            }
+            Err(TrySendError::Disconnected(_)) => {}
+        }
+    }
+
+    fn queue_status(&self) -> Option<String> {
+        Some(format!("{}: {} queued, {} dropped", self.name, self.queued.load(Ordering::Relaxed), self.dropped))
+    }
+
+    fn close(&mut self) {
+        self.tx.take();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+        if self.dropped > 0 {
+            warn!("Dropped {} records bound for {}", self.dropped, self.name);
+        }
+    }
+}
+
+// Collects records until the batch is full or the interval runs out, and
+// delivers what is left once the sink is closed.
+fn deliver_batches(mut transport: impl Transport, rx: Receiver<Value>, batching: Batching, queued: &AtomicUsize) {
+    let mut batch = Vec::with_capacity(batching.size);
+    let mut deadline = Instant::now() + batching.interval;
+    loop {
+        let closed = match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+            Ok(record) => {
+                queued.fetch_sub(1, Ordering::Relaxed);
+                batch.push(record);
+                if batch.len() < batching.size {
+                    continue;
+                }
+                false
+            }
+            Err(RecvTimeoutError::Timeout) => false,
+            Err(RecvTimeoutError::Disconnected) => true,
+        };
+        if !batch.is_empty() {
+            if let Err(e) = transport.deliver(&batch) {
+                warn!("Failed to deliver {} records to {}: {}", batch.len(), transport.describe(), e);
+            }
+            batch.clear();
+        }
+        if closed {
+            break;
+        }
+        deadline = Instant::now() + batching.interval;
+    }
+}
+
+/// Every configured sink.
+#[derive(Default)]
+pub struct Sinks {
+    sinks: Vec<Box<dyn Sink>>,
+}
+
+impl Sinks {
+    /// Builds the sinks whose sections are present in the configuration.
+    pub fn from_settings(settings: &Config) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut sinks: Vec<Box<dyn Sink>> = Vec::new();
+
+        if settings.get_string("sqlite.path").is_ok() {
+            #[cfg(feature = "sqlite")]
+            {
+                let transport = SqliteTransport::from_settings(settings)?;
+                sinks.push(Box::new(Background::spawn(transport, Batching::from_settings(settings, "sqlite", 1000, 5))));
+            }
+            #[cfg(not(feature = "sqlite"))]
+            return Err("SQLite output requires building with --features sqlite".into());
+        }
+
+        if settings.get_string("postgres.url").is_ok() {
+            #[cfg(feature = "postgres")]
+            {
+                let transport = PostgresTransport::from_settings(settings)?;
+                sinks.push(Box::new(Background::spawn(transport, Batching::from_settings(settings, "postgres", 500, 5))));
+            }
+            #[cfg(not(feature = "postgres"))]
+            return Err("PostgreSQL output requires building with --features postgres".into());
+        }
+
+        if settings.get_string("clickhouse.url").is_ok() {
+            let transport = ClickhouseTransport::from_settings(settings)?;
+            sinks.push(Box::new(Background::spawn(transport, Batching::from_settings(settings, "clickhouse", 10000, 10))));
+        }
+
+        if settings.get_string("parquet.dir").is_ok() {
+            #[cfg(feature = "parquet")]
+            {
+                let transport = ParquetTransport::from_settings(settings)?;
+                sinks.push(Box::new(Background::spawn(transport, Batching::from_settings(settings, "parquet", 1000, 10))));
+            }
+            #[cfg(not(feature = "parquet"))]
+            return Err("Parquet output requires building with --features parquet".into());
+        }
+
+        if settings.get_string("kafka.brokers").is_ok() {
+            #[cfg(feature = "kafka")]
+            {
+                let transport = KafkaTransport::from_settings(settings)?;
+                sinks.push(Box::new(Background::spawn(transport, Batching::from_settings(settings, "kafka", 1000, 1))));
+            }
+            #[cfg(not(feature = "kafka"))]
+            return Err("Kafka output requires building with --features kafka".into());
+        }
+
+        if settings.get_string("redis.address").is_ok() {
+            let transport = RedisTransport::from_settings(settings)?;
+            sinks.push(Box::new(Background::spawn(transport, Batching::from_settings(settings, "redis", 100, 1))));
+        }
+
+        if settings.get_string("nats.address").is_ok() {
+            let transport = NatsTransport::from_settings(settings)?;
+            sinks.push(Box::new(Background::spawn(transport, Batching::from_settings(settings, "nats", 100, 1))));
+        }
+
+        if settings.get_string("zeromq.bind").is_ok() {
+            #[cfg(feature = "zeromq")]
+            {
+                let transport = ZeromqTransport::from_settings(settings)?;
+                sinks.push(Box::new(Background::spawn(transport, Batching::from_settings(settings, "zeromq", 100, 1))));
+            }
+            #[cfg(not(feature = "zeromq"))]
+            return Err("ZeroMQ output requires building with --features zeromq".into());
+        }
+
+        if settings.get_string("amqp.url").is_ok() {
+            #[cfg(feature = "amqp")]
+            {
+                let transport = AmqpTransport::from_settings(settings)?;
+                sinks.push(Box::new(Background::spawn(transport, Batching::from_settings(settings, "amqp", 100, 1))));
+            }
+            #[cfg(not(feature = "amqp"))]
+            return Err("AMQP output requires building with --features amqp".into());
+        }
+
+        if settings.get_string("syslog.address").is_ok() {
+            let transport = SyslogTransport::from_settings(settings)?;
+            sinks.push(Box::new(Background::spawn(transport, Batching::from_settings(settings, "syslog", 100, 1))));
+        }
+
+        if settings.get_bool("journald.records").unwrap_or(false) {
+            sinks.push(Box::new(Background::spawn(JournaldTransport::from_settings(settings)?, Batching::from_settings(settings, "journald", 100, 1))));
+        }
+
+        if settings.get_string("socket.path").is_ok() {
+            let transport = SocketTransport::from_settings(settings)?;
+            sinks.push(Box::new(Background::spawn(transport, Batching::from_settings(settings, "socket", 1, 1))));
+        }
+
+        if settings.get_string("net.address").is_ok() {
+            let transport = NetTransport::from_settings(settings)?;
+            sinks.push(Box::new(Background::spawn(transport, Batching::from_settings(settings, "net", 100, 1))));
+        }
+
+        if settings.get_string("websocket.listen").is_ok() {
+            let transport = WebsocketTransport::from_settings(settings)?;
+            sinks.push(Box::new(Background::spawn(transport, Batching::from_settings(settings, "websocket", 1, 1))));
+        }
+
+        if settings.get_string("grpc.listen").is_ok() {
+            #[cfg(feature = "grpc")]
+            {
+                let transport = GrpcServerTransport::from_settings(settings)?;
+                sinks.push(Box::new(Background::spawn(transport, Batching::from_settings(settings, "grpc", 1, 1))));
+            }
+            #[cfg(not(feature = "grpc"))]
+            return Err("gRPC output requires building with --features grpc".into());
+        }
+
+        if settings.get_string("grpc.collector").is_ok() {
+            #[cfg(feature = "grpc")]
+            {
+                let transport = GrpcClientTransport::from_settings(settings)?;
+                sinks.push(Box::new(Background::spawn(transport, Batching::from_settings(settings, "grpc", 100, 1))));
+            }
+            #[cfg(not(feature = "grpc"))]
+            return Err("gRPC output requires building with --features grpc".into());
+        }
+
+        if settings.get_string("statsd.address").is_ok() {
+            sinks.push(Box::new(StatsdSink::from_settings(settings)?));
+        }
+
+        if settings.get_string("otlp.endpoint").is_ok() {
+            sinks.push(Box::new(OtlpSink::from_settings(settings)?));
+        }
+
+        if let Some(heartbeat) = Heartbeat::from_settings(settings)? {
+            sinks.push(Box::new(heartbeat));
+        }
+
+        if let Some(api) = Api::from_settings(settings)? {
+            sinks.push(Box::new(api));
+        }
+
+        if !sinks.is_empty() {
+            info!("Sending records to {} additional output(s)", sinks.len());
+        }
+        Ok(Sinks { sinks })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sinks.is_empty()
+    }
+
+    pub fn send(&mut self, record: &Value) {
+        for sink in &mut self.sinks {
+            sink.send(record);
+        }
+    }
+
+    pub fn update_stats(&mut self, stats: &Stats) {
+        for sink in &mut self.sinks {
+            sink.update_stats(stats);
+        }
+    }
+
+    pub fn heartbeat(&mut self) {
+        for sink in &mut self.sinks {
+            sink.heartbeat();
+        }
+    }
+
+    /// Queue depth and drops of every queued sink, for the stats summary.
+    pub fn queue_status(&self) -> String {
+        let status: Vec<String> = self.sinks.iter().filter_map(|sink| sink.queue_status()).collect();
+        if status.is_empty() { "none".to_string() } else { status.join(", ") }
+    }
+
+    /// Sends a record that is already rendered as a JSON line, as merged from
+    /// supervised capture children.
+    pub fn send_line(&mut self, line: &str) {
+        if self.sinks.is_empty() {
+            return;
+        }
+        match serde_json::from_str::<Value>(line) {
+            Ok(record) => self.send(&record),
+            Err(e) => warn!("Skipping malformed record for outputs: {}", e),
+        }
+    }
+
+    pub fn close(&mut self) {
+        for sink in &mut self.sinks {
+            sink.close();
+        }
+    }
+}
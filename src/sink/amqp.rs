@@ -0,0 +1,102 @@
+use std::io;
+use std::time::Duration;
+use amiquip::{AmqpProperties, Channel, Confirm, Connection, Publish};
+use config::Config;
+use crossbeam_channel::Receiver;
+use serde_json::Value;
+use crate::format::Format;
+use super::Transport;
+
+const AMQP_CONFIRM_TIMEOUT: Duration = Duration::from_secs(30);
+
+struct Session {
+    // Closing the connection closes the channel, so it is kept alongside
+    _connection: Connection,
+    channel: Channel,
+    confirms: Receiver<Confirm>,
+    next_delivery_tag: u64,
+    // Highest delivery tag the broker has acknowledged
+    confirmed: u64,
+}
+
+/// Publishes records to an AMQP 0.9.1 exchange (RabbitMQ) with publisher
+/// confirms, so a batch only counts as delivered once the broker has taken it.
+pub struct AmqpTransport {
+    url: String,
+    exchange: String,
+    routing_key: String,
+    format: Format,
+    session: Option<Session>,
+}
+
+impl AmqpTransport {
+    pub fn from_settings(settings: &Config) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(AmqpTransport {
+            url: settings.get_string("amqp.url")?,
+            exchange: settings.get_string("amqp.exchange").unwrap_or_else(|_| "muonfp".to_string()),
+            routing_key: settings.get_string("amqp.routing_key").unwrap_or_else(|_| "{event_type}".to_string()),
+            format: Format::text_from_settings(settings, "amqp")?,
+            session: None,
+        })
+    }
+
+    fn open(&self) -> amiquip::Result<Session> {
+        let mut connection = if self.url.starts_with("amqps://") {
+            Connection::open(&self.url)?
+        } else {
+            Connection::insecure_open(&self.url)?
+        };
+        let channel = connection.open_channel(None)?;
+        let confirms = channel.listen_for_publisher_confirms()?;
+        channel.enable_publisher_confirms()?;
+        Ok(Session { _connection: connection, channel, confirms, next_delivery_tag: 1, confirmed: 0 })
+    }
+
+    fn publish_batch(&mut self, batch: &[Value]) -> io::Result<()> {
+        if self.session.is_none() {
+            self.session = Some(self.open().map_err(amqp_error)?);
+        }
+        let session = self.session.as_mut().expect("opened above");
+        for record in batch {
+            // `{event_type}` lets a topic exchange route fingerprints and each event type apart
+            let event_type = record["event_type"].as_str().unwrap_or("fingerprint");
+            let routing_key = self.routing_key.replace("{event_type}", event_type);
+            let properties = AmqpProperties::default().with_content_type(self.format.content_type().to_string());
+            let body = self.format.render(record);
+            let publish = Publish::with_properties(body.as_bytes(), routing_key, properties);
+            session.channel.basic_publish(self.exchange.as_str(), publish).map_err(amqp_error)?;
+        }
+        let last_delivery_tag = session.next_delivery_tag + batch.len() as u64 - 1;
+        session.next_delivery_tag = last_delivery_tag + 1;
+
+        while session.confirmed < last_delivery_tag {
+            let confirm = session.confirms.recv_timeout(AMQP_CONFIRM_TIMEOUT)
+                .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "no publisher confirm from the broker"))?;
+            match confirm {
+                Confirm::Ack(payload) => session.confirmed = session.confirmed.max(payload.delivery_tag),
+                Confirm::Nack(payload) => {
+                    return Err(io::Error::new(io::ErrorKind::Other, format!("broker rejected delivery {}", payload.delivery_tag)));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Transport for AmqpTransport {
+    fn describe(&self) -> String {
+        format!("amqp exchange {}", self.exchange)
+    }
+
+    fn deliver(&mut self, batch: &[Value]) -> io::Result<()> {
+        let result = self.publish_batch(batch);
+        if result.is_err() {
+            self.session = None;
+        }
+        result
+    }
+}
+
+fn amqp_error(e: amiquip::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, e.to_string())
+}
@@ -0,0 +1,46 @@
+use std::io;
+use config::Config;
+use serde_json::Value;
+use super::Transport;
+
+/// Inserts records into a ClickHouse table through its HTTP interface as
+/// `JSONEachRow`, so fields the table doesn't have are simply skipped.
+pub struct ClickhouseTransport {
+    url: String,
+    table: String,
+    user: Option<String>,
+    password: Option<String>,
+}
+
+impl ClickhouseTransport {
+    pub fn from_settings(settings: &Config) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(ClickhouseTransport {
+            url: settings.get_string("clickhouse.url")?.trim_end_matches('/').to_string(),
+            table: settings.get_string("clickhouse.table").unwrap_or_else(|_| "muonfp".to_string()),
+            user: settings.get_string("clickhouse.user").ok(),
+            password: settings.get_string("clickhouse.password").ok(),
+        })
+    }
+}
+
+impl Transport for ClickhouseTransport {
+    fn describe(&self) -> String {
+        format!("clickhouse table {} at {}", self.table, self.url)
+    }
+
+    fn deliver(&mut self, batch: &[Value]) -> io::Result<()> {
+        let body: String = batch.iter().map(|record| format!("{}\n", record)).collect();
+        let mut request = ureq::post(&format!("{}/", self.url))
+            .query("query", &format!("INSERT INTO {} FORMAT JSONEachRow", self.table))
+            .query("input_format_skip_unknown_fields", "1")
+            .query("date_time_input_format", "best_effort");
+        if let Some(user) = &self.user {
+            request = request.set("X-ClickHouse-User", user);
+        }
+        if let Some(password) = &self.password {
+            request = request.set("X-ClickHouse-Key", password);
+        }
+        request.send_string(&body).map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+        Ok(())
+    }
+}
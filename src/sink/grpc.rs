@@ -0,0 +1,141 @@
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use config::Config;
+use log::{info, warn};
+use serde_json::Value;
+use tokio::runtime::Runtime;
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::{BroadcastStream, TcpListenerStream};
+use tokio_stream::{Stream, StreamExt};
+use tonic::transport::{Channel, Server};
+use tonic::{Request, Response, Status};
+use super::Transport;
+
+pub mod proto {
+    tonic::include_proto!("muonfp.v1");
+}
+
+use proto::collector_client::CollectorClient;
+use proto::sensor_server::{Sensor, SensorServer};
+
+// Records a subscriber may fall behind by before it skips ahead
+const GRPC_SUBSCRIBER_BACKLOG: usize = 4096;
+
+fn runtime() -> io::Result<Runtime> {
+    tokio::runtime::Builder::new_multi_thread().worker_threads(1).enable_all().build()
+}
+
+pub fn to_record(record: &Value) -> proto::Record {
+    let text = |name: &str| record[name].as_str().unwrap_or_default().to_string();
+    proto::Record {
+        event_type: record["event_type"].as_str().unwrap_or("fingerprint").to_string(),
+        timestamp: text("timestamp"),
+        hostname: text("hostname"),
+        sensor_id: text("sensor_id"),
+        interface: text("interface"),
+        ip_address: text("ip_address"),
+        src_port: record["src_port"].as_u64().unwrap_or_default() as u32,
+        dst_port: record["dst_port"].as_u64().unwrap_or_default() as u32,
+        muonfp_fingerprint: text("muonfp_fingerprint"),
+        os_guess: text("os_guess"),
+        tool_guess: text("tool_guess"),
+        ja4t: text("ja4t"),
+        json: record.to_string(),
+    }
+}
+
+struct SensorService {
+    records: broadcast::Sender<proto::Record>,
+}
+
+#[tonic::async_trait]
+impl Sensor for SensorService {
+    type SubscribeStream = Pin<Box<dyn Stream<Item = Result<proto::Record, Status>> + Send>>;
+
+    async fn subscribe(&self, request: Request<proto::SubscribeRequest>) -> Result<Response<Self::SubscribeStream>, Status> {
+        let event_types = request.into_inner().event_types;
+        // A lagging subscriber silently skips the records it missed
+        let stream = BroadcastStream::new(self.records.subscribe()).filter_map(move |record| {
+            let record = record.ok()?;
+            (event_types.is_empty() || event_types.contains(&record.event_type)).then_some(Ok(record))
+        });
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+/// Serves `Sensor.Subscribe`, streaming records to every subscriber.
+pub struct GrpcServerTransport {
+    listen: SocketAddr,
+    records: broadcast::Sender<proto::Record>,
+    // Runs the server for as long as the sink lives
+    _runtime: Runtime,
+}
+
+impl GrpcServerTransport {
+    pub fn from_settings(settings: &Config) -> Result<Self, Box<dyn std::error::Error>> {
+        let listen: SocketAddr = settings.get_string("grpc.listen")?.parse()?;
+        let runtime = runtime()?;
+        let listener = runtime.block_on(tokio::net::TcpListener::bind(listen))?;
+        let (records, _) = broadcast::channel(GRPC_SUBSCRIBER_BACKLOG);
+        let service = SensorServer::new(SensorService { records: records.clone() });
+        runtime.spawn(async move {
+            let result = Server::builder().add_service(service).serve_with_incoming(TcpListenerStream::new(listener)).await;
+            if let Err(e) = result {
+                warn!("gRPC server stopped: {}", e);
+            }
+        });
+        info!("Serving records over gRPC on {}", listen);
+        Ok(GrpcServerTransport { listen, records, _runtime: runtime })
+    }
+}
+
+impl Transport for GrpcServerTransport {
+    fn describe(&self) -> String {
+        format!("grpc server {}", self.listen)
+    }
+
+    fn deliver(&mut self, batch: &[Value]) -> io::Result<()> {
+        for record in batch {
+            // Fails only while nobody is subscribed
+            let _ = self.records.send(to_record(record));
+        }
+        Ok(())
+    }
+}
+
+/// Pushes every batch to a remote `Collector.Push` as one client stream.
+pub struct GrpcClientTransport {
+    endpoint: String,
+    runtime: Runtime,
+    client: Option<CollectorClient<Channel>>,
+}
+
+impl GrpcClientTransport {
+    pub fn from_settings(settings: &Config) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(GrpcClientTransport { endpoint: settings.get_string("grpc.collector")?, runtime: runtime()?, client: None })
+    }
+}
+
+impl Transport for GrpcClientTransport {
+    fn describe(&self) -> String {
+        format!("grpc collector {}", self.endpoint)
+    }
+
+    fn deliver(&mut self, batch: &[Value]) -> io::Result<()> {
+        let records: Vec<proto::Record> = batch.iter().map(to_record).collect();
+        let GrpcClientTransport { endpoint, runtime, client } = self;
+        let result: Result<(), Box<dyn std::error::Error + Send + Sync>> = runtime.block_on(async {
+            if client.is_none() {
+                *client = Some(CollectorClient::connect(endpoint.clone()).await?);
+            }
+            let client = client.as_mut().expect("connected above");
+            client.push(tokio_stream::iter(records)).await?;
+            Ok(())
+        });
+        result.map_err(|e| {
+            *client = None;
+            io::Error::new(io::ErrorKind::Other, e)
+        })
+    }
+}
@@ -0,0 +1,58 @@
+use std::io;
+use std::os::unix::net::UnixDatagram;
+use config::Config;
+use serde_json::Value;
+use crate::journald::{self, SYSLOG_IDENTIFIER};
+use crate::format::Format;
+use super::Transport;
+
+/// Writes records to the systemd journal with `MUONFP_*` fields, so
+/// `journalctl -t muonfp MUONFP_IP=192.0.2.1` finds a host's records.
+pub struct JournaldTransport {
+    format: Format,
+    socket: Option<UnixDatagram>,
+}
+
+impl JournaldTransport {
+    pub fn from_settings(settings: &Config) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(JournaldTransport { format: Format::text_from_settings(settings, "journald")?, socket: None })
+    }
+}
+
+impl Transport for JournaldTransport {
+    fn describe(&self) -> String {
+        "journald".to_string()
+    }
+
+    fn deliver(&mut self, batch: &[Value]) -> io::Result<()> {
+        if self.socket.is_none() {
+            self.socket = Some(journald::connect()?);
+        }
+        let socket = self.socket.as_ref().expect("connected above");
+        let result = batch.iter().try_for_each(|record| {
+            let message = self.format.render(record);
+            let event_type = record["event_type"].as_str().unwrap_or("fingerprint");
+            let mut fields = vec![
+                ("MESSAGE", message.as_str()),
+                ("PRIORITY", "6"),
+                ("SYSLOG_IDENTIFIER", SYSLOG_IDENTIFIER),
+                ("MUONFP_EVENT_TYPE", event_type),
+            ];
+            if let Some(ip_address) = record["ip_address"].as_str() {
+                fields.push(("MUONFP_IP", ip_address));
+            }
+            if let Some(fingerprint) = record["muonfp_fingerprint"].as_str() {
+                fields.push(("MUONFP_FP", fingerprint));
+            }
+            if let Some(os_guess) = record["os_guess"].as_str() {
+                fields.push(("MUONFP_OS_GUESS", os_guess));
+            }
+            socket.send(&journald::encode(&fields)).map(drop)
+        });
+        // Reconnect next time, e.g. after journald restarted
+        if result.is_err() {
+            self.socket = None;
+        }
+        result
+    }
+}
@@ -0,0 +1,82 @@
+use std::io;
+use std::time::Duration;
+use config::Config;
+use rdkafka::config::ClientConfig;
+use rdkafka::error::{KafkaError, RDKafkaErrorCode};
+use rdkafka::producer::{BaseProducer, BaseRecord, Producer};
+use serde_json::Value;
+use crate::format::Format;
+use super::Transport;
+
+const KAFKA_FLUSH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Publishes each record as a JSON message, keyed by its IP so all records
+/// of a host land on the same partition.
+pub struct KafkaTransport {
+    topic: String,
+    key_by_ip: bool,
+    format: Format,
+    producer: BaseProducer,
+}
+
+impl KafkaTransport {
+    pub fn from_settings(settings: &Config) -> Result<Self, Box<dyn std::error::Error>> {
+        let key_by_ip = match settings.get_string("kafka.key").as_deref() {
+            Ok("ip") | Err(_) => true,
+            Ok("none") => false,
+            Ok(other) => return Err(format!("Unknown kafka.key: {}", other).into()),
+        };
+        let mut client_config = ClientConfig::new();
+        client_config
+            .set("bootstrap.servers", settings.get_string("kafka.brokers")?)
+            .set("compression.type", settings.get_string("kafka.compression").unwrap_or_else(|_| "lz4".to_string()));
+        if let Ok(protocol) = settings.get_string("kafka.security_protocol") {
+            client_config.set("security.protocol", protocol);
+        }
+        if let Ok(mechanism) = settings.get_string("kafka.sasl_mechanism") {
+            client_config.set("sasl.mechanism", mechanism);
+        }
+        if let Ok(username) = settings.get_string("kafka.sasl_username") {
+            client_config.set("sasl.username", username);
+        }
+        if let Ok(password) = settings.get_string("kafka.sasl_password") {
+            client_config.set("sasl.password", password);
+        }
+        Ok(KafkaTransport {
+            topic: settings.get_string("kafka.topic").unwrap_or_else(|_| "muonfp".to_string()),
+            key_by_ip,
+            format: Format::text_from_settings(settings, "kafka")?,
+            producer: client_config.create()?,
+        })
+    }
+}
+
+impl Transport for KafkaTransport {
+    fn describe(&self) -> String {
+        format!("kafka topic {}", self.topic)
+    }
+
+    fn deliver(&mut self, batch: &[Value]) -> io::Result<()> {
+        for record in batch {
+            let payload = self.format.render(record);
+            let mut message = BaseRecord::<str, str>::to(&self.topic).payload(&payload);
+            if let Some(ip_address) = record["ip_address"].as_str().filter(|_| self.key_by_ip) {
+                message = message.key(ip_address);
+            }
+            // Wait for the local queue to drain rather than drop the record
+            while let Err((e, returned)) = self.producer.send(message) {
+                if !matches!(e, KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull)) {
+                    return Err(kafka_error(e));
+                }
+                self.producer.poll(Duration::from_millis(100));
+                message = returned;
+            }
+            self.producer.poll(Duration::ZERO);
+        }
+        self.producer.flush(KAFKA_FLUSH_TIMEOUT).map_err(kafka_error)
+    }
+}
+
+fn kafka_error(e: KafkaError) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, e.to_string())
+}
@@ -0,0 +1,156 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use config::Config;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use crate::format::Format;
+use super::Transport;
+
+const NATS_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Publishes records to a NATS subject. With JetStream every message is
+/// published with a reply inbox and the batch only counts as delivered once
+/// the stream has acknowledged each one; plain NATS waits for a PONG.
+pub struct NatsTransport {
+    address: String,
+    subject: String,
+    jetstream: bool,
+    credentials: Value,
+    format: Format,
+    // Reply subject prefix for JetStream acks, unique to the connection so
+    // sensors sharing a server never see each other's acks
+    inbox: String,
+    connection: Option<BufReader<TcpStream>>,
+}
+
+impl NatsTransport {
+    pub fn from_settings(settings: &Config) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut credentials = json!({});
+        if let Ok(token) = settings.get_string("nats.token") {
+            credentials["auth_token"] = json!(token);
+        }
+        if let Ok(user) = settings.get_string("nats.user") {
+            credentials["user"] = json!(user);
+            credentials["pass"] = json!(settings.get_string("nats.password").unwrap_or_default());
+        }
+        let address = settings.get_string("nats.address")?;
+        Ok(NatsTransport {
+            address: address.trim_start_matches("nats://").to_string(),
+            subject: settings.get_string("nats.subject").unwrap_or_else(|_| "muonfp".to_string()),
+            jetstream: settings.get_bool("nats.jetstream").unwrap_or(false),
+            credentials,
+            format: Format::text_from_settings(settings, "nats")?,
+            inbox: String::new(),
+            connection: None,
+        })
+    }
+
+    fn connect(&self) -> io::Result<BufReader<TcpStream>> {
+        let stream = TcpStream::connect(&self.address)?;
+        stream.set_read_timeout(Some(NATS_TIMEOUT))?;
+        stream.set_write_timeout(Some(NATS_TIMEOUT))?;
+        let mut connection = BufReader::new(stream);
+        let mut info = String::new();
+        connection.read_line(&mut info)?;
+        if !info.starts_with("INFO") {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not a NATS server"));
+        }
+        let mut options = json!({
+            "verbose": false,
+            "pedantic": false,
+            "name": "muonfp",
+            "lang": "rust",
+            "version": env!("CARGO_PKG_VERSION"),
+            "protocol": 1,
+        });
+        if let (Some(options), Some(credentials)) = (options.as_object_mut(), self.credentials.as_object()) {
+            options.extend(credentials.clone());
+        }
+        let mut setup = format!("CONNECT {}\r\n", options);
+        if self.jetstream {
+            setup.push_str(&format!("SUB {}.* 1\r\n", self.inbox));
+        }
+        connection.get_mut().write_all(setup.as_bytes())?;
+        Ok(connection)
+    }
+
+    fn send_batch(&mut self, batch: &[Value]) -> io::Result<()> {
+        if self.connection.is_none() {
+            self.inbox = new_inbox();
+            self.connection = Some(self.connect()?);
+        }
+        let mut pipeline = Vec::new();
+        for (index, record) in batch.iter().enumerate() {
+            let payload = self.format.render(record);
+            let header = if self.jetstream {
+                format!("PUB {} {}.{} {}\r\n", self.subject, self.inbox, index, payload.len())
+            } else {
+                format!("PUB {} {}\r\n", self.subject, payload.len())
+            };
+            pipeline.extend_from_slice(header.as_bytes());
+            pipeline.extend_from_slice(payload.as_bytes());
+            pipeline.extend_from_slice(b"\r\n");
+        }
+        if !self.jetstream {
+            pipeline.extend_from_slice(b"PING\r\n");
+        }
+        let connection = self.connection.as_mut().expect("connected above");
+        connection.get_mut().write_all(&pipeline)?;
+
+        let mut pending_acks = if self.jetstream { batch.len() } else { 0 };
+        let mut awaiting_pong = !self.jetstream;
+        while pending_acks > 0 || awaiting_pong {
+            let mut line = String::new();
+            if connection.read_line(&mut line)? == 0 {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed by NATS"));
+            }
+            let line = line.trim_end();
+            if line == "PING" {
+                connection.get_mut().write_all(b"PONG\r\n")?;
+            } else if line == "PONG" {
+                awaiting_pong = false;
+            } else if let Some(error) = line.strip_prefix("-ERR") {
+                return Err(io::Error::new(io::ErrorKind::Other, error.trim().to_string()));
+            } else if line.starts_with("MSG") {
+                // MSG <subject> <sid> [reply-to] <size>
+                let size: usize = line.rsplit(' ').next().and_then(|size| size.parse().ok())
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "bad MSG line"))?;
+                let mut payload = vec![0; size + 2];
+                connection.read_exact(&mut payload)?;
+                let subject = line.split(' ').nth(1).unwrap_or_default();
+                if !subject.strip_prefix(self.inbox.as_str()).is_some_and(|rest| rest.starts_with('.')) {
+                    continue;
+                }
+                let ack: Value = serde_json::from_slice(&payload[..size]).unwrap_or(Value::Null);
+                if let Some(error) = ack.get("error") {
+                    return Err(io::Error::new(io::ErrorKind::Other, format!("JetStream rejected a record: {}", error)));
+                }
+                pending_acks -= 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+// _INBOX.<32 hex digits>, from the host, process and time of the connection
+fn new_inbox() -> String {
+    let host = hostname::get().map(|host| host.to_string_lossy().into_owned()).unwrap_or_default();
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_nanos());
+    let id = Sha256::digest(format!("{}{}{}", host, std::process::id(), nanos).as_bytes());
+    format!("_INBOX.{}", hex::encode(&id[..16]))
+}
+
+impl Transport for NatsTransport {
+    fn describe(&self) -> String {
+        format!("nats subject {} at {}", self.subject, self.address)
+    }
+
+    fn deliver(&mut self, batch: &[Value]) -> io::Result<()> {
+        let result = self.send_batch(batch);
+        if result.is_err() {
+            self.connection = None;
+        }
+        result
+    }
+}
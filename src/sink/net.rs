@@ -0,0 +1,166 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, Write};
+use std::net::{TcpStream, UdpSocket};
+use std::time::{Duration, Instant};
+use config::Config;
+use log::{info, warn};
+use serde_json::Value;
+use crate::format::Format;
+use super::Transport;
+
+const NET_TIMEOUT: Duration = Duration::from_secs(10);
+const NET_MIN_BACKOFF: Duration = Duration::from_secs(1);
+
+enum Connection {
+    Tcp(TcpStream),
+    Udp(UdpSocket),
+}
+
+/// Forwards records as JSON lines over TCP, or one datagram per record over
+/// UDP. While a TCP collector is down, reconnects back off exponentially and
+/// records are appended to an optional spool file, replayed once it is back.
+pub struct NetTransport {
+    address: String,
+    udp: bool,
+    format: Format,
+    spool: Option<String>,
+    spool_max_size: u64,
+    max_backoff: Duration,
+    backoff: Duration,
+    retry_at: Option<Instant>,
+    spool_full: bool,
+    connection: Option<Connection>,
+}
+
+impl NetTransport {
+    pub fn from_settings(settings: &Config) -> Result<Self, Box<dyn std::error::Error>> {
+        let address = settings.get_string("net.address")?;
+        let (address, udp) = if let Some(address) = address.strip_prefix("tcp://") {
+            (address.to_string(), false)
+        } else if let Some(address) = address.strip_prefix("udp://") {
+            (address.to_string(), true)
+        } else {
+            return Err(format!("net.address must start with tcp:// or udp://: {}", address).into());
+        };
+        // UDP can't tell whether the collector is up, so there is nothing to spool for
+        let spool = settings.get_string("net.spool").ok();
+        if udp && spool.is_some() {
+            return Err("net.spool needs a tcp:// net.address".into());
+        }
+        Ok(NetTransport {
+            address,
+            udp,
+            format: Format::from_settings(settings, "net")?,
+            spool,
+            spool_max_size: settings.get_int("net.spool_max_size").unwrap_or(100) as u64 * 1024 * 1024,
+            max_backoff: Duration::from_secs(settings.get_int("net.max_backoff").unwrap_or(60).max(1) as u64),
+            backoff: NET_MIN_BACKOFF,
+            retry_at: None,
+            spool_full: false,
+            connection: None,
+        })
+    }
+
+    fn connect(&self) -> io::Result<Connection> {
+        if self.udp {
+            let socket = UdpSocket::bind(if self.address.starts_with('[') { "[::]:0" } else { "0.0.0.0:0" })?;
+            socket.connect(&self.address)?;
+            return Ok(Connection::Udp(socket));
+        }
+        let stream = TcpStream::connect(&self.address)?;
+        stream.set_write_timeout(Some(NET_TIMEOUT))?;
+        Ok(Connection::Tcp(stream))
+    }
+
+    fn send(&mut self, batch: &[Value]) -> io::Result<()> {
+        if self.connection.is_none() {
+            self.connection = Some(self.connect()?);
+            self.replay_spool()?;
+        }
+        match self.connection.as_mut().expect("connected above") {
+            Connection::Tcp(stream) => stream.write_all(&lines(batch, &self.format)),
+            Connection::Udp(socket) => batch.iter().try_for_each(|record| {
+                // One record per datagram, so text needs no line ending
+                let datagram = if self.format.is_binary() { self.format.encode(record) } else { self.format.render(record).into_bytes() };
+                socket.send(&datagram).map(drop)
+            }),
+        }
+    }
+
+    // Sends what was spooled during an outage ahead of new records. A replay
+    // cut short is repeated in full next time, so records may arrive twice.
+    fn replay_spool(&mut self) -> io::Result<()> {
+        let Some(path) = &self.spool else { return Ok(()) };
+        let Ok(file) = File::open(path) else { return Ok(()) };
+        if let Some(Connection::Tcp(stream)) = self.connection.as_mut() {
+            let replayed = io::copy(&mut BufReader::new(file), stream)?;
+            info!("Replayed {} spooled bytes to {}", replayed, self.address);
+        }
+        fs::remove_file(path)?;
+        self.spool_full = false;
+        Ok(())
+    }
+
+    fn write_spool(&mut self, path: &str, batch: &[Value]) -> io::Result<()> {
+        let lines = lines(batch, &self.format);
+        let size = fs::metadata(path).map_or(0, |metadata| metadata.len());
+        if size + lines.len() as u64 > self.spool_max_size {
+            if !self.spool_full {
+                warn!("Spool {} is full, dropping records for {}", path, self.address);
+                self.spool_full = true;
+            }
+            return Ok(());
+        }
+        OpenOptions::new().create(true).append(true).open(path)?.write_all(&lines)
+    }
+}
+
+impl Transport for NetTransport {
+    fn describe(&self) -> String {
+        format!("net {}://{}", if self.udp { "udp" } else { "tcp" }, self.address)
+    }
+
+    fn deliver(&mut self, batch: &[Value]) -> io::Result<()> {
+        let waiting = self.retry_at.is_some_and(|retry_at| Instant::now() < retry_at);
+        let result = if waiting {
+            Err(io::Error::new(io::ErrorKind::NotConnected, format!("reconnecting in {:?}", self.backoff)))
+        } else {
+            self.send(batch)
+        };
+        match result {
+            Ok(()) => {
+                if self.retry_at.take().is_some() {
+                    info!("Reconnected to {}", self.address);
+                }
+                self.backoff = NET_MIN_BACKOFF;
+                Ok(())
+            }
+            Err(e) => {
+                if !waiting {
+                    self.connection = None;
+                    if self.retry_at.is_some() {
+                        self.backoff = (self.backoff * 2).min(self.max_backoff);
+                    }
+                    self.retry_at = Some(Instant::now() + self.backoff);
+                }
+                match self.spool.clone() {
+                    Some(path) => {
+                        if !waiting {
+                            warn!("Cannot reach {} ({}), spooling to {}", self.address, e, path);
+                        }
+                        self.write_spool(&path, batch)
+                    }
+                    None => Err(e),
+                }
+            }
+        }
+    }
+}
+
+fn lines(batch: &[Value], format: &Format) -> Vec<u8> {
+    let mut lines = Vec::new();
+    for record in batch {
+        lines.extend_from_slice(&format.encode(record));
+    }
+    lines
+}
@@ -0,0 +1,212 @@
+use std::io;
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Utc};
+use config::Config;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use crate::stats::Stats;
+use super::{Background, Batching, Sink, Transport};
+
+const OTLP_SCOPE: &str = "muonfp";
+// Spans held between exports; more are not sampled
+const OTLP_MAX_SPANS: usize = 1000;
+
+/// Exports the capture counters as OTLP metrics and, optionally, one span per
+/// sampled record covering capture to write, over OTLP/HTTP with JSON bodies.
+pub struct OtlpSink {
+    exporter: Background,
+    resource: Value,
+    interval: Duration,
+    trace_every: u64,
+    started: DateTime<Utc>,
+    last_export: Instant,
+    capture: Option<[u64; 7]>,
+    fingerprints_seen: u64,
+    events_seen: u64,
+    spans: Vec<Value>,
+}
+
+impl OtlpSink {
+    pub fn from_settings(settings: &Config) -> Result<Self, Box<dyn std::error::Error>> {
+        let transport = OtlpTransport::from_settings(settings)?;
+        let hostname = hostname::get()?.to_string_lossy().into_owned();
+        Ok(OtlpSink {
+            exporter: Background::spawn(transport, Batching { size: 1, interval: Duration::from_secs(1) }),
+            resource: json!({
+                "attributes": [
+                    attribute("service.name", &settings.get_string("otlp.service_name").unwrap_or_else(|_| "muonfp".to_string())),
+                    attribute("host.name", &hostname),
+                ]
+            }),
+            interval: Duration::from_secs(settings.get_int("otlp.interval").unwrap_or(10).max(1) as u64),
+            trace_every: settings.get_int("otlp.trace_every").unwrap_or(0).max(0) as u64,
+            started: Utc::now(),
+            last_export: Instant::now(),
+            capture: None,
+            fingerprints_seen: 0,
+            events_seen: 0,
+            spans: Vec::new(),
+        })
+    }
+
+    // The span for a record runs from its timestamp, taken at capture, to now,
+    // after it has been written to the .out file.
+    fn span(&self, record: &Value) -> Option<Value> {
+        let start = DateTime::parse_from_rfc3339(record["timestamp"].as_str()?).ok()?;
+        let seed = format!("{}{}{}", record, self.fingerprints_seen, self.events_seen);
+        let id = Sha256::digest(seed.as_bytes());
+        let mut attributes = vec![attribute("muonfp.event_type", record["event_type"].as_str().unwrap_or("fingerprint"))];
+        if let Some(ip_address) = record["ip_address"].as_str() {
+            attributes.push(attribute("muonfp.ip_address", ip_address));
+        }
+        if let Some(fingerprint) = record["muonfp_fingerprint"].as_str() {
+            attributes.push(attribute("muonfp.fingerprint", fingerprint));
+        }
+        Some(json!({
+            "traceId": hex::encode(&id[..16]),
+            "spanId": hex::encode(&id[16..24]),
+            "name": "capture_to_write",
+            "kind": 1,
+            "startTimeUnixNano": nanos(start.with_timezone(&Utc)),
+            "endTimeUnixNano": nanos(Utc::now()),
+            "attributes": attributes,
+        }))
+    }
+
+    fn export(&mut self) {
+        let now = nanos(Utc::now());
+        let start = nanos(self.started);
+        let sum = |name: &str, value: u64| {
+            json!({
+                "name": format!("muonfp.{}", name),
+                "sum": {
+                    "aggregationTemporality": 2,
+                    "isMonotonic": true,
+                    "dataPoints": [{ "asInt": value.to_string(), "startTimeUnixNano": start, "timeUnixNano": now }],
+                },
+            })
+        };
+        let mut metrics = vec![sum("fingerprints_seen", self.fingerprints_seen), sum("events_seen", self.events_seen)];
+        if let Some([packets, bytes, fingerprints, events, errors, drops, uptime]) = self.capture {
+            metrics.extend([
+                sum("packets_captured", packets),
+                sum("bytes_captured", bytes),
+                sum("fingerprints_written", fingerprints),
+                sum("events_written", events),
+                sum("capture_errors", errors),
+                sum("kernel_drops", drops),
+            ]);
+            metrics.push(json!({
+                "name": "muonfp.uptime",
+                "unit": "s",
+                "gauge": { "dataPoints": [{ "asInt": uptime.to_string(), "timeUnixNano": now }] },
+            }));
+        }
+        self.exporter.send(&json!({
+            "resourceMetrics": [{
+                "resource": self.resource,
+                "scopeMetrics": [{ "scope": { "name": OTLP_SCOPE }, "metrics": metrics }],
+            }]
+        }));
+
+        if !self.spans.is_empty() {
+            let spans = std::mem::take(&mut self.spans);
+            self.exporter.send(&json!({
+                "resourceSpans": [{
+                    "resource": self.resource,
+                    "scopeSpans": [{ "scope": { "name": OTLP_SCOPE }, "spans": spans }],
+                }]
+            }));
+        }
+        self.last_export = Instant::now();
+    }
+}
+
+impl Sink for OtlpSink {
+    fn send(&mut self, record: &Value) {
+        if record.get("event_type").is_some() {
+            self.events_seen += 1;
+        } else {
+            self.fingerprints_seen += 1;
+        }
+        let seen = self.fingerprints_seen + self.events_seen;
+        if self.trace_every > 0 && seen % self.trace_every == 0 && self.spans.len() < OTLP_MAX_SPANS {
+            if let Some(span) = self.span(record) {
+                self.spans.push(span);
+            }
+        }
+        if self.last_export.elapsed() >= self.interval {
+            self.export();
+        }
+    }
+
+    fn update_stats(&mut self, stats: &Stats) {
+        self.capture = Some([
+            stats.packets_captured,
+            stats.bytes_captured,
+            stats.fingerprints_written,
+            stats.events_written,
+            stats.capture_errors,
+            stats.kernel_drops,
+            stats.uptime().as_secs(),
+        ]);
+        if self.last_export.elapsed() >= self.interval {
+            self.export();
+        }
+    }
+
+    fn queue_status(&self) -> Option<String> {
+        self.exporter.queue_status()
+    }
+
+    fn close(&mut self) {
+        self.export();
+        self.exporter.close();
+    }
+}
+
+/// Posts OTLP request bodies to `/v1/metrics` or `/v1/traces` by their signal.
+struct OtlpTransport {
+    endpoint: String,
+    headers: Vec<(String, String)>,
+}
+
+impl OtlpTransport {
+    fn from_settings(settings: &Config) -> Result<Self, Box<dyn std::error::Error>> {
+        let headers = settings
+            .get_string("otlp.headers")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|header| header.split_once('='))
+            .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+            .collect();
+        Ok(OtlpTransport { endpoint: settings.get_string("otlp.endpoint")?.trim_end_matches('/').to_string(), headers })
+    }
+}
+
+impl Transport for OtlpTransport {
+    fn describe(&self) -> String {
+        format!("otlp {}", self.endpoint)
+    }
+
+    fn deliver(&mut self, batch: &[Value]) -> io::Result<()> {
+        for body in batch {
+            let signal = if body.get("resourceSpans").is_some() { "traces" } else { "metrics" };
+            let mut request = ureq::post(&format!("{}/v1/{}", self.endpoint, signal)).set("Content-Type", "application/json");
+            for (name, value) in &self.headers {
+                request = request.set(name, value);
+            }
+            request.send_string(&body.to_string()).map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+        }
+        Ok(())
+    }
+}
+
+fn attribute(key: &str, value: &str) -> Value {
+    json!({ "key": key, "value": { "stringValue": value } })
+}
+
+// OTLP/JSON carries 64-bit integers as strings
+fn nanos(time: DateTime<Utc>) -> String {
+    time.timestamp_nanos_opt().unwrap_or_default().to_string()
+}
@@ -0,0 +1,167 @@
+use std::fs::{self, File};
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use arrow_array::builder::{Int32Builder, ListBuilder, StringBuilder, TimestampMicrosecondBuilder};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use chrono::DateTime;
+use config::Config;
+use log::{info, warn};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::{Compression, ZstdLevel};
+use parquet::file::properties::WriterProperties;
+use serde_json::Value;
+use super::Transport;
+
+// Nullable string columns taken as-is from the record
+const TEXT_COLUMNS: [&str; 8] = [
+    "hostname",
+    "sensor_id",
+    "interface",
+    "ip_address",
+    "muonfp_fingerprint",
+    "ja4t",
+    "os_guess",
+    "tool_guess",
+];
+const NUMBER_COLUMNS: [&str; 3] = ["src_port", "dst_port", "ttl"];
+
+/// Buffers records in memory and writes them out as one Parquet file per
+/// rotation, split into row groups. The common fields get typed columns,
+/// the whole record is kept as JSON in `record`.
+pub struct ParquetTransport {
+    dir: PathBuf,
+    rotate_interval: Duration,
+    max_records: usize,
+    row_group_size: usize,
+    records: Vec<Value>,
+    started: Instant,
+    file_count: u32,
+}
+
+impl ParquetTransport {
+    pub fn from_settings(settings: &Config) -> Result<Self, Box<dyn std::error::Error>> {
+        let dir = PathBuf::from(settings.get_string("parquet.dir")?);
+        if !dir.is_dir() {
+            return Err(format!("Parquet directory does not exist: {}", dir.display()).into());
+        }
+        Ok(ParquetTransport {
+            dir,
+            rotate_interval: Duration::from_secs(settings.get_int("parquet.rotate_interval").unwrap_or(3600).max(1) as u64),
+            max_records: settings.get_int("parquet.max_records").unwrap_or(1_000_000).max(1) as usize,
+            row_group_size: settings.get_int("parquet.row_group_size").unwrap_or(100_000).max(1) as usize,
+            records: Vec::new(),
+            started: Instant::now(),
+            file_count: 0,
+        })
+    }
+
+    // Writes the buffered records under a .part name, renamed once complete.
+    // A file that fails to write is given up on rather than retried.
+    fn rotate(&mut self) -> io::Result<()> {
+        self.started = Instant::now();
+        let records = std::mem::take(&mut self.records);
+        if records.is_empty() {
+            return Ok(());
+        }
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards").as_secs();
+        let name = format!("muonfp_{:010}_{:04}", timestamp, self.file_count);
+        self.file_count += 1;
+        let part_path = self.dir.join(format!("{}.part", name));
+        let path = self.dir.join(format!("{}.parquet", name));
+
+        let batch = record_batch(&records).map_err(parquet_error)?;
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::ZSTD(ZstdLevel::default()))
+            .set_max_row_group_size(self.row_group_size)
+            .build();
+        let mut writer = ArrowWriter::try_new(File::create(&part_path)?, batch.schema(), Some(properties)).map_err(parquet_error)?;
+        writer.write(&batch).map_err(parquet_error)?;
+        writer.close().map_err(parquet_error)?;
+        fs::rename(&part_path, &path)?;
+        info!("Wrote {} records to {}", records.len(), path.display());
+        Ok(())
+    }
+}
+
+impl Transport for ParquetTransport {
+    fn describe(&self) -> String {
+        format!("parquet:{}", self.dir.display())
+    }
+
+    fn deliver(&mut self, batch: &[Value]) -> io::Result<()> {
+        self.records.extend_from_slice(batch);
+        if self.records.len() >= self.max_records || self.started.elapsed() >= self.rotate_interval {
+            self.rotate()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for ParquetTransport {
+    fn drop(&mut self) {
+        if let Err(e) = self.rotate() {
+            warn!("Failed to write the last Parquet file to {}: {}", self.dir.display(), e);
+        }
+    }
+}
+
+fn schema() -> Schema {
+    let mut fields = vec![
+        Field::new("timestamp", DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())), true),
+        Field::new("event_type", DataType::Utf8, false),
+    ];
+    fields.extend(TEXT_COLUMNS.iter().map(|name| Field::new(*name, DataType::Utf8, true)));
+    fields.extend(NUMBER_COLUMNS.iter().map(|name| Field::new(*name, DataType::Int32, true)));
+    fields.push(Field::new("tags", DataType::List(Arc::new(Field::new("item", DataType::Utf8, true))), true));
+    fields.push(Field::new("record", DataType::Utf8, false));
+    Schema::new(fields)
+}
+
+fn record_batch(records: &[Value]) -> Result<RecordBatch, arrow_schema::ArrowError> {
+    let mut timestamps = TimestampMicrosecondBuilder::with_capacity(records.len()).with_timezone("UTC");
+    let mut event_types = StringBuilder::new();
+    let mut texts: Vec<StringBuilder> = TEXT_COLUMNS.iter().map(|_| StringBuilder::new()).collect();
+    let mut numbers: Vec<Int32Builder> = NUMBER_COLUMNS.iter().map(|_| Int32Builder::with_capacity(records.len())).collect();
+    let mut tags = ListBuilder::new(StringBuilder::new());
+    let mut jsons = StringBuilder::new();
+
+    for record in records {
+        timestamps.append_option(
+            record["timestamp"]
+                .as_str()
+                .and_then(|timestamp| DateTime::parse_from_rfc3339(timestamp).ok())
+                .map(|timestamp| timestamp.timestamp_micros()),
+        );
+        event_types.append_value(record["event_type"].as_str().unwrap_or("fingerprint"));
+        for (builder, name) in texts.iter_mut().zip(TEXT_COLUMNS) {
+            builder.append_option(record[name].as_str());
+        }
+        for (builder, name) in numbers.iter_mut().zip(NUMBER_COLUMNS) {
+            builder.append_option(record[name].as_i64().and_then(|number| i32::try_from(number).ok()));
+        }
+        match record["tags"].as_array() {
+            Some(values) => {
+                for value in values {
+                    tags.values().append_option(value.as_str());
+                }
+                tags.append(true);
+            }
+            None => tags.append(false),
+        }
+        jsons.append_value(record.to_string());
+    }
+
+    let mut columns: Vec<ArrayRef> = vec![Arc::new(timestamps.finish()), Arc::new(event_types.finish())];
+    columns.extend(texts.iter_mut().map(|builder| Arc::new(builder.finish()) as ArrayRef));
+    columns.extend(numbers.iter_mut().map(|builder| Arc::new(builder.finish()) as ArrayRef));
+    columns.push(Arc::new(tags.finish()));
+    columns.push(Arc::new(jsons.finish()));
+    RecordBatch::try_new(Arc::new(schema()), columns)
+}
+
+fn parquet_error(e: impl std::fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, e.to_string())
+}
@@ -0,0 +1,82 @@
+use std::io;
+use config::Config;
+use postgres::{Client, NoTls};
+use serde_json::Value;
+use super::Transport;
+
+/// Keeps one row per (IP, fingerprint) in a PostgreSQL table, with first and
+/// last sighting, a sighting count and the latest full record.
+pub struct PostgresTransport {
+    url: String,
+    table: String,
+    client: Option<Client>,
+}
+
+impl PostgresTransport {
+    pub fn from_settings(settings: &Config) -> Result<Self, Box<dyn std::error::Error>> {
+        let table = settings.get_string("postgres.table").unwrap_or_else(|_| "muonfp_fingerprints".to_string());
+        if table.is_empty() || !table.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(format!("Invalid postgres.table: {}", table).into());
+        }
+        Ok(PostgresTransport { url: settings.get_string("postgres.url")?, table, client: None })
+    }
+
+    // Connects on first use and again after the connection is lost, so the
+    // sensor starts even while the database is down.
+    fn client(&mut self) -> Result<&mut Client, postgres::Error> {
+        if self.client.as_ref().map_or(true, Client::is_closed) {
+            let mut client = Client::connect(&self.url, NoTls)?;
+            client.batch_execute(&format!(
+                "CREATE TABLE IF NOT EXISTS {} (
+                    ip_address inet NOT NULL,
+                    muonfp_fingerprint text NOT NULL,
+                    first_seen timestamptz NOT NULL,
+                    last_seen timestamptz NOT NULL,
+                    count bigint NOT NULL DEFAULT 1,
+                    record jsonb NOT NULL,
+                    PRIMARY KEY (ip_address, muonfp_fingerprint)
+                )",
+                self.table
+            ))?;
+            self.client = Some(client);
+        }
+        Ok(self.client.as_mut().expect("connected above"))
+    }
+}
+
+impl Transport for PostgresTransport {
+    fn describe(&self) -> String {
+        format!("postgres table {}", self.table)
+    }
+
+    fn deliver(&mut self, batch: &[Value]) -> io::Result<()> {
+        let upsert = format!(
+            "INSERT INTO {table} (ip_address, muonfp_fingerprint, first_seen, last_seen, record)
+             VALUES ($1::text::inet, $2, $3::text::timestamptz, $3::text::timestamptz, $4::text::jsonb)
+             ON CONFLICT (ip_address, muonfp_fingerprint) DO UPDATE
+             SET last_seen = EXCLUDED.last_seen, count = {table}.count + 1, record = EXCLUDED.record",
+            table = self.table
+        );
+        let client = self.client().map_err(postgres_error)?;
+        let mut transaction = client.transaction().map_err(postgres_error)?;
+        let statement = transaction.prepare(&upsert).map_err(postgres_error)?;
+        // Events have no fingerprint of their own and are left out
+        for record in batch.iter().filter(|record| record.get("event_type").is_none()) {
+            let (Some(ip_address), Some(fingerprint), Some(timestamp)) = (
+                record["ip_address"].as_str(),
+                record["muonfp_fingerprint"].as_str(),
+                record["timestamp"].as_str(),
+            ) else {
+                continue;
+            };
+            transaction
+                .execute(&statement, &[&ip_address, &fingerprint, &timestamp, &record.to_string()])
+                .map_err(postgres_error)?;
+        }
+        transaction.commit().map_err(postgres_error)
+    }
+}
+
+fn postgres_error(e: postgres::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, e.to_string())
+}
@@ -0,0 +1,145 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+use config::Config;
+use serde_json::Value;
+use crate::format::Format;
+use super::Transport;
+
+const REDIS_TIMEOUT: Duration = Duration::from_secs(10);
+
+enum Target {
+    /// PUBLISH to a pub/sub channel
+    Channel(String),
+    /// XADD to a stream, trimmed to about `max_length` entries
+    Stream { key: String, max_length: Option<u64> },
+}
+
+/// Publishes records to a Redis channel or appends them to a Redis Stream,
+/// speaking RESP directly and pipelining each batch.
+pub struct RedisTransport {
+    address: String,
+    password: Option<String>,
+    database: Option<i64>,
+    target: Target,
+    format: Format,
+    connection: Option<BufReader<TcpStream>>,
+}
+
+impl RedisTransport {
+    pub fn from_settings(settings: &Config) -> Result<Self, Box<dyn std::error::Error>> {
+        let target = match (settings.get_string("redis.channel"), settings.get_string("redis.stream")) {
+            (Ok(channel), Err(_)) => Target::Channel(channel),
+            (Err(_), Ok(key)) => Target::Stream {
+                key,
+                max_length: settings.get_int("redis.max_length").ok().map(|length| length as u64),
+            },
+            _ => return Err("redis needs exactly one of channel or stream".into()),
+        };
+        Ok(RedisTransport {
+            address: settings.get_string("redis.address")?,
+            password: settings.get_string("redis.password").ok(),
+            database: settings.get_int("redis.database").ok(),
+            target,
+            format: Format::text_from_settings(settings, "redis")?,
+            connection: None,
+        })
+    }
+
+    fn connect(&self) -> io::Result<BufReader<TcpStream>> {
+        let stream = TcpStream::connect(&self.address)?;
+        stream.set_read_timeout(Some(REDIS_TIMEOUT))?;
+        stream.set_write_timeout(Some(REDIS_TIMEOUT))?;
+        let mut connection = BufReader::new(stream);
+        let mut setup = Vec::new();
+        if let Some(password) = &self.password {
+            setup.push(command(&["AUTH", password]));
+        }
+        if let Some(database) = self.database {
+            setup.push(command(&["SELECT", &database.to_string()]));
+        }
+        for request in &setup {
+            connection.get_mut().write_all(request)?;
+        }
+        for _ in &setup {
+            read_reply(&mut connection)?;
+        }
+        Ok(connection)
+    }
+
+    fn send_batch(&mut self, batch: &[Value]) -> io::Result<()> {
+        if self.connection.is_none() {
+            self.connection = Some(self.connect()?);
+        }
+        let mut pipeline = Vec::new();
+        for record in batch {
+            let json = self.format.render(record);
+            let request = match &self.target {
+                Target::Channel(channel) => command(&["PUBLISH", channel, &json]),
+                Target::Stream { key, max_length: Some(max_length) } => {
+                    command(&["XADD", key, "MAXLEN", "~", &max_length.to_string(), "*", "record", &json])
+                }
+                Target::Stream { key, max_length: None } => command(&["XADD", key, "*", "record", &json]),
+            };
+            pipeline.extend_from_slice(&request);
+        }
+        let connection = self.connection.as_mut().expect("connected above");
+        connection.get_mut().write_all(&pipeline)?;
+        for _ in batch {
+            read_reply(connection)?;
+        }
+        Ok(())
+    }
+}
+
+impl Transport for RedisTransport {
+    fn describe(&self) -> String {
+        match &self.target {
+            Target::Channel(channel) => format!("redis channel {} at {}", channel, self.address),
+            Target::Stream { key, .. } => format!("redis stream {} at {}", key, self.address),
+        }
+    }
+
+    fn deliver(&mut self, batch: &[Value]) -> io::Result<()> {
+        let result = self.send_batch(batch);
+        // Reconnect for the next batch instead of reading stale replies
+        if result.is_err() {
+            self.connection = None;
+        }
+        result
+    }
+}
+
+// Encodes a command as a RESP array of bulk strings.
+fn command(arguments: &[&str]) -> Vec<u8> {
+    let mut encoded = format!("*{}\r\n", arguments.len()).into_bytes();
+    for argument in arguments {
+        encoded.extend_from_slice(format!("${}\r\n", argument.len()).as_bytes());
+        encoded.extend_from_slice(argument.as_bytes());
+        encoded.extend_from_slice(b"\r\n");
+    }
+    encoded
+}
+
+// Reads one reply, failing on error replies. Only the simple, integer and
+// bulk string replies these commands return are expected.
+fn read_reply(connection: &mut BufReader<TcpStream>) -> io::Result<()> {
+    let mut line = String::new();
+    if connection.read_line(&mut line)? == 0 {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed by redis"));
+    }
+    let line = line.trim_end();
+    match line.chars().next() {
+        Some('-') => Err(io::Error::new(io::ErrorKind::Other, line[1..].to_string())),
+        Some('$') => {
+            let length: i64 = line[1..].parse().map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "bad bulk length"))?;
+            if length >= 0 {
+                let mut data = vec![0; length as usize + 2];
+                io::Read::read_exact(connection, &mut data)?;
+            }
+            Ok(())
+        }
+        Some('+') | Some(':') => Ok(()),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData, format!("unexpected reply: {}", line))),
+    }
+}
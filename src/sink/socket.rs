@@ -0,0 +1,88 @@
+use std::fs;
+use std::io::{self, Write};
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::time::Duration;
+use config::Config;
+use log::{debug, info};
+use serde_json::Value;
+use crate::format::Format;
+use super::Transport;
+
+// A client that can't take a batch within this long is disconnected
+const SOCKET_CLIENT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Streams one line per record (JSON unless `format` says otherwise) to
+/// every client connected to a unix socket. Clients only see records sent
+/// after they connect.
+pub struct SocketTransport {
+    path: String,
+    listener: UnixListener,
+    format: Format,
+    clients: Vec<UnixStream>,
+}
+
+impl SocketTransport {
+    pub fn from_settings(settings: &Config) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = settings.get_string("socket.path")?;
+        // A socket left behind by an earlier run would make bind fail; anything
+        // else at the path is more likely a typo than ours to delete
+        match fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.file_type().is_socket() => fs::remove_file(&path)?,
+            Ok(_) => return Err(format!("socket.path {} exists and is not a socket", path).into()),
+            Err(_) => {}
+        }
+        let listener = UnixListener::bind(&path)?;
+        listener.set_nonblocking(true)?;
+        if let Ok(mode) = settings.get_string("socket.mode") {
+            let mode = u32::from_str_radix(&mode, 8).map_err(|_| format!("socket.mode {} is not an octal mode", mode))?;
+            fs::set_permissions(&path, fs::Permissions::from_mode(mode))?;
+        }
+        info!("Streaming records on {}", path);
+        Ok(SocketTransport { path, listener, format: Format::from_settings(settings, "socket")?, clients: Vec::new() })
+    }
+
+    fn accept_clients(&mut self) -> io::Result<()> {
+        loop {
+            match self.listener.accept() {
+                Ok((client, _)) => {
+                    client.set_write_timeout(Some(SOCKET_CLIENT_TIMEOUT))?;
+                    self.clients.push(client);
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl Transport for SocketTransport {
+    fn describe(&self) -> String {
+        format!("socket {}", self.path)
+    }
+
+    fn deliver(&mut self, batch: &[Value]) -> io::Result<()> {
+        self.accept_clients()?;
+        if self.clients.is_empty() {
+            return Ok(());
+        }
+        let mut lines = Vec::new();
+        for record in batch {
+            lines.extend_from_slice(&self.format.encode(record));
+        }
+        self.clients.retain_mut(|client| match client.write_all(&lines) {
+            Ok(()) => true,
+            Err(e) => {
+                debug!("Dropping socket client: {}", e);
+                false
+            }
+        });
+        Ok(())
+    }
+}
+
+impl Drop for SocketTransport {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
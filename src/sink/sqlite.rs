@@ -0,0 +1,65 @@
+use std::io;
+use config::Config;
+use rusqlite::{params, Connection};
+use serde_json::Value;
+use super::Transport;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS records (
+        timestamp TEXT NOT NULL,
+        event_type TEXT NOT NULL,
+        ip_address TEXT,
+        muonfp_fingerprint TEXT,
+        record TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS records_ip_address ON records (ip_address);
+    CREATE INDEX IF NOT EXISTS records_fingerprint ON records (muonfp_fingerprint);
+";
+
+/// Inserts records into a local SQLite database. The common fields get
+/// their own columns, everything else is reachable with `json_extract(record, ...)`.
+pub struct SqliteTransport {
+    path: String,
+    connection: Connection,
+}
+
+impl SqliteTransport {
+    pub fn from_settings(settings: &Config) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = settings.get_string("sqlite.path")?;
+        let connection = Connection::open(&path)?;
+        // WAL lets analysts query the database while the sensor writes to it
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        connection.pragma_update(None, "synchronous", "NORMAL")?;
+        connection.execute_batch(SCHEMA)?;
+        Ok(SqliteTransport { path, connection })
+    }
+}
+
+impl Transport for SqliteTransport {
+    fn describe(&self) -> String {
+        format!("sqlite:{}", self.path)
+    }
+
+    fn deliver(&mut self, batch: &[Value]) -> io::Result<()> {
+        let transaction = self.connection.transaction().map_err(sqlite_error)?;
+        {
+            let mut insert = transaction
+                .prepare_cached("INSERT INTO records (timestamp, event_type, ip_address, muonfp_fingerprint, record) VALUES (?1, ?2, ?3, ?4, ?5)")
+                .map_err(sqlite_error)?;
+            for record in batch {
+                insert.execute(params![
+                    record["timestamp"].as_str().unwrap_or_default(),
+                    record["event_type"].as_str().unwrap_or("fingerprint"),
+                    record["ip_address"].as_str(),
+                    record["muonfp_fingerprint"].as_str(),
+                    record.to_string(),
+                ]).map_err(sqlite_error)?;
+            }
+        }
+        transaction.commit().map_err(sqlite_error)
+    }
+}
+
+fn sqlite_error(e: rusqlite::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, e.to_string())
+}
@@ -0,0 +1,118 @@
+use std::net::UdpSocket;
+use std::time::{Duration, Instant};
+use config::Config;
+use log::info;
+use serde_json::Value;
+use crate::stats::Stats;
+use super::Sink;
+
+// Keeps each datagram below a typical MTU
+const STATSD_MAX_DATAGRAM: usize = 1400;
+
+/// Pushes the capture counters and record counts to a StatsD or DogStatsD
+/// server over UDP. Counters are sent as deltas since the last push.
+pub struct StatsdSink {
+    socket: UdpSocket,
+    prefix: String,
+    /// DogStatsD `|#tag,tag` suffix, empty for plain StatsD
+    tags: String,
+    interval: Duration,
+    last_push: Instant,
+    fingerprints_seen: u64,
+    events_seen: u64,
+    last_stats: [u64; 6],
+}
+
+impl StatsdSink {
+    pub fn from_settings(settings: &Config) -> Result<Self, Box<dyn std::error::Error>> {
+        let address = settings.get_string("statsd.address")?;
+        let socket = UdpSocket::bind(if address.starts_with('[') { "[::]:0" } else { "0.0.0.0:0" })?;
+        socket.connect(&address)?;
+        socket.set_nonblocking(true)?;
+        let tags = match settings.get_string("statsd.tags") {
+            Ok(tags) if !tags.is_empty() => format!("|#{}", tags),
+            _ => String::new(),
+        };
+        info!("Sending metrics to statsd {}", address);
+        Ok(StatsdSink {
+            socket,
+            prefix: settings.get_string("statsd.prefix").unwrap_or_else(|_| "muonfp".to_string()),
+            tags,
+            interval: Duration::from_secs(settings.get_int("statsd.interval").unwrap_or(10).max(1) as u64),
+            last_push: Instant::now(),
+            fingerprints_seen: 0,
+            events_seen: 0,
+            last_stats: [0; 6],
+        })
+    }
+
+    fn metric(&self, name: &str, value: u64, kind: &str) -> String {
+        format!("{}.{}:{}|{}{}", self.prefix, name, value, kind, self.tags)
+    }
+
+    // Packs metrics into as few datagrams as fit; lost datagrams are fine for StatsD
+    fn push(&self, metrics: &[String]) {
+        let mut datagram = String::new();
+        for metric in metrics {
+            if !datagram.is_empty() && datagram.len() + metric.len() + 1 > STATSD_MAX_DATAGRAM {
+                let _ = self.socket.send(datagram.as_bytes());
+                datagram.clear();
+            }
+            if !datagram.is_empty() {
+                datagram.push('\n');
+            }
+            datagram.push_str(metric);
+        }
+        if !datagram.is_empty() {
+            let _ = self.socket.send(datagram.as_bytes());
+        }
+    }
+
+    fn push_record_counts(&mut self) {
+        let metrics = [
+            self.metric("fingerprints_seen", self.fingerprints_seen, "c"),
+            self.metric("events_seen", self.events_seen, "c"),
+        ];
+        self.push(&metrics);
+        self.fingerprints_seen = 0;
+        self.events_seen = 0;
+        self.last_push = Instant::now();
+    }
+}
+
+impl Sink for StatsdSink {
+    fn send(&mut self, record: &Value) {
+        if record.get("event_type").is_some() {
+            self.events_seen += 1;
+        } else {
+            self.fingerprints_seen += 1;
+        }
+        if self.last_push.elapsed() >= self.interval {
+            self.push_record_counts();
+        }
+    }
+
+    fn update_stats(&mut self, stats: &Stats) {
+        let current = [
+            stats.packets_captured,
+            stats.bytes_captured,
+            stats.fingerprints_written,
+            stats.events_written,
+            stats.capture_errors,
+            stats.kernel_drops,
+        ];
+        let names = ["packets_captured", "bytes_captured", "fingerprints_written", "events_written", "capture_errors", "kernel_drops"];
+        let mut metrics: Vec<String> = names
+            .iter()
+            .zip(current.iter().zip(self.last_stats))
+            .map(|(name, (current, last))| self.metric(name, current.saturating_sub(last), "c"))
+            .collect();
+        metrics.push(self.metric("uptime_seconds", stats.uptime().as_secs(), "g"));
+        self.push(&metrics);
+        self.last_stats = current;
+    }
+
+    fn close(&mut self) {
+        self.push_record_counts();
+    }
+}
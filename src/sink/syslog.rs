@@ -0,0 +1,216 @@
+use std::fs::File;
+use std::io::{self, BufReader, Write};
+use std::net::{TcpStream, UdpSocket};
+use std::os::unix::net::UnixDatagram;
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::Utc;
+use config::Config;
+use rustls::pki_types::ServerName;
+use rustls::{ClientConfig, ClientConnection, RootCertStore, StreamOwned};
+use serde_json::Value;
+use crate::format::Format;
+use super::Transport;
+
+const SYSLOG_TIMEOUT: Duration = Duration::from_secs(10);
+const SEVERITY_INFORMATIONAL: u8 = 6;
+const FACILITIES: [&str; 24] = [
+    "kern", "user", "mail", "daemon", "auth", "syslog", "lpr", "news", "uucp", "cron", "authpriv", "ftp",
+    "ntp", "security", "console", "solaris-cron", "local0", "local1", "local2", "local3", "local4", "local5",
+    "local6", "local7",
+];
+
+enum Destination {
+    Udp(String),
+    /// Octet-counted frames (RFC 6587) over a stream
+    Tcp(String),
+    /// Octet-counted frames over TLS (RFC 5425)
+    Tls { address: String, server_name: ServerName<'static>, config: Arc<ClientConfig> },
+    Unix(String),
+}
+
+enum Connection {
+    Datagram(UdpSocket),
+    Stream(Box<dyn Write + Send>),
+    Unix(UnixDatagram),
+}
+
+/// Sends every record as an RFC 5424 message, carrying the JSON either as
+/// the message body or in a structured-data element.
+pub struct SyslogTransport {
+    destination: Destination,
+    facility: u8,
+    app_name: String,
+    sd_id: String,
+    structured: bool,
+    format: Format,
+    connection: Option<Connection>,
+}
+
+impl SyslogTransport {
+    pub fn from_settings(settings: &Config) -> Result<Self, Box<dyn std::error::Error>> {
+        let address = settings.get_string("syslog.address")?;
+        let destination = if let Some(address) = address.strip_prefix("udp://") {
+            Destination::Udp(address.to_string())
+        } else if let Some(address) = address.strip_prefix("tcp://") {
+            Destination::Tcp(address.to_string())
+        } else if let Some(address) = address.strip_prefix("tls://") {
+            let host = address.rsplit_once(':').map_or(address, |(host, _)| host).trim_matches(['[', ']']);
+            let server_name = settings.get_string("syslog.server_name").unwrap_or_else(|_| host.to_string());
+            Destination::Tls {
+                address: address.to_string(),
+                server_name: ServerName::try_from(server_name)?,
+                config: tls_config(settings)?,
+            }
+        } else if let Some(path) = address.strip_prefix("unix://") {
+            Destination::Unix(path.to_string())
+        } else {
+            return Err(format!("syslog.address must start with udp://, tcp://, tls:// or unix://: {}", address).into());
+        };
+        let facility_name = settings.get_string("syslog.facility").unwrap_or_else(|_| "local0".to_string());
+        let facility = FACILITIES
+            .iter()
+            .position(|name| *name == facility_name)
+            .ok_or_else(|| format!("Unknown syslog.facility: {}", facility_name))?;
+        let structured = match settings.get_string("syslog.json").as_deref() {
+            Ok("message") | Err(_) => false,
+            Ok("structured") => true,
+            Ok(other) => return Err(format!("Unknown syslog.json: {}", other).into()),
+        };
+        let format = Format::text_from_settings(settings, "syslog")?;
+        if structured && !format.is_json() {
+            return Err("syslog.json=structured needs the json format".into());
+        }
+        Ok(SyslogTransport {
+            destination,
+            facility: facility as u8,
+            app_name: settings.get_string("syslog.app_name").unwrap_or_else(|_| "muonfp".to_string()),
+            sd_id: format!("muonfp@{}", settings.get_int("syslog.enterprise").unwrap_or(32473)),
+            structured,
+            format,
+            connection: None,
+        })
+    }
+
+    fn connect(&self) -> io::Result<Connection> {
+        match &self.destination {
+            Destination::Udp(address) => {
+                let socket = UdpSocket::bind("0.0.0.0:0")?;
+                socket.connect(address)?;
+                Ok(Connection::Datagram(socket))
+            }
+            Destination::Tcp(address) => {
+                let stream = TcpStream::connect(address)?;
+                stream.set_write_timeout(Some(SYSLOG_TIMEOUT))?;
+                Ok(Connection::Stream(Box::new(stream)))
+            }
+            Destination::Tls { address, server_name, config } => {
+                let stream = TcpStream::connect(address)?;
+                stream.set_read_timeout(Some(SYSLOG_TIMEOUT))?;
+                stream.set_write_timeout(Some(SYSLOG_TIMEOUT))?;
+                let session = ClientConnection::new(Arc::clone(config), server_name.clone())
+                    .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+                Ok(Connection::Stream(Box::new(StreamOwned::new(session, stream))))
+            }
+            Destination::Unix(path) => {
+                let socket = UnixDatagram::unbound()?;
+                socket.connect(path)?;
+                Ok(Connection::Unix(socket))
+            }
+        }
+    }
+
+    // <PRI>1 TIMESTAMP HOSTNAME APP-NAME PROCID MSGID STRUCTURED-DATA MSG
+    fn format(&self, record: &Value) -> String {
+        let priority = self.facility * 8 + SEVERITY_INFORMATIONAL;
+        let timestamp = record["timestamp"].as_str().map_or_else(|| Utc::now().to_rfc3339(), str::to_string);
+        let hostname = record["hostname"].as_str().unwrap_or("-");
+        let message_id = record["event_type"].as_str().unwrap_or("fingerprint");
+        let message = self.format.render(record);
+        let mut parameters = String::new();
+        if let Some(ip_address) = record["ip_address"].as_str() {
+            parameters.push_str(&format!(" ip=\"{}\"", escape_parameter(ip_address)));
+        }
+        if let Some(fingerprint) = record["muonfp_fingerprint"].as_str() {
+            parameters.push_str(&format!(" fp=\"{}\"", escape_parameter(fingerprint)));
+        }
+        let header = format!("<{}>1 {} {} {} {} {}", priority, timestamp, hostname, self.app_name, std::process::id(), message_id);
+        if self.structured {
+            format!("{} [{}{} json=\"{}\"]", header, self.sd_id, parameters, escape_parameter(&message))
+        } else if parameters.is_empty() {
+            format!("{} - {}", header, message)
+        } else {
+            format!("{} [{}{}] {}", header, self.sd_id, parameters, message)
+        }
+    }
+}
+
+impl Transport for SyslogTransport {
+    fn describe(&self) -> String {
+        match &self.destination {
+            Destination::Udp(address) => format!("syslog udp://{}", address),
+            Destination::Tcp(address) => format!("syslog tcp://{}", address),
+            Destination::Tls { address, .. } => format!("syslog tls://{}", address),
+            Destination::Unix(path) => format!("syslog unix://{}", path),
+        }
+    }
+
+    fn deliver(&mut self, batch: &[Value]) -> io::Result<()> {
+        if self.connection.is_none() {
+            self.connection = Some(self.connect()?);
+        }
+        let messages: Vec<String> = batch.iter().map(|record| self.format(record)).collect();
+        let connection = self.connection.as_mut().expect("connected above");
+        let result = messages.iter().try_for_each(|message| match connection {
+            Connection::Datagram(socket) => socket.send(message.as_bytes()).map(drop),
+            Connection::Unix(socket) => socket.send(message.as_bytes()).map(drop),
+            Connection::Stream(stream) => write!(stream, "{} {}", message.len(), message),
+        });
+        let result = result.and_then(|()| match connection {
+            Connection::Stream(stream) => stream.flush(),
+            _ => Ok(()),
+        });
+        if result.is_err() {
+            self.connection = None;
+        }
+        result
+    }
+}
+
+// Trusts `syslog.ca` (PEM) or the bundled web PKI roots, and presents
+// `syslog.cert` and `syslog.key` for mutual authentication when both are set.
+fn tls_config(settings: &Config) -> Result<Arc<ClientConfig>, Box<dyn std::error::Error>> {
+    let mut roots = RootCertStore::empty();
+    match settings.get_string("syslog.ca") {
+        Ok(path) => {
+            for certificate in rustls_pemfile::certs(&mut BufReader::new(File::open(&path)?)) {
+                roots.add(certificate?)?;
+            }
+        }
+        Err(_) => roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned()),
+    }
+    let builder = ClientConfig::builder().with_root_certificates(roots);
+    let config = match (settings.get_string("syslog.cert"), settings.get_string("syslog.key")) {
+        (Ok(cert), Ok(key)) => {
+            let chain = rustls_pemfile::certs(&mut BufReader::new(File::open(&cert)?)).collect::<Result<Vec<_>, _>>()?;
+            let key = rustls_pemfile::private_key(&mut BufReader::new(File::open(&key)?))?
+                .ok_or_else(|| format!("No private key in {}", key))?;
+            builder.with_client_auth_cert(chain, key)?
+        }
+        (Err(_), Err(_)) => builder.with_no_client_auth(),
+        _ => return Err("syslog.cert and syslog.key must be set together".into()),
+    };
+    Ok(Arc::new(config))
+}
+
+// Inside PARAM-VALUE, '"', '\' and ']' must be escaped with a backslash.
+fn escape_parameter(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '"' | '\\' | ']') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
@@ -0,0 +1,137 @@
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use config::Config;
+use log::{debug, info};
+use serde_json::Value;
+use sha1::{Digest, Sha1};
+use crate::format::Format;
+use super::Transport;
+
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+const WEBSOCKET_TIMEOUT: Duration = Duration::from_secs(5);
+// Longest upgrade request accepted
+const WEBSOCKET_MAX_REQUEST: usize = 8192;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_CLOSE: u8 = 0x8;
+
+/// Pushes every record as a text message to the WebSocket clients connected
+/// at the time, e.g. browsers showing a live wall display. Nothing is read
+/// from clients after the handshake.
+pub struct WebsocketTransport {
+    listen: String,
+    format: Format,
+    clients: Arc<Mutex<Vec<TcpStream>>>,
+}
+
+impl WebsocketTransport {
+    pub fn from_settings(settings: &Config) -> Result<Self, Box<dyn std::error::Error>> {
+        let listen = settings.get_string("websocket.listen")?;
+        let path = settings.get_string("websocket.path").ok();
+        let listener = TcpListener::bind(&listen)?;
+        let clients = Arc::new(Mutex::new(Vec::new()));
+        let accepted = Arc::clone(&clients);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                match handshake(&stream, path.as_deref()) {
+                    Ok(()) => accepted.lock().unwrap().push(stream),
+                    Err(e) => debug!("Rejected WebSocket client: {}", e),
+                }
+            }
+        });
+        info!("Serving records over WebSocket on {}", listen);
+        Ok(WebsocketTransport { listen, format: Format::text_from_settings(settings, "websocket")?, clients })
+    }
+}
+
+impl Transport for WebsocketTransport {
+    fn describe(&self) -> String {
+        format!("websocket {}", self.listen)
+    }
+
+    fn deliver(&mut self, batch: &[Value]) -> io::Result<()> {
+        let mut clients = self.clients.lock().unwrap();
+        if clients.is_empty() {
+            return Ok(());
+        }
+        let mut frames = Vec::new();
+        for record in batch {
+            frames.extend_from_slice(&frame(OPCODE_TEXT, self.format.render(record).as_bytes()));
+        }
+        clients.retain_mut(|client| match client.write_all(&frames) {
+            Ok(()) => true,
+            Err(e) => {
+                debug!("Dropping WebSocket client: {}", e);
+                false
+            }
+        });
+        Ok(())
+    }
+}
+
+impl Drop for WebsocketTransport {
+    fn drop(&mut self) {
+        for client in self.clients.lock().unwrap().iter_mut() {
+            let _ = client.write_all(&frame(OPCODE_CLOSE, &[]));
+        }
+    }
+}
+
+// Answers the HTTP upgrade request (RFC 6455 section 4.2)
+fn handshake(mut stream: &TcpStream, path: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    stream.set_read_timeout(Some(WEBSOCKET_TIMEOUT))?;
+    stream.set_write_timeout(Some(WEBSOCKET_TIMEOUT))?;
+    let mut request = Vec::new();
+    let mut buffer = [0; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        let read = stream.read(&mut buffer)?;
+        if read == 0 || request.len() + read > WEBSOCKET_MAX_REQUEST {
+            return Err("incomplete upgrade request".into());
+        }
+        request.extend_from_slice(&buffer[..read]);
+    }
+    let request = String::from_utf8_lossy(&request);
+    let mut lines = request.split("\r\n");
+    let request_path = lines.next().and_then(|line| line.strip_prefix("GET ")).and_then(|line| line.split(' ').next());
+    if path.is_some_and(|path| request_path != Some(path)) {
+        stream.write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n")?;
+        return Err(format!("unknown path {}", request_path.unwrap_or("-")).into());
+    }
+    let key = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("sec-websocket-key"))
+        .map(|(_, value)| value.trim());
+    let Some(key) = key else {
+        stream.write_all(b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n")?;
+        return Err("not a WebSocket upgrade".into());
+    };
+    let accept = BASE64.encode(Sha1::digest(format!("{}{}", key, WEBSOCKET_GUID).as_bytes()));
+    write!(
+        stream,
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept
+    )?;
+    Ok(())
+}
+
+// A single unmasked, final frame, as servers send them
+fn frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        length @ 0..=125 => frame.push(length as u8),
+        length @ 126..=0xffff => {
+            frame.push(126);
+            frame.extend_from_slice(&(length as u16).to_be_bytes());
+        }
+        length => {
+            frame.push(127);
+            frame.extend_from_slice(&(length as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    frame
+}
@@ -0,0 +1,52 @@
+use std::io;
+use config::Config;
+use serde_json::Value;
+use crate::format::Format;
+use super::Transport;
+
+/// Streams records from a ZeroMQ PUB socket. Subscribers that can't keep up
+/// lose messages at the socket's high-water mark, as usual for PUB.
+pub struct ZeromqTransport {
+    endpoint: String,
+    topic: Option<String>,
+    format: Format,
+    // The context must outlive the socket
+    _context: zmq::Context,
+    socket: zmq::Socket,
+}
+
+impl ZeromqTransport {
+    pub fn from_settings(settings: &Config) -> Result<Self, Box<dyn std::error::Error>> {
+        let endpoint = settings.get_string("zeromq.bind")?;
+        let context = zmq::Context::new();
+        let socket = context.socket(zmq::PUB)?;
+        socket.set_sndhwm(settings.get_int("zeromq.high_water_mark").unwrap_or(10000) as i32)?;
+        socket.bind(&endpoint)?;
+        Ok(ZeromqTransport {
+            endpoint,
+            topic: settings.get_string("zeromq.topic").ok(),
+            format: Format::text_from_settings(settings, "zeromq")?,
+            _context: context,
+            socket,
+        })
+    }
+}
+
+impl Transport for ZeromqTransport {
+    fn describe(&self) -> String {
+        format!("zeromq {}", self.endpoint)
+    }
+
+    fn deliver(&mut self, batch: &[Value]) -> io::Result<()> {
+        for record in batch {
+            let message = self.format.render(record);
+            // A topic goes out as its own frame so subscribers can filter on it
+            let result = match &self.topic {
+                Some(topic) => self.socket.send_multipart([topic.as_bytes(), message.as_bytes()], 0),
+                None => self.socket.send(message.as_bytes(), 0),
+            };
+            result.map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+use md5::{Digest, Md5};
+use serde::Serialize;
+use crate::fingerprint::Connection;
+
+const SSH_MSG_KEXINIT: u8 = 20;
+// Connections whose banner was seen but whose KEXINIT is still pending
+const SSH_MAX_PENDING: usize = 65536;
+
+/// Body of an `ssh` event.
+#[derive(Serialize)]
+pub struct SshHandshake {
+    /// `client` or `server`
+    pub role: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub banner: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hassh: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hassh_server: Option<String>,
+    /// The `kex;encryption;mac;compression` string that was hashed
+    pub hassh_algorithms: String,
+}
+
+/// Follows the first segments of SSH connections to pair each side's
+/// version banner with the algorithm lists of its KEXINIT.
+#[derive(Default)]
+pub struct SshTracker {
+    banners: HashMap<Connection, String>,
+}
+
+impl SshTracker {
+    pub fn new() -> Self {
+        SshTracker { banners: HashMap::new() }
+    }
+
+    /// Inspects one segment sent over `connection`; `is_client` tells which
+    /// side of the connection sent it.
+    pub fn observe(&mut self, connection: Connection, payload: &[u8], is_client: bool) -> Option<SshHandshake> {
+        let mut payload = payload;
+        if payload.starts_with(b"SSH-") {
+            let end = payload.windows(2).position(|pair| pair == b"\r\n")
+                .or_else(|| payload.iter().position(|&byte| byte == b'\n'))?;
+            let banner = String::from_utf8_lossy(&payload[..end]).into_owned();
+            if self.banners.len() >= SSH_MAX_PENDING {
+                self.banners.clear();
+            }
+            self.banners.insert(connection, banner);
+            payload = &payload[end..];
+            payload = payload.strip_prefix(b"\r\n").or_else(|| payload.strip_prefix(b"\n")).unwrap_or(payload);
+        }
+
+        let lists = kexinit_name_lists(payload)?;
+        let algorithms = if is_client {
+            [&lists[0], &lists[2], &lists[4], &lists[6]]
+        } else {
+            [&lists[0], &lists[3], &lists[5], &lists[7]]
+        };
+        let hassh_algorithms = algorithms.map(String::as_str).join(";");
+        let hash = hex::encode(Md5::digest(hassh_algorithms.as_bytes()));
+
+        Some(SshHandshake {
+            role: if is_client { "client" } else { "server" },
+            banner: self.banners.remove(&connection),
+            hassh: if is_client { Some(hash.clone()) } else { None },
+            hassh_server: if is_client { None } else { Some(hash) },
+            hassh_algorithms,
+        })
+    }
+}
+
+// Returns the first eight name-lists of an unencrypted SSH_MSG_KEXINIT
+// packet: kex, host key, then encryption, MAC and compression for each
+// direction. A KEXINIT often spans several segments, so only the bytes
+// present are read and it fails only when a name-list is cut short.
+fn kexinit_name_lists(packet: &[u8]) -> Option<Vec<String>> {
+    let packet_length = u32::from_be_bytes(packet.get(..4)?.try_into().ok()?) as usize;
+    let end = packet_length.saturating_add(4).min(packet.len());
+    let body = packet.get(5..end)?;
+    if body.first() != Some(&SSH_MSG_KEXINIT) {
+        return None;
+    }
+    let mut data = body.get(17..)?; // message type and cookie
+    let mut lists = Vec::with_capacity(8);
+    for _ in 0..8 {
+        let length = u32::from_be_bytes(data.get(..4)?.try_into().ok()?) as usize;
+        let list = data.get(4..4 + length)?;
+        lists.push(String::from_utf8_lossy(list).into_owned());
+        data = &data[4 + length..];
+    }
+    Some(lists)
+}
@@ -0,0 +1,112 @@
+use std::time::{Duration, Instant};
+use serde_json::{json, Value};
+
+pub struct Stats {
+    started: Instant,
+    // Where the current interval of `interval_summary` began
+    interval_started: Instant,
+    interval_packets: u64,
+    interval_fingerprints: u64,
+    interval_kernel_drops: u64,
+    pub packets_captured: u64,
+    pub bytes_captured: u64,
+    pub fingerprints_written: u64,
+    pub events_written: u64,
+    pub capture_errors: u64,
+    /// Dropped by the kernel before capture could read them
+    pub kernel_drops: u64,
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Stats {
+            started: Instant::now(),
+            interval_started: Instant::now(),
+            interval_packets: 0,
+            interval_fingerprints: 0,
+            interval_kernel_drops: 0,
+            packets_captured: 0,
+            bytes_captured: 0,
+            fingerprints_written: 0,
+            events_written: 0,
+            capture_errors: 0,
+            kernel_drops: 0,
+        }
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "uptime {}s, {} packets ({} bytes) captured, {} dropped by the kernel, {} fingerprints and {} events written, {} capture errors",
+            self.uptime().as_secs(),
+            self.packets_captured,
+            self.bytes_captured,
+            self.kernel_drops,
+            self.fingerprints_written,
+            self.events_written,
+            self.capture_errors
+        )
+    }
+
+    /// Rates and drops since the previous call, then starts a new interval.
+    pub fn interval_summary(&mut self) -> String {
+        let seconds = self.interval_started.elapsed().as_secs_f64().max(1.0);
+        let summary = format!(
+            "{:.0} packets/s, {:.2} fingerprints/s, {} dropped by the kernel",
+            (self.packets_captured - self.interval_packets) as f64 / seconds,
+            (self.fingerprints_written - self.interval_fingerprints) as f64 / seconds,
+            self.kernel_drops - self.interval_kernel_drops
+        );
+        self.interval_kernel_drops = self.kernel_drops;
+        self.interval_started = Instant::now();
+        self.interval_packets = self.packets_captured;
+        self.interval_fingerprints = self.fingerprints_written;
+        summary
+    }
+
+    /// Sets the counters to `base` plus the `to_value()` reports of capture
+    /// children; uptime and the current interval carry on.
+    pub fn set_counters<'a>(&mut self, base: &Stats, reports: impl IntoIterator<Item = &'a Value>) {
+        self.packets_captured = base.packets_captured;
+        self.bytes_captured = base.bytes_captured;
+        self.fingerprints_written = base.fingerprints_written;
+        self.events_written = base.events_written;
+        self.capture_errors = base.capture_errors;
+        self.kernel_drops = base.kernel_drops;
+        for report in reports {
+            self.add(report);
+        }
+    }
+
+    /// Adds the counters of a `to_value()` report.
+    pub fn add(&mut self, report: &Value) {
+        let counter = |name: &str| report[name].as_u64().unwrap_or(0);
+        self.packets_captured += counter("packets_captured");
+        self.bytes_captured += counter("bytes_captured");
+        self.fingerprints_written += counter("fingerprints_written");
+        self.events_written += counter("events_written");
+        self.capture_errors += counter("capture_errors");
+        self.kernel_drops += counter("kernel_drops");
+    }
+
+    pub fn to_value(&self) -> Value {
+        json!({
+            "uptime_seconds": self.uptime().as_secs(),
+            "packets_captured": self.packets_captured,
+            "bytes_captured": self.bytes_captured,
+            "fingerprints_written": self.fingerprints_written,
+            "events_written": self.events_written,
+            "capture_errors": self.capture_errors,
+            "kernel_drops": self.kernel_drops,
+        })
+    }
+}
@@ -0,0 +1,293 @@
+use std::collections::HashMap;
+use std::env;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use log::{info, warn, error};
+use serde_json::{json, Value};
+use signal_hook::consts::{SIGUSR1, SIGUSR2};
+use crate::format::Format;
+use crate::output::FingerprintOutput;
+use crate::sink::Sinks;
+use crate::signals::{self, Signals};
+use crate::stats::Stats;
+
+// Start of the lines children report their capture counters on, which are
+// kept out of the merged output
+const CHILD_STATS_PREFIX: &str = "{\"event_type\":\"child_stats\",";
+
+/// The line a capture child reports its counters to the supervisor with.
+pub fn child_stats_line(interface: &str, stats: &Stats) -> String {
+    format!("{}\"interface\":{},\"stats\":{}}}", CHILD_STATS_PREFIX, json!(interface), stats.to_value())
+}
+
+// One capture child process bound to a single interface.
+struct Worker {
+    interface: String,
+    child: Option<Child>,
+    reader: Option<JoinHandle<()>>,
+    restart_at: Option<Instant>,
+}
+
+/// Runs one `muonfp --child` process per interface, restarts children that
+/// exit unexpectedly and merges their fingerprint streams into one output.
+pub struct Supervisor {
+    workers: Vec<Worker>,
+    restart_delay: Duration,
+    lines_tx: Sender<String>,
+    lines_rx: Receiver<String>,
+    // Capture counters summed over the children
+    stats: Stats,
+    // Latest report of each interface's current child, and the last reports
+    // of children that have since been restarted
+    child_stats: HashMap<String, Value>,
+    restarted_stats: Stats,
+}
+
+impl Supervisor {
+    pub fn new(interfaces: &[String], restart_delay: Duration) -> Self {
+        let (lines_tx, lines_rx) = mpsc::channel();
+        let workers = interfaces
+            .iter()
+            .map(|interface| Worker {
+                interface: interface.clone(),
+                child: None,
+                reader: None,
+                restart_at: Some(Instant::now()),
+            })
+            .collect();
+        Supervisor {
+            workers,
+            restart_delay,
+            lines_tx,
+            lines_rx,
+            stats: Stats::new(),
+            child_stats: HashMap::new(),
+            restarted_stats: Stats::new(),
+        }
+    }
+
+    /// Capture counters reported by the children so far.
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /// Supervises the children until shutdown is requested, writing every line
+    /// they emit to `output`, rendered in `format`, and to `sinks`. Returns the
+    /// number of lines merged.
+    pub fn run(
+        &mut self,
+        output: &mut Option<FingerprintOutput>,
+        format: &Format,
+        sinks: &mut Sinks,
+        signals: &Signals,
+        flush_interval: Duration,
+        stats_interval: Option<Duration>,
+    ) -> io::Result<u64> {
+        let mut lines_written = 0;
+        let mut last_flush = Instant::now();
+        let mut last_summary = Instant::now();
+        let mut summary_lines = 0;
+
+        while signals.running() {
+            self.check_workers();
+            sinks.heartbeat();
+
+            if signals.take_rotate_request() {
+                info!("Rotating output files on request");
+                if let Some(output) = output.as_mut() {
+                    output.rotate()?;
+                }
+                self.signal_workers(SIGUSR2);
+            }
+
+            // Each child also logs its own statistics
+            if signals.take_dump_request() {
+                info!("Statistics: {}; {} records merged; outputs: {}", self.stats.summary(), lines_written, sinks.queue_status());
+                self.signal_workers(SIGUSR1);
+            }
+
+            match self.lines_rx.recv_timeout(Duration::from_secs(1)) {
+                Ok(line) if line.starts_with(CHILD_STATS_PREFIX) => {
+                    self.merge_child_stats(&line);
+                    sinks.update_stats(&self.stats);
+                }
+                Ok(line) => {
+                    if let Some(output) = output.as_mut() {
+                        write_line(output, format, &line)?;
+                    }
+                    sinks.send_line(&line);
+                    lines_written += 1;
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+
+            if last_flush.elapsed() >= flush_interval {
+                if let Some(output) = output.as_mut() {
+                    output.flush()?;
+                }
+                last_flush = Instant::now();
+            }
+
+            if stats_interval.is_some_and(|interval| last_summary.elapsed() >= interval) {
+                info!(
+                    "Stats: {}; {} records merged in the last {}s; outputs: {}",
+                    self.stats.interval_summary(),
+                    lines_written - summary_lines,
+                    last_summary.elapsed().as_secs(),
+                    sinks.queue_status()
+                );
+                last_summary = Instant::now();
+                summary_lines = lines_written;
+            }
+        }
+
+        self.stop_workers();
+        while let Ok(line) = self.lines_rx.try_recv() {
+            if line.starts_with(CHILD_STATS_PREFIX) {
+                self.merge_child_stats(&line);
+                continue;
+            }
+            if let Some(output) = output.as_mut() {
+                write_line(output, format, &line)?;
+            }
+            sinks.send_line(&line);
+            lines_written += 1;
+        }
+        if let Some(output) = output.as_mut() {
+            output.flush()?;
+        }
+
+        Ok(lines_written)
+    }
+
+    // Takes a child's cumulative counters. A report with a shorter uptime than
+    // the last one comes from a restarted child, whose predecessor's final
+    // counts are kept.
+    fn merge_child_stats(&mut self, line: &str) {
+        let report: Value = match serde_json::from_str(line) {
+            Ok(report) => report,
+            Err(e) => {
+                warn!("Skipping malformed statistics from a capture child: {}", e);
+                return;
+            }
+        };
+        let interface = report["interface"].as_str().unwrap_or_default().to_string();
+        let stats = report["stats"].clone();
+        if let Some(previous) = self.child_stats.get(&interface) {
+            if stats["uptime_seconds"].as_u64() < previous["uptime_seconds"].as_u64() {
+                self.restarted_stats.add(previous);
+            }
+        }
+        self.child_stats.insert(interface, stats);
+        self.stats.set_counters(&self.restarted_stats, self.child_stats.values());
+    }
+
+    // Reaps exited children and (re)starts any whose restart time has come.
+    fn check_workers(&mut self) {
+        let restart_delay = self.restart_delay;
+        for i in 0..self.workers.len() {
+            if let Some(child) = self.workers[i].child.as_mut() {
+                match child.try_wait() {
+                    Ok(Some(status)) => {
+                        let worker = &mut self.workers[i];
+                        warn!("Capture on {} exited with {}, restarting in {}s", worker.interface, status, restart_delay.as_secs());
+                        worker.child = None;
+                        worker.reader = None;
+                        worker.restart_at = Some(Instant::now() + restart_delay);
+                    }
+                    Ok(None) => {}
+                    Err(e) => warn!("Failed to poll capture on {}: {}", self.workers[i].interface, e),
+                }
+            }
+
+            let due = matches!(self.workers[i].restart_at, Some(at) if Instant::now() >= at);
+            if due {
+                let interface = self.workers[i].interface.clone();
+                match self.spawn(&interface) {
+                    Ok((child, reader)) => {
+                        info!("Started capture on {} (pid {})", interface, child.id());
+                        let worker = &mut self.workers[i];
+                        worker.child = Some(child);
+                        worker.reader = Some(reader);
+                        worker.restart_at = None;
+                    }
+                    Err(e) => {
+                        error!("Failed to start capture on {}: {}", interface, e);
+                        self.workers[i].restart_at = Some(Instant::now() + restart_delay);
+                    }
+                }
+            }
+        }
+    }
+
+    fn spawn(&self, interface: &str) -> io::Result<(Child, JoinHandle<()>)> {
+        let mut child = Command::new(env::current_exe()?)
+            .arg("--child")
+            .arg("--interface")
+            .arg(interface)
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdout = child.stdout.take().expect("child stdout is piped");
+        let lines_tx = self.lines_tx.clone();
+        let interface = interface.to_string();
+        let reader = thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                match line {
+                    Ok(line) => {
+                        if lines_tx.send(line).is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        warn!("Failed to read output of capture on {}: {}", interface, e);
+                        break;
+                    }
+                }
+            }
+        });
+        Ok((child, reader))
+    }
+
+    fn signal_workers(&self, signal: i32) {
+        for worker in &self.workers {
+            if let Some(child) = worker.child.as_ref() {
+                if let Err(e) = signals::forward(child.id(), signal) {
+                    warn!("Failed to signal capture on {}: {}", worker.interface, e);
+                }
+            }
+        }
+    }
+
+    // Asks every child to shut down gracefully and waits for its output to drain.
+    fn stop_workers(&mut self) {
+        self.signal_workers(libc::SIGTERM);
+        for worker in &mut self.workers {
+            if let Some(mut child) = worker.child.take() {
+                if let Err(e) = child.wait() {
+                    warn!("Failed to wait for capture on {}: {}", worker.interface, e);
+                }
+            }
+            if let Some(reader) = worker.reader.take() {
+                let _ = reader.join();
+            }
+        }
+    }
+}
+
+// Children emit JSON; other formats are rendered from the parsed record
+fn write_line(output: &mut FingerprintOutput, format: &Format, line: &str) -> io::Result<()> {
+    if format.is_json() {
+        return writeln!(output, "{}", line);
+    }
+    match serde_json::from_str(line) {
+        Ok(record) => output.write_all(&format.encode(&record)),
+        Err(e) => {
+            warn!("Skipping malformed record from a capture child: {}", e);
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use sha2::{Digest, Sha256};
+
+const HANDSHAKE_CLIENT_HELLO: u8 = 1;
+const EXTENSION_SERVER_NAME: u16 = 0x0000;
+const EXTENSION_SIGNATURE_ALGORITHMS: u16 = 0x000d;
+const EXTENSION_ALPN: u16 = 0x0010;
+const EXTENSION_SUPPORTED_VERSIONS: u16 = 0x002b;
+
+/// The parts of a TLS ClientHello that identify the client.
+#[derive(Default)]
+pub struct ClientHello {
+    pub legacy_version: u16,
+    pub cipher_suites: Vec<u16>,
+    pub extensions: Vec<u16>,
+    pub server_name: Option<String>,
+    pub alpn: Vec<String>,
+    pub supported_versions: Vec<u16>,
+    pub signature_algorithms: Vec<u16>,
+    /// Whether the whole message was present, so the extension list is too
+    pub complete: bool,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, length: usize) -> Option<&'a [u8]> {
+        if self.data.len() < length {
+            return None;
+        }
+        let (bytes, rest) = self.data.split_at(length);
+        self.data = rest;
+        Some(bytes)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        let bytes = self.bytes(2)?;
+        Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u16_list(data: &[u8]) -> Vec<u16> {
+        data.chunks_exact(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect()
+    }
+}
+
+/// GREASE values (RFC 8701) are random placeholders and left out of fingerprints.
+pub fn is_grease(value: u16) -> bool {
+    value & 0x0f0f == 0x0a0a && value >> 8 == value & 0xff
+}
+
+/// Parses a ClientHello handshake message. A truncated message (e.g. the
+/// first part of a ClientHello split across QUIC packets) yields whatever
+/// could be read, as long as the cipher suites are complete, with
+/// `complete` left unset.
+pub fn parse_client_hello(message: &[u8]) -> Option<ClientHello> {
+    let mut reader = Reader { data: message };
+    if reader.u8()? != HANDSHAKE_CLIENT_HELLO {
+        return None;
+    }
+    let length = reader.bytes(3)?;
+    let length = u32::from_be_bytes([0, length[0], length[1], length[2]]) as usize;
+    let complete = reader.data.len() >= length;
+    reader.data = &reader.data[..length.min(reader.data.len())];
+    let mut hello = ClientHello { legacy_version: reader.u16()?, ..Default::default() };
+    reader.bytes(32)?; // random
+    let session_id_length = reader.u8()? as usize;
+    reader.bytes(session_id_length)?;
+    let cipher_suites_length = reader.u16()? as usize;
+    hello.cipher_suites = Reader::u16_list(reader.bytes(cipher_suites_length)?);
+
+    let compression_length = reader.u8().unwrap_or(0) as usize;
+    // Extensions length; a hello cut short before it still has its ciphers
+    if reader.bytes(compression_length).and_then(|_| reader.u16()).is_none() {
+        return Some(hello);
+    }
+
+    while let Some(extension_type) = reader.u16() {
+        let Some(length) = reader.u16() else { break };
+        hello.extensions.push(extension_type);
+        let Some(data) = reader.bytes(length as usize) else { break };
+        let mut extension = Reader { data };
+        match extension_type {
+            EXTENSION_SERVER_NAME => {
+                // server_name_list length and name type precede the host name
+                let name = extension
+                    .bytes(3)
+                    .and_then(|_| extension.u16())
+                    .and_then(|length| extension.bytes(length as usize));
+                hello.server_name = name.map(|name| String::from_utf8_lossy(name).into_owned());
+            }
+            EXTENSION_ALPN => {
+                if extension.u16().is_some() {
+                    while let Some(length) = extension.u8() {
+                        let Some(protocol) = extension.bytes(length as usize) else { break };
+                        hello.alpn.push(String::from_utf8_lossy(protocol).into_owned());
+                    }
+                }
+            }
+            EXTENSION_SUPPORTED_VERSIONS => {
+                if let Some(length) = extension.u8() {
+                    hello.supported_versions = Reader::u16_list(extension.bytes(length as usize).unwrap_or(&[]));
+                }
+            }
+            EXTENSION_SIGNATURE_ALGORITHMS => {
+                if let Some(length) = extension.u16() {
+                    hello.signature_algorithms = Reader::u16_list(extension.bytes(length as usize).unwrap_or(&[]));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    hello.complete = complete;
+    Some(hello)
+}
+
+/// Renders the FoxIO JA4 fingerprint. `transport` is `t` for TCP or `q` for QUIC.
+pub fn ja4(hello: &ClientHello, transport: char) -> String {
+    let version = hello
+        .supported_versions
+        .iter()
+        .copied()
+        .filter(|&version| !is_grease(version))
+        .max()
+        .unwrap_or(hello.legacy_version);
+    let version = match version {
+        0x0304 => "13",
+        0x0303 => "12",
+        0x0302 => "11",
+        0x0301 => "10",
+        0x0300 => "s3",
+        _ => "00",
+    };
+
+    let ciphers: Vec<u16> = hello.cipher_suites.iter().copied().filter(|&cipher| !is_grease(cipher)).collect();
+    let extensions: Vec<u16> = hello.extensions.iter().copied().filter(|&extension| !is_grease(extension)).collect();
+    let alpn = match hello.alpn.first().map(|alpn| alpn.as_bytes()) {
+        Some([first, .., last]) => format!("{}{}", *first as char, *last as char),
+        Some([only]) => format!("{}{}", *only as char, *only as char),
+        _ => "00".to_string(),
+    };
+
+    let mut sorted_ciphers = ciphers.clone();
+    sorted_ciphers.sort_unstable();
+    let mut sorted_extensions: Vec<u16> = extensions
+        .iter()
+        .copied()
+        .filter(|&extension| extension != EXTENSION_SERVER_NAME && extension != EXTENSION_ALPN)
+        .collect();
+    sorted_extensions.sort_unstable();
+    let mut extension_input = hex_list(&sorted_extensions);
+    if !hello.signature_algorithms.is_empty() {
+        extension_input = format!("{}_{}", extension_input, hex_list(&hello.signature_algorithms));
+    }
+
+    format!(
+        "{}{}{}{:02}{:02}{}_{}_{}",
+        transport,
+        version,
+        if hello.server_name.is_some() { 'd' } else { 'i' },
+        ciphers.len().min(99),
+        extensions.len().min(99),
+        alpn,
+        truncated_hash(&hex_list(&sorted_ciphers), sorted_ciphers.is_empty()),
+        truncated_hash(&extension_input, sorted_extensions.is_empty())
+    )
+}
+
+fn hex_list(values: &[u16]) -> String {
+    values.iter().map(|value| format!("{:04x}", value)).collect::<Vec<_>>().join(",")
+}
+
+fn truncated_hash(input: &str, empty: bool) -> String {
+    if empty {
+        return "000000000000".to_string();
+    }
+    hex::encode(Sha256::digest(input.as_bytes()))[..12].to_string()
+}
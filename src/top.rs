@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use ratatui::crossterm::event::{self, Event, KeyCode};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::widgets::{Block, Borders, Paragraph, Row, Table};
+use ratatui::Frame;
+use serde_json::Value;
+
+const TOP_REFRESH: Duration = Duration::from_secs(2);
+// Recent fingerprints the top lists are counted over
+const TOP_WINDOW: usize = 1000;
+const TOP_ROWS: usize = 20;
+
+#[derive(Default)]
+struct View {
+    error: Option<String>,
+    fingerprints_seen: u64,
+    packets_per_second: Option<f64>,
+    drops_per_second: Option<f64>,
+    top_fingerprints: Vec<(String, String, usize)>,
+    top_ips: Vec<(String, usize)>,
+    // Capture counters and when they were read, for the rates
+    previous: Option<(Instant, u64, u64)>,
+}
+
+/// `muonfp top`: a live view of a running sensor, read from its API.
+pub fn run(url: &str, token: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let url = url.trim_end_matches('/');
+    let mut view = View::default();
+    let mut terminal = ratatui::init();
+    let mut last_refresh: Option<Instant> = None;
+    let result = loop {
+        if !last_refresh.is_some_and(|last_refresh| last_refresh.elapsed() < TOP_REFRESH) {
+            if let Err(e) = view.refresh(url, token) {
+                view.error = Some(e.to_string());
+            }
+            last_refresh = Some(Instant::now());
+        }
+        if let Err(e) = terminal.draw(|frame| draw(frame, url, &view)) {
+            break Err(e.into());
+        }
+        match event::poll(Duration::from_millis(250)) {
+            Ok(true) => match event::read() {
+                Ok(Event::Key(key)) if matches!(key.code, KeyCode::Char('q') | KeyCode::Esc) => break Ok(()),
+                Ok(_) => {}
+                Err(e) => break Err(e.into()),
+            },
+            Ok(false) => {}
+            Err(e) => break Err(e.into()),
+        }
+    };
+    ratatui::restore();
+    result
+}
+
+fn get(url: &str, token: Option<&str>) -> Result<Value, Box<dyn std::error::Error>> {
+    let mut request = ureq::get(url).timeout(Duration::from_secs(5));
+    if let Some(token) = token {
+        request = request.set("Authorization", &format!("Bearer {}", token));
+    }
+    Ok(serde_json::from_str(&request.call()?.into_string()?)?)
+}
+
+impl View {
+    fn refresh(&mut self, url: &str, token: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+        let stats = get(&format!("{}/api/stats", url), token)?;
+        let recent = get(&format!("{}/api/fingerprints/recent?limit={}", url, TOP_WINDOW), token)?;
+        self.error = None;
+        self.fingerprints_seen = stats["fingerprints_seen"].as_u64().unwrap_or_default();
+
+        // Capture counters exist only when the sensor captures in-process
+        let capture = &stats["capture"];
+        if let (Some(packets), Some(drops)) = (capture["packets_captured"].as_u64(), capture["kernel_drops"].as_u64()) {
+            let now = Instant::now();
+            if let Some((then, previous_packets, previous_drops)) = self.previous {
+                let seconds = now.duration_since(then).as_secs_f64().max(0.001);
+                self.packets_per_second = Some(packets.saturating_sub(previous_packets) as f64 / seconds);
+                self.drops_per_second = Some(drops.saturating_sub(previous_drops) as f64 / seconds);
+            }
+            self.previous = Some((now, packets, drops));
+        }
+
+        let mut fingerprints: HashMap<(String, String), usize> = HashMap::new();
+        let mut ips: HashMap<String, usize> = HashMap::new();
+        for record in recent.as_array().into_iter().flatten() {
+            let fingerprint = record["muonfp_fingerprint"].as_str().unwrap_or("-").to_string();
+            let guess = record["os_guess"].as_str().or(record["tool_guess"].as_str()).unwrap_or("").to_string();
+            *fingerprints.entry((fingerprint, guess)).or_default() += 1;
+            *ips.entry(record["ip_address"].as_str().unwrap_or("-").to_string()).or_default() += 1;
+        }
+        self.top_fingerprints = top(fingerprints).into_iter().map(|((fingerprint, guess), count)| (fingerprint, guess, count)).collect();
+        self.top_ips = top(ips);
+        Ok(())
+    }
+}
+
+fn top<K: Ord>(counts: HashMap<K, usize>) -> Vec<(K, usize)> {
+    let mut counts: Vec<(K, usize)> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts.truncate(TOP_ROWS);
+    counts
+}
+
+fn draw(frame: &mut Frame, url: &str, view: &View) {
+    let [header, body] = Layout::vertical([Constraint::Length(3), Constraint::Min(0)]).areas(frame.area());
+    let [left, right] = Layout::horizontal([Constraint::Percentage(65), Constraint::Percentage(35)]).areas(body);
+
+    let rate = |rate: Option<f64>| rate.map_or("-".to_string(), |rate| format!("{:.0}", rate));
+    let status = match &view.error {
+        Some(error) => format!("{}: {}", url, error),
+        None => format!(
+            "{}   packets/s {}   kernel drops/s {}   fingerprints seen {}   (q to quit)",
+            url,
+            rate(view.packets_per_second),
+            rate(view.drops_per_second),
+            view.fingerprints_seen
+        ),
+    };
+    frame.render_widget(Paragraph::new(status).block(Block::default().borders(Borders::ALL).title("muonfp top")), header);
+
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let rows = view
+        .top_fingerprints
+        .iter()
+        .map(|(fingerprint, guess, count)| Row::new(vec![count.to_string(), fingerprint.clone(), guess.clone()]));
+    let fingerprints = Table::new(rows, [Constraint::Length(6), Constraint::Percentage(60), Constraint::Percentage(40)])
+        .header(Row::new(vec!["count", "fingerprint", "guess"]).style(bold))
+        .block(Block::default().borders(Borders::ALL).title(format!("Top fingerprints (last {})", TOP_WINDOW)));
+    frame.render_widget(fingerprints, left);
+
+    let rows = view.top_ips.iter().map(|(ip, count)| Row::new(vec![count.to_string(), ip.clone()]));
+    let ips = Table::new(rows, [Constraint::Length(6), Constraint::Min(15)])
+        .header(Row::new(vec!["count", "source"]).style(bold))
+        .block(Block::default().borders(Borders::ALL).title("Top sources"));
+    frame.render_widget(ips, right);
+}
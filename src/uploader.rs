@@ -0,0 +1,168 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use chrono::Utc;
+use config::Config;
+use log::{info, warn, error};
+
+mod s3;
+mod gcs;
+mod azure;
+#[cfg(feature = "sftp")]
+mod sftp;
+
+use s3::S3Shipper;
+use gcs::GcsShipper;
+use azure::AzureShipper;
+#[cfg(feature = "sftp")]
+use sftp::SftpShipper;
+
+const UPLOAD_ATTEMPTS: u32 = 3;
+
+/// A remote destination for completed rotation files.
+pub trait Shipper: Send {
+    /// Human readable location of `key`, used in log messages.
+    fn describe(&self, key: &str) -> String;
+
+    /// Copies the file at `path` to `key` on the remote side.
+    fn ship(&self, path: &Path, key: &str) -> io::Result<()>;
+}
+
+/// Ships completed rotation files from a background thread so the capture
+/// loop never waits on the network.
+pub struct Uploader {
+    tx: Option<Sender<PathBuf>>,
+    handle: Option<JoinHandle<()>>,
+    shutdown_timeout: Duration,
+}
+
+struct UploadOptions {
+    prefix: String,
+    hostname: String,
+    delete_after_upload: bool,
+}
+
+impl Uploader {
+    /// Builds the uploader from the `[upload]` section. Returns `None` when
+    /// uploads are not configured.
+    pub fn from_settings(settings: &Config, hostname: &str) -> Result<Option<Self>, Box<dyn std::error::Error>> {
+        let backend = match settings.get_string("upload.backend") {
+            Ok(backend) => backend,
+            // Older configs only set a bucket, which always meant S3
+            Err(_) if settings.get_string("upload.bucket").is_ok() => "s3".to_string(),
+            Err(_) => return Ok(None),
+        };
+        let shipper: Box<dyn Shipper> = match backend.as_str() {
+            "s3" => Box::new(S3Shipper::from_settings(settings)?),
+            "gcs" => Box::new(GcsShipper::from_settings(settings)?),
+            "azure" => Box::new(AzureShipper::from_settings(settings)?),
+            #[cfg(feature = "sftp")]
+            "sftp" => Box::new(SftpShipper::from_settings(settings)?),
+            #[cfg(not(feature = "sftp"))]
+            "sftp" => return Err("SFTP upload requires building with --features sftp".into()),
+            other => return Err(format!("Unknown upload.backend: {}", other).into()),
+        };
+        let options = UploadOptions {
+            prefix: settings.get_string("upload.prefix").unwrap_or_else(|_| "{hostname}/{kind}/".to_string()),
+            hostname: hostname.to_string(),
+            delete_after_upload: settings.get_bool("upload.delete_after_upload").unwrap_or(false),
+        };
+        let shutdown_timeout = Duration::from_secs(settings.get_int("upload.shutdown_timeout").unwrap_or(60).max(0) as u64);
+        Ok(Some(Uploader::new(shipper, options, shutdown_timeout)))
+    }
+
+    fn new(shipper: Box<dyn Shipper>, options: UploadOptions, shutdown_timeout: Duration) -> Self {
+        let (tx, rx) = mpsc::channel::<PathBuf>();
+        let handle = thread::spawn(move || {
+            for path in rx {
+                upload_with_retry(shipper.as_ref(), &options, &path);
+            }
+        });
+        Uploader { tx: Some(tx), handle: Some(handle), shutdown_timeout }
+    }
+
+    /// Returns a handle for queueing completed files.
+    pub fn sender(&self) -> Sender<PathBuf> {
+        self.tx.clone().expect("uploader is running")
+    }
+
+    /// How long `finish` waits for queued uploads, which the shutdown
+    /// watchdog allows for on top of `shutdown_timeout`.
+    pub fn shutdown_timeout(&self) -> Duration {
+        self.shutdown_timeout
+    }
+
+    /// Uploads everything already queued and stops the background thread,
+    /// giving up after `upload.shutdown_timeout`. Files not uploaded by then
+    /// stay on disk.
+    pub fn finish(&mut self) {
+        self.tx.take();
+        let Some(handle) = self.handle.take() else { return };
+        let deadline = Instant::now() + self.shutdown_timeout;
+        while !handle.is_finished() {
+            if Instant::now() >= deadline {
+                warn!("Uploads still pending after {}s, leaving the remaining files on disk", self.shutdown_timeout.as_secs());
+                return;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        let _ = handle.join();
+    }
+}
+
+fn upload_with_retry(shipper: &dyn Shipper, options: &UploadOptions, path: &Path) {
+    let key = object_key(&options.prefix, &options.hostname, path);
+    for attempt in 1..=UPLOAD_ATTEMPTS {
+        match shipper.ship(path, &key) {
+            Ok(()) => {
+                info!("Uploaded {} to {}", path.display(), shipper.describe(&key));
+                if options.delete_after_upload {
+                    if let Err(e) = std::fs::remove_file(path) {
+                        warn!("Failed to delete {} after upload: {}", path.display(), e);
+                    }
+                }
+                return;
+            }
+            Err(e) if attempt < UPLOAD_ATTEMPTS => {
+                warn!("Upload of {} failed (attempt {}): {}", path.display(), attempt, e);
+                thread::sleep(Duration::from_secs(5 * attempt as u64));
+            }
+            Err(e) => error!("Giving up on upload of {}: {}", path.display(), e),
+        }
+    }
+}
+
+// Expands `{hostname}`, `{kind}` (file extension) and `{year}`/`{month}`/`{day}`
+// in the configured prefix and appends the file name.
+fn object_key(prefix: &str, hostname: &str, path: &Path) -> String {
+    let now = Utc::now();
+    let kind = path.extension().map(|e| e.to_string_lossy().into_owned()).unwrap_or_default();
+    let file_name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let prefix = prefix
+        .replace("{hostname}", hostname)
+        .replace("{kind}", &kind)
+        .replace("{year}", &now.format("%Y").to_string())
+        .replace("{month}", &now.format("%m").to_string())
+        .replace("{day}", &now.format("%d").to_string());
+    format!("{}{}", prefix, file_name)
+}
+
+// Percent-encodes everything except unreserved characters (and '/' unless
+// `encode_slash` is set, as needed for query parameters).
+fn uri_encode(value: &str, encode_slash: bool) -> String {
+    let mut encoded = String::new();
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(byte as char),
+            b'/' if !encode_slash => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+fn http_error(e: ureq::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, e.to_string())
+}
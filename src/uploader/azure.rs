@@ -0,0 +1,53 @@
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use config::Config;
+use super::{Shipper, uri_encode, http_error};
+
+pub struct AzureShipper {
+    account: String,
+    container: String,
+    sas_token: String,
+}
+
+impl AzureShipper {
+    /// Authenticates with a container SAS token (needs create/write permission).
+    pub fn from_settings(settings: &Config) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(AzureShipper {
+            account: settings.get_string("upload.account")?,
+            container: settings.get_string("upload.container")?,
+            sas_token: settings
+                .get_string("upload.sas_token")
+                .ok()
+                .or_else(|| std::env::var("AZURE_STORAGE_SAS_TOKEN").ok())
+                .ok_or("upload.sas_token or AZURE_STORAGE_SAS_TOKEN is required for Azure upload")?
+                .trim_start_matches('?')
+                .to_string(),
+        })
+    }
+}
+
+impl Shipper for AzureShipper {
+    fn describe(&self, key: &str) -> String {
+        format!("https://{}.blob.core.windows.net/{}/{}", self.account, self.container, key)
+    }
+
+    fn ship(&self, path: &Path, key: &str) -> io::Result<()> {
+        let file = File::open(path)?;
+        let length = file.metadata()?.len();
+        let url = format!(
+            "https://{}.blob.core.windows.net/{}/{}?{}",
+            self.account,
+            self.container,
+            uri_encode(key, false),
+            self.sas_token
+        );
+        ureq::put(&url)
+            .set("x-ms-blob-type", "BlockBlob")
+            .set("x-ms-version", "2021-08-06")
+            .set("Content-Length", &length.to_string())
+            .send(file)
+            .map_err(http_error)?;
+        Ok(())
+    }
+}
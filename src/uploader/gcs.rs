@@ -0,0 +1,82 @@
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use config::Config;
+use serde::Deserialize;
+use super::{Shipper, uri_encode, http_error};
+
+const METADATA_TOKEN_URL: &str =
+    "http://metadata.google.internal/computeMetadata/v1/instance/service-accounts/default/token";
+
+#[derive(Deserialize)]
+struct MetadataToken {
+    access_token: String,
+    expires_in: u64,
+}
+
+pub struct GcsShipper {
+    bucket: String,
+    static_token: Option<String>,
+    cached_token: Mutex<Option<(String, Instant)>>,
+}
+
+impl GcsShipper {
+    /// Uses `upload.token` (or `GOOGLE_OAUTH_ACCESS_TOKEN`) when set, otherwise
+    /// fetches tokens for the instance service account from the GCE metadata server.
+    pub fn from_settings(settings: &Config) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(GcsShipper {
+            bucket: settings.get_string("upload.bucket")?,
+            static_token: settings
+                .get_string("upload.token")
+                .ok()
+                .or_else(|| std::env::var("GOOGLE_OAUTH_ACCESS_TOKEN").ok()),
+            cached_token: Mutex::new(None),
+        })
+    }
+
+    fn access_token(&self) -> io::Result<String> {
+        if let Some(token) = &self.static_token {
+            return Ok(token.clone());
+        }
+        let mut cached = self.cached_token.lock().unwrap();
+        if let Some((token, expires)) = cached.as_ref() {
+            if Instant::now() < *expires {
+                return Ok(token.clone());
+            }
+        }
+        let token: MetadataToken = ureq::get(METADATA_TOKEN_URL)
+            .set("Metadata-Flavor", "Google")
+            .call()
+            .map_err(http_error)?
+            .into_json()?;
+        // Refresh a minute early so a token never expires mid-upload
+        let expires = Instant::now() + Duration::from_secs(token.expires_in.saturating_sub(60));
+        *cached = Some((token.access_token.clone(), expires));
+        Ok(token.access_token)
+    }
+}
+
+impl Shipper for GcsShipper {
+    fn describe(&self, key: &str) -> String {
+        format!("gs://{}/{}", self.bucket, key)
+    }
+
+    fn ship(&self, path: &Path, key: &str) -> io::Result<()> {
+        let file = File::open(path)?;
+        let length = file.metadata()?.len();
+        let url = format!(
+            "https://storage.googleapis.com/upload/storage/v1/b/{}/o?uploadType=media&name={}",
+            uri_encode(&self.bucket, true),
+            uri_encode(key, true)
+        );
+        ureq::post(&url)
+            .set("Authorization", &format!("Bearer {}", self.access_token()?))
+            .set("Content-Type", "application/octet-stream")
+            .set("Content-Length", &length.to_string())
+            .send(file)
+            .map_err(http_error)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,119 @@
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use chrono::Utc;
+use config::Config;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use super::{Shipper, uri_encode, http_error};
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub struct S3Shipper {
+    bucket: String,
+    region: String,
+    endpoint: Option<String>,
+    access_key: String,
+    secret_key: String,
+    session_token: Option<String>,
+}
+
+impl S3Shipper {
+    /// Credentials fall back to the standard AWS environment variables.
+    pub fn from_settings(settings: &Config) -> Result<Self, Box<dyn std::error::Error>> {
+        let setting_or_env = |key: &str, var: &str| {
+            settings.get_string(key).ok().or_else(|| std::env::var(var).ok())
+        };
+        Ok(S3Shipper {
+            bucket: settings.get_string("upload.bucket")?,
+            region: settings.get_string("upload.region").unwrap_or_else(|_| "us-east-1".to_string()),
+            endpoint: settings.get_string("upload.endpoint").ok(),
+            access_key: setting_or_env("upload.access_key", "AWS_ACCESS_KEY_ID")
+                .ok_or("upload.access_key or AWS_ACCESS_KEY_ID is required for S3 upload")?,
+            secret_key: setting_or_env("upload.secret_key", "AWS_SECRET_ACCESS_KEY")
+                .ok_or("upload.secret_key or AWS_SECRET_ACCESS_KEY is required for S3 upload")?,
+            session_token: setting_or_env("upload.session_token", "AWS_SESSION_TOKEN"),
+        })
+    }
+}
+
+impl Shipper for S3Shipper {
+    fn describe(&self, key: &str) -> String {
+        format!("s3://{}/{}", self.bucket, key)
+    }
+
+    // PUTs the file with an AWS Signature Version 4 signed request. The payload
+    // is streamed unsigned, which S3 accepts over HTTPS.
+    fn ship(&self, path: &Path, key: &str) -> io::Result<()> {
+        let file = File::open(path)?;
+        let length = file.metadata()?.len();
+
+        let (host, canonical_uri) = match &self.endpoint {
+            Some(endpoint) => {
+                let host = endpoint.trim_start_matches("https://").trim_start_matches("http://").trim_end_matches('/');
+                (host.to_string(), format!("/{}/{}", self.bucket, uri_encode(key, false)))
+            }
+            None => (format!("{}.s3.{}.amazonaws.com", self.bucket, self.region), format!("/{}", uri_encode(key, false))),
+        };
+        let scheme = match &self.endpoint {
+            Some(endpoint) if endpoint.starts_with("http://") => "http",
+            _ => "https",
+        };
+
+        let now = Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let payload_hash = "UNSIGNED-PAYLOAD";
+
+        let mut headers = vec![
+            ("host", host.clone()),
+            ("x-amz-content-sha256", payload_hash.to_string()),
+            ("x-amz-date", amz_date.clone()),
+        ];
+        if let Some(token) = &self.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+        let canonical_headers: String = headers.iter().map(|(k, v)| format!("{}:{}\n", k, v.trim())).collect();
+        let signed_headers = headers.iter().map(|(k, _)| *k).collect::<Vec<_>>().join(";");
+
+        let canonical_request = format!(
+            "PUT\n{}\n\n{}\n{}\n{}",
+            canonical_uri, canonical_headers, signed_headers, payload_hash
+        );
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex::encode(Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let signing_key = [date.as_str(), self.region.as_str(), "s3", "aws4_request"]
+            .iter()
+            .fold(format!("AWS4{}", self.secret_key).into_bytes(), |key, part| hmac_sha256(&key, part.as_bytes()));
+        let signature = hex::encode(hmac_sha256(&signing_key, string_to_sign.as_bytes()));
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.access_key, scope, signed_headers, signature
+        );
+
+        let mut request = ureq::put(&format!("{}://{}{}", scheme, host, canonical_uri))
+            .set("Authorization", &authorization)
+            .set("Content-Length", &length.to_string());
+        for (name, value) in &headers {
+            if *name != "host" {
+                request = request.set(name, value);
+            }
+        }
+        request
+            .send(file)
+            .map_err(http_error)?;
+        Ok(())
+    }
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
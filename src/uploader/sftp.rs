@@ -0,0 +1,119 @@
+use std::fs::File;
+use std::io;
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD_NO_PAD as BASE64;
+use config::Config;
+use ssh2::{CheckResult, HashType, KnownHostFileKind, Session};
+use super::Shipper;
+
+pub struct SftpShipper {
+    host: String,
+    port: u16,
+    user: String,
+    key_file: Option<PathBuf>,
+    password: Option<String>,
+    known_hosts: Option<PathBuf>,
+    host_fingerprint: Option<String>,
+    remote_dir: PathBuf,
+}
+
+impl SftpShipper {
+    pub fn from_settings(settings: &Config) -> Result<Self, Box<dyn std::error::Error>> {
+        let key_file = settings.get_string("upload.key_file").ok().map(PathBuf::from);
+        let password = settings.get_string("upload.password").ok();
+        if key_file.is_none() && password.is_none() {
+            return Err("upload.key_file or upload.password is required for SFTP upload".into());
+        }
+        let known_hosts = settings.get_string("upload.known_hosts").ok().map(PathBuf::from);
+        let host_fingerprint = settings.get_string("upload.host_fingerprint").ok();
+        if known_hosts.is_none() && host_fingerprint.is_none() {
+            return Err("upload.known_hosts or upload.host_fingerprint is required for SFTP upload".into());
+        }
+        Ok(SftpShipper {
+            host: settings.get_string("upload.host")?,
+            port: settings.get_int("upload.port").unwrap_or(22) as u16,
+            user: settings.get_string("upload.user")?,
+            key_file,
+            password,
+            known_hosts,
+            host_fingerprint,
+            remote_dir: PathBuf::from(settings.get_string("upload.remote_dir").unwrap_or_else(|_| ".".to_string())),
+        })
+    }
+
+    fn connect(&self) -> io::Result<Session> {
+        let tcp = TcpStream::connect((self.host.as_str(), self.port))?;
+        let mut session = Session::new()?;
+        session.set_tcp_stream(tcp);
+        session.handshake()?;
+        self.verify_host_key(&session)?;
+        match &self.key_file {
+            Some(key_file) => session.userauth_pubkey_file(&self.user, None, key_file, None)?,
+            None => session.userauth_password(&self.user, self.password.as_deref().unwrap_or_default())?,
+        }
+        Ok(session)
+    }
+
+    // Refuses the server unless its key is in known_hosts or matches the
+    // configured SHA256 fingerprint, checked before any credentials are sent
+    fn verify_host_key(&self, session: &Session) -> io::Result<()> {
+        if let Some(fingerprint) = &self.host_fingerprint {
+            let hash = session
+                .host_key_hash(HashType::Sha256)
+                .ok_or_else(|| host_key_error(format!("no host key from {}", self.host)))?;
+            let actual = format!("SHA256:{}", BASE64.encode(hash));
+            if actual != fingerprint.trim() {
+                return Err(host_key_error(format!("host key {} of {} does not match upload.host_fingerprint", actual, self.host)));
+            }
+        }
+        if let Some(path) = &self.known_hosts {
+            let mut known_hosts = session.known_hosts()?;
+            known_hosts.read_file(path, KnownHostFileKind::OpenSSH)?;
+            let (key, _) = session
+                .host_key()
+                .ok_or_else(|| host_key_error(format!("no host key from {}", self.host)))?;
+            match known_hosts.check_port(&self.host, self.port, key) {
+                CheckResult::Match => {}
+                CheckResult::Mismatch => {
+                    return Err(host_key_error(format!("host key of {} does not match {}", self.host, path.display())))
+                }
+                CheckResult::NotFound | CheckResult::Failure => {
+                    return Err(host_key_error(format!("{} is not in {}", self.host, path.display())))
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn host_key_error(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::PermissionDenied, message)
+}
+
+impl Shipper for SftpShipper {
+    fn describe(&self, key: &str) -> String {
+        format!("sftp://{}@{}{}", self.user, self.host, self.remote_dir.join(key).display())
+    }
+
+    fn ship(&self, path: &Path, key: &str) -> io::Result<()> {
+        let session = self.connect()?;
+        let sftp = session.sftp()?;
+        let remote_path = self.remote_dir.join(key);
+
+        // Create missing directories from the prefix; existing ones just fail
+        if let Some(parent) = remote_path.parent() {
+            let mut dir = PathBuf::new();
+            for component in parent.components() {
+                dir.push(component);
+                let _ = sftp.mkdir(&dir, 0o755);
+            }
+        }
+
+        let mut local = File::open(path)?;
+        let mut remote = sftp.create(&remote_path)?;
+        io::copy(&mut local, &mut remote)?;
+        Ok(())
+    }
+}